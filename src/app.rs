@@ -1,30 +1,72 @@
 extern crate gl;
 extern crate glfw;
 
+use crate::buffer::Buffer;
+use crate::caret::{next_blink_in, Caret};
+use crate::config::Config;
+use crate::cursor::Point;
+use crate::diff::{diff_lines, diff_summary};
+use crate::display_map::DisplayMap;
+use crate::encoding::FileEncoding;
+use crate::file_io::{create_private_dir, write_atomically, write_private, FileStamp};
+use crate::folding::{enclosing_fold, Folds};
+use crate::hex::{self, HexBuffer};
 use crate::matrix;
+use crate::minimap::{Minimap, MinimapLayout, MINIMAP_CHAR_WIDTH, MINIMAP_COLUMNS};
+use crate::prompt::{AfterSave, Prompt, Question};
+use crate::recovery::leftover_untitled_files;
 use crate::rect;
+use crate::scrollbar::{scroll_at, thumb, Scrollbar, MIN_THUMB_HEIGHT, SCROLLBAR_WIDTH};
+use crate::status_bar::{self, language_for, window_title, Picker};
 use crate::text;
 use crate::{font, process_keyboard::KeyBoardShortcuts};
 
 use crate::scroll;
 use scroll::Scroll;
 
-use font::font_renderer::FontRenderer;
+use font::{font_lookup::load_fonts, font_renderer::FontRenderer};
 use rect::rect_renderer::RectRenderer;
 
-use std::fs::File;
+use glfw::Key;
+
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::time::Instant;
+
+pub const MIN_FONT_SIZE: u32 = 6;
+pub const MAX_FONT_SIZE: u32 = 72;
+// Space above and below the status bar text in logical pixels
+pub const STATUS_BAR_PADDING: f32 = 3.0;
 
 pub struct App {
-    pub file_path: String,
+    pub config: Config,
     pub font_renderer: FontRenderer,
     pub rect_renderer: RectRenderer,
     pub projection: matrix::Matrix,
-    pub scroll: Scroll,
+    // framebuffer pixels per logical pixel, 2.0 on a typical HiDPI display
+    pub content_scale: f32,
     pub should_rerender: bool,
     pub window: glfw::Window,
     pub glfw: glfw::Glfw,
-    pub text: text::Text,
     pub shortcuts: KeyBoardShortcuts,
+    // the buffer in the active tab, everything edits and renders this one
+    pub buffer: Buffer,
+    // buffers in the other tabs in tab order, the active one goes at `active`
+    pub background: Vec<Buffer>,
+    pub active: usize,
+    // paths of closed tabs and where they were, the last closed one last
+    closed: Vec<(String, usize)>,
+    // the left button went down on the minimap and is still held
+    pub minimap_drag: bool,
+    pub scrollbar: Scrollbar,
+    pub caret: Caret,
+    // status bar picker that is open
+    pub picker: Option<Picker>,
+    // question waiting for an answer, takes all keys until answered
+    pub prompt: Option<Prompt>,
+    // window title last set, to set it only when it changes
+    title: String,
 }
 
 pub fn projection_from_size(width: i32, height: i32) -> matrix::Matrix {
@@ -38,45 +80,1220 @@ pub fn projection_from_size(width: i32, height: i32) -> matrix::Matrix {
     })
 }
 
+// Range of screen rows, which are text lines unless soft wrap is on
 pub fn visible_range(app: &App, scroll_y: f32) -> std::ops::Range<usize> {
-    let (_, y_size) = app.window.get_framebuffer_size();
-
     scroll_y as usize
-        ..scroll_y as usize + ((y_size as f32) / app.font_renderer.advance_height).ceil() as usize
+        ..scroll_y as usize + (text_height(app) / app.font_renderer.advance_height).ceil() as usize
 }
 
 pub fn visible_range_x(app: &App, scroll_x: f32) -> std::ops::Range<usize> {
+    scroll_x as usize
+        ..scroll_x as usize + (text_width(app) / app.font_renderer.char_width).ceil() as usize
+}
+
+// Width left for text between the gutter and the minimap
+fn text_width(app: &App) -> f32 {
     let (x_size, _) = app.window.get_framebuffer_size();
+    x_size as f32 - text_left(app) - minimap_width(app) - scrollbar_width(app)
+}
 
-    scroll_x as usize
-        ..scroll_x as usize + ((x_size as f32) / app.font_renderer.char_width).ceil() as usize
+pub fn status_bar_height(app: &App) -> f32 {
+    if !app.config.status_bar {
+        return 0.0;
+    }
+    app.font_renderer.advance_height + scaled(app, STATUS_BAR_PADDING) * 2.0
+}
+
+pub fn tab_bar_height(app: &App) -> f32 {
+    if !app.config.tab_bar {
+        return 0.0;
+    }
+    app.font_renderer.advance_height + scaled(app, STATUS_BAR_PADDING) * 2.0
+}
+
+// Height left for text between the tab bar and the status bar. Everything
+// under the tab bar is drawn and hit tested from the bottom of the tab bar
+// down.
+pub fn text_height(app: &App) -> f32 {
+    let (_, y_size) = app.window.get_framebuffer_size();
+    y_size as f32 - tab_bar_height(app) - status_bar_height(app)
+}
+
+// Screen x where text starts, right after the gutter
+pub fn text_left(app: &App) -> f32 {
+    app.font_renderer
+        .gutter_width(app.buffer.text.get_text().len_lines())
+}
+
+// Keeps the cursor line at the same height on screen after the line height
+// changed from `old_height`
+fn keep_cursor_line(app: &mut App, old_height: f32) {
+    let old_row = cursor_row(app) as f32;
+    let lines_above = old_row - app.buffer.scroll.target_scroll.y;
+
+    sync_display_map(app);
+    let cursor_y = cursor_row(app) as f32;
+    let new_scroll = cursor_y - lines_above * old_height / app.font_renderer.advance_height;
+
+    scroll::jump_to(&mut app.buffer.scroll, new_scroll.max(0.0).floor());
+    app.should_rerender = true;
+}
+
+pub fn set_font_size(app: &mut App, font_size: u32) {
+    let font_size = font_size.max(MIN_FONT_SIZE).min(MAX_FONT_SIZE);
+    let old_height = app.font_renderer.advance_height;
+
+    app.font_renderer.set_font_size(font_size);
+    keep_cursor_line(app, old_height);
+}
+
+// Called when the window moves to a monitor with a different scale
+pub fn set_content_scale(app: &mut App, content_scale: f32) {
+    if content_scale <= 0.0 || content_scale == app.content_scale {
+        return;
+    }
+    let old_height = app.font_renderer.advance_height;
+
+    app.content_scale = content_scale;
+    app.font_renderer.set_content_scale(content_scale);
+    keep_cursor_line(app, old_height);
+}
+
+pub fn toggle_soft_wrap(app: &mut App) {
+    let old_height = app.font_renderer.advance_height;
+    app.buffer.soft_wrap = !app.buffer.soft_wrap;
+    keep_cursor_line(app, old_height);
+}
+
+fn wrap_width(app: &App) -> Option<usize> {
+    if !app.buffer.soft_wrap {
+        return None;
+    }
+
+    match app.config.wrap_column {
+        Some(column) => Some(column),
+        None => Some(((text_width(app) / app.font_renderer.char_width) as usize).max(1)),
+    }
+}
+
+// Rebuilds screen rows when the text, folds, the window or the font size
+// changed
+pub fn sync_display_map(app: &mut App) {
+    let edits = app.buffer.text.take_edits();
+    for &edit in edits.iter() {
+        app.buffer.folds.apply_edit(edit);
+    }
+    if app.config.minimap {
+        app.buffer.minimap.update(
+            app.buffer.text.get_text(),
+            app.buffer.text.revision(),
+            &edits,
+        );
+    }
+    // the cursor can end up inside a fold by moving over it horizontally, by
+    // undo and so on, open whatever hides it
+    let cursor_line = app.buffer.text.get_cursor().position.y as usize;
+    app.buffer.folds.reveal(cursor_line);
+
+    let width = wrap_width(app);
+    let revision = app.buffer.text.revision();
+    app.buffer.display_map.update(
+        app.buffer.text.get_text(),
+        revision,
+        &edits,
+        width,
+        &app.buffer.folds,
+    );
+}
+
+// Folds the block the cursor is in and moves the cursor to its first line
+pub fn fold_at_cursor(app: &mut App) {
+    let line = app.buffer.text.get_cursor().position.y as usize;
+    let fold = match enclosing_fold(app.buffer.text.get_text(), line) {
+        Some(fold) => fold,
+        None => return,
+    };
+
+    app.buffer.folds.add(fold);
+    if fold.start != line {
+        let cursor = app.buffer.text.get_cursor();
+        cursor.position.y = fold.start as i64;
+        cursor.position.x = 0;
+        cursor.remembered_x = 0;
+        cursor.selection = None;
+    }
+    app.should_rerender = true;
+}
+
+// Unfolds the fold on the cursor line, or the one the cursor is in
+pub fn unfold_at_cursor(app: &mut App) {
+    let line = app.buffer.text.get_cursor().position.y as usize;
+    if !app.buffer.folds.remove(line) {
+        if let Some(fold) = enclosing_fold(app.buffer.text.get_text(), line) {
+            app.buffer.folds.remove(fold.start);
+        }
+    }
+    app.should_rerender = true;
+}
+
+pub fn cursor_row(app: &mut App) -> usize {
+    if let Some(hex) = &app.buffer.hex {
+        return hex.cursor_row();
+    }
+    let position = app.buffer.text.get_cursor().position;
+    app.buffer.display_map.point_to_row(position)
+}
+
+pub fn minimap_width(app: &App) -> f32 {
+    if !app.config.minimap || app.buffer.hex.is_some() {
+        return 0.0;
+    }
+    MINIMAP_COLUMNS as f32 * MINIMAP_CHAR_WIDTH * app.content_scale
+}
+
+pub fn minimap_layout(app: &App, scroll_y: f32) -> MinimapLayout {
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let visible = visible_range(app, scroll_y);
+    let row_count = app.buffer.display_map.row_count(app.buffer.text.get_text());
+    let max_scroll = row_count.saturating_sub(visible.end - visible.start);
+
+    MinimapLayout::new(
+        x_size as f32 - minimap_width(app) - scrollbar_width(app),
+        app.content_scale,
+        text_height(app),
+        app.buffer.minimap.line_count(),
+        scroll_y,
+        max_scroll as f32,
+    )
+}
+
+pub fn is_on_minimap(app: &App, x: f32) -> bool {
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let right = x_size as f32 - scrollbar_width(app);
+    app.config.minimap && x >= right - minimap_width(app) && x < right
+}
+
+// Scrolls so the line under screen `y` on the minimap is in the middle of the
+// window
+pub fn scroll_to_minimap(app: &mut App, y: f32) {
+    sync_display_map(app);
+    let layout = minimap_layout(app, app.buffer.scroll.current_scroll.y);
+    let line = layout.y_to_line(y, app.buffer.minimap.line_count());
+    let visible = visible_range(app, app.buffer.scroll.current_scroll.y);
+
+    let row = app.buffer.display_map.line_to_row(line) as f32;
+    let target = (row - ((visible.end - visible.start) / 2) as f32).max(0.0);
+    scroll::scroll_to(&mut app.buffer.scroll, target.floor());
+    app.should_rerender = true;
+}
+
+pub fn scrollbar_width(app: &App) -> f32 {
+    if !app.config.scrollbar || app.buffer.hex.is_some() {
+        return 0.0;
+    }
+    scaled(app, SCROLLBAR_WIDTH)
+}
+
+pub fn is_on_scrollbar(app: &App, x: f32) -> bool {
+    let (x_size, _) = app.window.get_framebuffer_size();
+    app.config.scrollbar && x >= x_size as f32 - scrollbar_width(app)
+}
+
+// Top and height of the thumb in framebuffer pixels
+pub fn scrollbar_thumb(app: &App) -> (f32, f32) {
+    let visible = visible_range(app, app.buffer.scroll.current_scroll.y);
+    thumb(
+        text_height(app),
+        scaled(app, MIN_THUMB_HEIGHT),
+        app.buffer.display_map.row_count(app.buffer.text.get_text()),
+        visible.end - visible.start,
+        app.buffer.scroll.current_scroll.y,
+    )
+}
+
+// Grabs the thumb, clicking next to it moves the thumb there first
+pub fn press_scrollbar(app: &mut App, y: f32) {
+    sync_display_map(app);
+    let (thumb_y, thumb_height) = scrollbar_thumb(app);
+    let grab = if y >= thumb_y && y < thumb_y + thumb_height {
+        y - thumb_y
+    } else {
+        thumb_height / 2.0
+    };
+
+    app.scrollbar.drag = Some(grab);
+    drag_scrollbar(app, y);
+}
+
+pub fn drag_scrollbar(app: &mut App, y: f32) {
+    let grab = match app.scrollbar.drag {
+        Some(grab) => grab,
+        None => return,
+    };
+    let (_, thumb_height) = scrollbar_thumb(app);
+    let visible = visible_range(app, app.buffer.scroll.current_scroll.y);
+
+    let scroll = scroll_at(
+        text_height(app),
+        thumb_height,
+        app.buffer.display_map.row_count(app.buffer.text.get_text()),
+        visible.end - visible.start,
+        y - grab,
+    );
+    scroll::jump_to(&mut app.buffer.scroll, scroll);
+    app.scrollbar.wake();
+    app.should_rerender = true;
+}
+
+// Seconds until something animates or blinks without any input, None when
+// the window can wait for events
+pub fn next_frame_in(app: &App) -> Option<f32> {
+    let mut next: Option<f32> = None;
+    let mut wake = |seconds: Option<f32>| {
+        if let Some(seconds) = seconds {
+            next = Some(next.map_or(seconds, |next| next.min(seconds)));
+        }
+    };
+
+    if app.config.scrollbar && app.config.scrollbar_fade {
+        wake(app.scrollbar.next_frame_in());
+    }
+    if app.config.cursor_blink && app.window.is_focused() {
+        wake(next_blink_in(app.caret.idle()));
+    }
+    if app.caret.is_moving() {
+        wake(Some(1.0 / 60.0));
+    }
+    if app.config.recovery {
+        let now = Instant::now();
+        wake(app.buffer.autosave.next_in(now));
+        for buffer in app.background.iter() {
+            wake(buffer.autosave.next_in(now));
+        }
+    }
+    next
+}
+
+// Shows `message` until it is dismissed
+pub fn show_message(app: &mut App, message: String) {
+    app.prompt = Some(Prompt::new(Question::Message, message, vec!["OK"]));
+    app.should_rerender = true;
+}
+
+// Writes the file, errors are shown instead, false when nothing was written
+fn write_file(app: &mut App, path: &str, bytes: &[u8]) -> bool {
+    match write_atomically(Path::new(path), bytes) {
+        Ok(()) => true,
+        Err(error) => {
+            let name = status_bar::file_text(path, false);
+            show_message(app, format!("Couldn't save {}: {}", name, error));
+            false
+        }
+    }
+}
+
+// Directory the file goes in when it doesn't exist yet
+fn missing_dir(path: &str) -> Option<&Path> {
+    let dir = Path::new(path).parent()?;
+    if dir == Path::new("") || dir.exists() {
+        None
+    } else {
+        Some(dir)
+    }
+}
+
+// Asks for a path when there is none, a missing directory or a file that
+// changed on disk since it was loaded. False when nothing was written.
+pub fn save(app: &mut App) -> bool {
+    if app.buffer.is_untitled() {
+        save_as(app);
+        return false;
+    }
+    let path = app.buffer.file_path.clone();
+    save_checked(app, path)
+}
+
+// Writes to `path` unless its directory is missing or the buffer's own file
+// changed on disk, which is asked about first. The buffer only belongs to
+// `path` once written.
+fn save_checked(app: &mut App, path: String) -> bool {
+    if let Some(dir) = missing_dir(&path) {
+        let message = format!("{} doesn't exist, create it?", dir.display());
+        let mut prompt = Prompt::new(Question::CreateDirectory, message, vec!["Create", "Cancel"]);
+        prompt.path = Some(path);
+        app.prompt = Some(prompt);
+        app.should_rerender = true;
+        return false;
+    }
+    // another file was already agreed to be replaced
+    let stamp = FileStamp::of(Path::new(&path));
+    if path == app.buffer.file_path && stamp.is_some() && stamp != app.buffer.disk_stamp {
+        let name = status_bar::file_text(&path, false);
+        let mut prompt = Prompt::new(
+            Question::OverwriteChanged,
+            format!("{} changed on disk since it was loaded", name),
+            vec!["Overwrite", "Cancel"],
+        );
+        prompt.path = Some(path);
+        app.prompt = Some(prompt);
+        app.should_rerender = true;
+        return false;
+    }
+    save_anyway(app, path)
+}
+
+fn save_anyway(app: &mut App, path: String) -> bool {
+    if let Some(hex) = &app.buffer.hex {
+        let bytes = hex.bytes.clone();
+        if !write_file(app, &path, &bytes) {
+            return false;
+        }
+        set_file_path(app, path);
+        if let Some(hex) = &mut app.buffer.hex {
+            hex.mark_saved();
+        }
+        mark_loaded(app);
+        app.should_rerender = true;
+        return true;
+    }
+
+    let bytes = match app.buffer.text.to_bytes() {
+        Ok(bytes) => bytes,
+        Err(c) => {
+            let message = format!(
+                "{} can't store {:?}, save with another encoding",
+                app.buffer.text.encoding.name(),
+                c
+            );
+            show_message(app, message);
+            return false;
+        }
+    };
+    if !write_file(app, &path, &bytes) {
+        return false;
+    }
+    set_file_path(app, path);
+    app.buffer.text.mark_saved();
+    mark_loaded(app);
+    app.should_rerender = true;
+    true
+}
+
+// Saves every tab that has a path when the window loses focus. Nothing is
+// asked then, tabs that would need a question wait for an explicit save.
+pub fn save_on_focus_loss(app: &mut App) {
+    for index in 0..tab_count(app) {
+        // a question or an error is already waiting for an answer
+        if app.prompt.is_some() {
+            return;
+        }
+        let buffer = tab(app, index);
+        if buffer.is_untitled() || !buffer.is_modified() {
+            continue;
+        }
+        let path = buffer.file_path.clone();
+        let stamp = FileStamp::of(Path::new(&path));
+        if missing_dir(&path).is_some() || (stamp.is_some() && stamp != buffer.disk_stamp) {
+            continue;
+        }
+        with_tab(app, index, |app| save_anyway(app, path));
+    }
+}
+
+// Runs `f` with the tab at `index` as the active buffer, without showing it
+fn with_tab<T>(app: &mut App, index: usize, f: impl FnOnce(&mut App) -> T) -> T {
+    if index == app.active {
+        return f(app);
+    }
+    let position = if index > app.active { index - 1 } else { index };
+    mem::swap(&mut app.buffer, &mut app.background[position]);
+    let result = f(app);
+    mem::swap(&mut app.buffer, &mut app.background[position]);
+    result
+}
+
+// The buffer belongs to the file it was just written to
+fn set_file_path(app: &mut App, path: String) {
+    if path == app.buffer.file_path {
+        return;
+    }
+    // the recovery file is named after the path
+    if app.buffer.autosave.has_file {
+        remove_recovery(&mut app.buffer);
+    }
+    app.buffer.autosave.reset();
+    app.buffer.language = language_for(&path);
+    app.buffer.file_path = path;
+    app.buffer.watch();
+}
+
+// The buffer now matches what is on disk
+fn mark_loaded(app: &mut App) {
+    app.buffer.disk_stamp = FileStamp::of(Path::new(&app.buffer.file_path));
+    app.buffer.seen_stamp = app.buffer.disk_stamp;
+}
+
+pub fn save_as(app: &mut App) {
+    let mut prompt =
+        Prompt::with_input(Question::SaveAs, "Save as:".into(), vec!["Save", "Cancel"]);
+    prompt.input = Some(app.buffer.file_path.clone());
+    app.prompt = Some(prompt);
+    app.should_rerender = true;
+}
+
+// Saves to `path`, which the buffer belongs to once written. An existing file
+// other than the buffer's own is only replaced when `replace` says so.
+fn save_to(app: &mut App, path: String, replace: bool) -> bool {
+    if path.is_empty() {
+        return false;
+    }
+    if !replace && path != app.buffer.file_path && Path::new(&path).exists() {
+        let mut prompt = Prompt::with_input(
+            Question::ReplaceFile,
+            "Already exists, replace:".into(),
+            vec!["Replace", "Cancel"],
+        );
+        prompt.input = Some(path);
+        app.prompt = Some(prompt);
+        app.should_rerender = true;
+        return false;
+    }
+    save_checked(app, path)
+}
+
+// Opens an empty buffer in a new tab, it gets a path once saved
+pub fn new_file(app: &mut App) {
+    let index = app.active + 1;
+    open_tab(app, String::new(), index);
+}
+
+pub fn save_with_encoding(app: &mut App, encoding: FileEncoding) {
+    let previous = app.buffer.text.encoding;
+    app.buffer.text.encoding = encoding;
+    if !save(app) {
+        app.buffer.text.encoding = previous;
+    }
+}
+
+// Replaces the whole text, everything derived from the old one starts over
+pub fn set_text(app: &mut App, text: text::Text) {
+    app.buffer.text = text;
+    app.buffer.folds = Folds::new();
+    app.buffer.display_map = DisplayMap::new();
+    app.buffer.minimap = Minimap::new();
+    app.buffer.hex = None;
+    app.buffer.autosave.reset();
+    app.should_rerender = true;
+}
+
+// Switches between the text and the bytes it saves to, edits made in the hex
+// view replace the text when going back
+pub fn toggle_hex_view(app: &mut App) {
+    match app.buffer.hex.take() {
+        Some(hex) => {
+            if hex.has_edits() {
+                let mut text = text::Text::decode(&hex.bytes, app.buffer.text.encoding);
+                if hex.is_modified() {
+                    text.forget_saved();
+                }
+                set_text(app, text);
+            } else if !hex.is_modified() {
+                // saved from the hex view
+                app.buffer.text.mark_saved();
+            }
+        }
+        None => match app.buffer.text.to_bytes() {
+            Ok(bytes) => {
+                let saved = !app.buffer.text.is_modified();
+                app.buffer.hex = Some(HexBuffer::new(bytes, saved));
+            }
+            Err(c) => {
+                let message = format!("{} can't store {:?}", app.buffer.text.encoding.name(), c);
+                show_message(app, message);
+                return;
+            }
+        },
+    }
+    app.buffer.autosave.reset();
+    app.buffer.scroll = Scroll::new();
+    app.should_rerender = true;
+}
+
+pub fn find_bytes(app: &mut App) {
+    if app.buffer.hex.is_none() {
+        return;
+    }
+    app.prompt = Some(Prompt::with_input(
+        Question::FindBytes,
+        "Find bytes:".to_owned(),
+        vec!["Find", "Cancel"],
+    ));
+    app.should_rerender = true;
+}
+
+// Moves the hex cursor to the next match of the last pattern searched for
+pub fn find_next_bytes(app: &mut App) {
+    let hex = match &mut app.buffer.hex {
+        Some(hex) => hex,
+        None => return,
+    };
+    if hex.pattern.is_empty() {
+        return;
+    }
+    match hex.find(&hex.pattern) {
+        Some(offset) => hex.cursor = offset * 2,
+        None => {
+            let message = format!("{} not found", hex::pattern_text(&hex.pattern));
+            show_message(app, message);
+        }
+    }
+    app.should_rerender = true;
+}
+
+// Reads the file again decoding it as `encoding`, unsaved changes are lost
+// so they are asked about first
+pub fn reopen_with_encoding(app: &mut App, encoding: FileEncoding) {
+    if app.buffer.is_modified() {
+        app.prompt = Some(Prompt::new(
+            Question::ReopenWithEncoding(encoding),
+            format!("Reopening as {} discards unsaved changes", encoding.name()),
+            vec!["Reopen", "Cancel"],
+        ));
+        app.should_rerender = true;
+        return;
+    }
+
+    reload_as(app, encoding);
+}
+
+fn reload_as(app: &mut App, encoding: FileEncoding) {
+    match fs::read(&app.buffer.file_path) {
+        Ok(bytes) => {
+            set_text(app, text::Text::decode(&bytes, encoding));
+            mark_loaded(app);
+        }
+        Err(error) => {
+            let name = status_bar::file_text(&app.buffer.file_path, false);
+            show_message(app, format!("Couldn't read {}: {}", name, error));
+        }
+    }
+}
+
+pub fn update_title(app: &mut App) {
+    let modified = app.buffer.is_modified();
+    let title = window_title(&app.buffer.file_path, modified);
+    if title != app.title {
+        app.window.set_title(&title);
+        app.title = title;
+    }
+}
+
+// The window was asked to close, unsaved changes keep it open until the
+// prompt is answered. Tabs with unsaved changes are asked about one by one.
+pub fn request_close(app: &mut App) {
+    let index = match (0..tab_count(app)).find(|&index| tab(app, index).is_modified()) {
+        Some(index) => index,
+        None => return,
+    };
+
+    app.window.set_should_close(false);
+    switch_tab(app, index);
+    let name = status_bar::file_text(&app.buffer.file_path, false);
+    app.prompt = Some(Prompt::new(
+        Question::CloseUnsaved,
+        format!("Save changes to {} before closing?", name),
+        vec!["Save", "Discard", "Cancel"],
+    ));
+    app.should_rerender = true;
+}
+
+pub fn tab_count(app: &App) -> usize {
+    app.background.len() + 1
+}
+
+// Buffer in the tab at `index`
+pub fn tab(app: &mut App, index: usize) -> &mut Buffer {
+    if index == app.active {
+        &mut app.buffer
+    } else if index < app.active {
+        &mut app.background[index]
+    } else {
+        &mut app.background[index - 1]
+    }
+}
+
+// Puts `buffer` in a new tab at `index` and makes it the active one
+fn insert_tab(app: &mut App, buffer: Buffer, index: usize) {
+    let previous = mem::replace(&mut app.buffer, buffer);
+    app.background.insert(app.active, previous);
+    app.active = index;
+}
+
+// Opens `file_path` in a new tab at `index`, an empty path opens a new file
+pub fn open_tab(app: &mut App, file_path: String, index: usize) {
+    let (buffer, read_error) = Buffer::open(file_path, &app.config);
+    insert_tab(app, buffer, index.min(tab_count(app)));
+    show_buffer(app);
+    if let Some(error) = read_error {
+        let name = status_bar::file_text(&app.buffer.file_path, false);
+        show_message(app, format!("Couldn't read {}: {}", name, error));
+    }
+}
+
+pub fn switch_tab(app: &mut App, index: usize) {
+    if index == app.active || index >= tab_count(app) {
+        return;
+    }
+    let position = if index > app.active { index - 1 } else { index };
+    let buffer = app.background.remove(position);
+    let previous = mem::replace(&mut app.buffer, buffer);
+    app.background.insert(app.active, previous);
+    app.active = index;
+    show_buffer(app);
+}
+
+pub fn next_tab(app: &mut App) {
+    let index = (app.active + 1) % tab_count(app);
+    switch_tab(app, index);
+}
+
+pub fn prev_tab(app: &mut App) {
+    let index = (app.active + tab_count(app) - 1) % tab_count(app);
+    switch_tab(app, index);
+}
+
+// A buffer was just made the active one
+fn show_buffer(app: &mut App) {
+    app.picker = None;
+    app.scrollbar.drag = None;
+    app.minimap_drag = false;
+    if !app.buffer.recovery_checked {
+        check_recovery(app);
+    }
+    app.should_rerender = true;
+}
+
+// Closes the active tab, unsaved changes are asked about first
+pub fn close_tab(app: &mut App) {
+    if !app.buffer.is_modified() {
+        remove_tab(app);
+        return;
+    }
+
+    let name = status_bar::file_text(&app.buffer.file_path, false);
+    app.prompt = Some(Prompt::new(
+        Question::CloseTabUnsaved,
+        format!("Save changes to {} before closing?", name),
+        vec!["Save", "Discard", "Cancel"],
+    ));
+    app.should_rerender = true;
+}
+
+// Drops the active buffer whatever changes it has, the tab after it takes its
+// place. Closing the last tab closes the window.
+fn remove_tab(app: &mut App) {
+    if app.buffer.autosave.has_file {
+        remove_recovery(&mut app.buffer);
+    }
+    if !app.buffer.is_untitled() {
+        app.closed.push((app.buffer.file_path.clone(), app.active));
+    }
+    if app.background.is_empty() {
+        app.window.set_should_close(true);
+        return;
+    }
+
+    let index = app.active.min(app.background.len() - 1);
+    app.buffer = app.background.remove(index);
+    app.active = index;
+    show_buffer(app);
+}
+
+// Opens the last closed tab again where it was, or goes to it when the file
+// is open already
+pub fn reopen_closed_tab(app: &mut App) {
+    let (file_path, index) = match app.closed.pop() {
+        Some(closed) => closed,
+        None => return,
+    };
+    match (0..tab_count(app)).find(|&open| tab(app, open).file_path == file_path) {
+        Some(open) => switch_tab(app, open),
+        None => open_tab(app, file_path, index),
+    }
+}
+
+// Closes the prompt and does what `choice` means for it
+fn answer_prompt(app: &mut App, choice: usize) {
+    let prompt = match app.prompt.take() {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    match (prompt.question, choice) {
+        (Question::CloseUnsaved, 0) => {
+            let saved = save(app);
+            after_save(app, saved, Some(AfterSave::CloseWindow));
+        }
+        (Question::CloseUnsaved, 1) => {
+            app.window.set_should_close(true);
+            // the other tabs are asked about once this one is gone
+            if tab_count(app) > 1 {
+                remove_tab(app);
+                request_close(app);
+            }
+        }
+        (Question::ReopenWithEncoding(encoding), 0) => reload_as(app, encoding),
+        (Question::CloseTabUnsaved, 0) => {
+            let saved = save(app);
+            after_save(app, saved, Some(AfterSave::CloseTab));
+        }
+        (Question::CloseTabUnsaved, 1) => remove_tab(app),
+        (Question::SaveAs, 0) => {
+            let saved = save_to(app, prompt.input.unwrap_or_default(), false);
+            after_save(app, saved, prompt.after_save);
+        }
+        (Question::ReplaceFile, 0) => {
+            let saved = save_to(app, prompt.input.unwrap_or_default(), true);
+            after_save(app, saved, prompt.after_save);
+        }
+        (Question::CreateDirectory, 0) => {
+            let path = prompt.path.unwrap_or_default();
+            let created = match Path::new(&path).parent() {
+                Some(dir) => fs::create_dir_all(dir),
+                None => Ok(()),
+            };
+            match created {
+                Ok(()) => {
+                    let saved = save_checked(app, path);
+                    after_save(app, saved, prompt.after_save);
+                }
+                Err(error) => {
+                    show_message(app, format!("Couldn't create the directory: {}", error))
+                }
+            }
+        }
+        (Question::ChangedOnDisk, 0) => reload_from_disk(app),
+        (Question::ChangedOnDisk, 1) => show_disk_diff(app),
+        (Question::DiskDiff, _) => ask_about_disk_change(app),
+        (Question::OverwriteChanged, 0) => {
+            let saved = save_anyway(app, prompt.path.unwrap_or_default());
+            after_save(app, saved, prompt.after_save);
+        }
+        (Question::Recover, 0) => restore_recovery(app),
+        (Question::Recover, 1) => show_recovery_diff(app),
+        (Question::Recover, 2) => remove_recovery(&mut app.buffer),
+        (Question::RecoveryDiff, _) => ask_to_recover(app),
+        (Question::FindBytes, 0) => {
+            let input = prompt.input.unwrap_or_default();
+            match hex::parse_pattern(&input) {
+                Some(pattern) => {
+                    if let Some(hex) = &mut app.buffer.hex {
+                        hex.pattern = pattern;
+                    }
+                    find_next_bytes(app);
+                }
+                None => show_message(app, format!("{:?} isn't a byte pattern", input)),
+            }
+        }
+        _ => {}
+    }
+    app.should_rerender = true;
+}
+
+// Does `after` once the save is written, a save still waiting on another
+// question passes it on to that one
+fn after_save(app: &mut App, saved: bool, after: Option<AfterSave>) {
+    match (saved, after) {
+        (true, Some(AfterSave::CloseWindow)) => {
+            app.window.set_should_close(true);
+            request_close(app);
+        }
+        (true, Some(AfterSave::CloseTab)) => remove_tab(app),
+        (false, Some(_)) => {
+            if let Some(prompt) = &mut app.prompt {
+                match prompt.question {
+                    Question::SaveAs
+                    | Question::ReplaceFile
+                    | Question::CreateDirectory
+                    | Question::OverwriteChanged => prompt.after_save = after,
+                    _ => {}
+                }
+            }
+        }
+        (_, None) => {}
+    }
+}
+
+pub fn prompt_key(app: &mut App, key: Key) {
+    let prompt = match &mut app.prompt {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    if let Some(choice) = prompt.key(key) {
+        answer_prompt(app, choice);
+    }
+}
+
+pub fn prompt_char(app: &mut App, c: char) {
+    let prompt = match &mut app.prompt {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    if let Some(choice) = prompt.char(c) {
+        answer_prompt(app, choice);
+    }
+}
+
+// Top of the prompt row, it sits right above the status bar
+pub fn prompt_top(app: &App) -> f32 {
+    text_height(app) - app.font_renderer.advance_height - scaled(app, STATUS_BAR_PADDING) * 2.0
+}
+
+// Answers the prompt with the choice under `x`, false when there is no prompt
+pub fn click_prompt(app: &mut App, x: f32, y: f32) -> bool {
+    let prompt = match &app.prompt {
+        Some(prompt) => prompt,
+        None => return false,
+    };
+    if y >= prompt_top(app) && y < text_height(app) {
+        let ranges = prompt.choice_ranges(app.font_renderer.char_width);
+        if let Some(choice) = ranges.iter().position(|range| range.contains(&x)) {
+            answer_prompt(app, choice);
+        }
+    }
+    true
+}
+
+fn read_recovery(app: &App) -> Option<Vec<u8>> {
+    fs::read(app.buffer.recovery_path()?).ok()
+}
+
+pub fn remove_recovery(buffer: &mut Buffer) {
+    if let Some(path) = buffer.recovery_path() {
+        let _ = fs::remove_file(path);
+    }
+    buffer.autosave.mark_removed();
+}
+
+// Keeps unsaved changes of every tab in recovery files, which go away once
+// there are none
+pub fn autosave(app: &mut App) {
+    if !app.config.recovery {
+        return;
+    }
+    autosave_buffer(&mut app.buffer);
+    for buffer in app.background.iter_mut() {
+        autosave_buffer(buffer);
+    }
+}
+
+fn autosave_buffer(buffer: &mut Buffer) {
+    if !buffer.is_modified() {
+        if buffer.autosave.has_file {
+            remove_recovery(buffer);
+        }
+        return;
+    }
+
+    let revision = buffer.content_revision();
+    if !buffer.autosave.is_due(revision, Instant::now()) {
+        return;
+    }
+    let (path, bytes) = match (buffer.recovery_path(), buffer.content_bytes()) {
+        (Some(path), Some(bytes)) => (path, bytes),
+        _ => return,
+    };
+    // the changes may be to a file others can't read
+    let written = match path.parent() {
+        Some(dir) => create_private_dir(dir),
+        None => Ok(()),
+    }
+    .and_then(|_| write_private(&path, &bytes));
+    if let Err(error) = written {
+        println!("recovery: can't write {}: {}", path.display(), error);
+    }
+    // a failed write is tried again on the next change
+    buffer.autosave.mark_written(revision);
+}
+
+// Asks about a recovery file left behind by a session that didn't end
+// cleanly, one with what is on disk already is just removed
+fn check_recovery(app: &mut App) {
+    app.buffer.recovery_checked = true;
+    if !app.config.recovery {
+        return;
+    }
+    match read_recovery(app) {
+        Some(bytes) if Some(&bytes) == app.buffer.content_bytes().as_ref() => {
+            remove_recovery(&mut app.buffer)
+        }
+        Some(_) => ask_to_recover(app),
+        None => {}
+    }
+}
+
+// A new untitled tab for each untitled buffer of an editor that didn't end
+// cleanly. Their recovery files become the new tabs' own, which are asked
+// about like any other once shown.
+fn adopt_untitled_recovery(config: &Config) -> Vec<Buffer> {
+    let mut buffers = Vec::new();
+    for leftover in leftover_untitled_files() {
+        let buffer = Buffer::open(String::new(), config).0;
+        let adopted = match buffer.recovery_path() {
+            // another editor starting at the same time may take it first
+            Some(path) => fs::rename(&leftover, &path).is_ok(),
+            None => false,
+        };
+        if adopted {
+            buffers.push(buffer);
+        }
+    }
+    buffers
+}
+
+fn ask_to_recover(app: &mut App) {
+    let name = status_bar::file_text(&app.buffer.file_path, false);
+    app.prompt = Some(Prompt::new(
+        Question::Recover,
+        format!("Found unsaved changes to {} from a previous session", name),
+        vec!["Restore", "Show diff", "Delete", "Ignore"],
+    ));
+    app.should_rerender = true;
+}
+
+fn show_recovery_diff(app: &mut App) {
+    let bytes = match read_recovery(app) {
+        Some(bytes) => bytes,
+        None => return,
+    };
+    let recovered = app.buffer.text.encoding.decode(&bytes);
+    let text = app.buffer.content_text();
+    show_diff(
+        app,
+        Question::RecoveryDiff,
+        (&text, "in the file"),
+        (&recovered, "recovered"),
+    );
+}
+
+// Replaces the buffer with the recovered changes, they stay unsaved
+fn restore_recovery(app: &mut App) {
+    let bytes = match read_recovery(app) {
+        Some(bytes) => bytes,
+        None => return,
+    };
+    let binary = app.buffer.hex.is_some();
+    let mut text = text::Text::decode(&bytes, app.buffer.text.encoding);
+    text.forget_saved();
+    set_text(app, text);
+    if binary {
+        app.buffer.hex = Some(HexBuffer::new(bytes, false));
+    }
+    app.buffer.autosave.has_file = true;
+}
+
+// The window is closing with everything saved or discarded
+pub fn close(app: &mut App) {
+    for index in 0..tab_count(app) {
+        let buffer = tab(app, index);
+        if buffer.autosave.has_file {
+            remove_recovery(buffer);
+        }
+    }
+}
+
+// Follows the file when it changed on disk, changes in the buffer are only
+// replaced when asked to
+pub fn check_disk(app: &mut App) {
+    let stamp = FileStamp::of(Path::new(&app.buffer.file_path));
+    if stamp == app.buffer.seen_stamp {
+        return;
+    }
+    app.buffer.seen_stamp = stamp;
+    // a deleted file keeps the buffer, saving writes it again
+    let bytes = match fs::read(&app.buffer.file_path) {
+        Ok(bytes) => bytes,
+        Err(_) => return,
+    };
+
+    if Some(&bytes) == app.buffer.content_bytes().as_ref() {
+        if app.buffer.is_modified() {
+            match &mut app.buffer.hex {
+                Some(hex) => hex.mark_saved(),
+                None => app.buffer.text.mark_saved(),
+            }
+        }
+        app.buffer.disk_stamp = stamp;
+        app.should_rerender = true;
+    } else if !app.buffer.is_modified() {
+        reload_keeping_position(app, &bytes);
+        app.buffer.disk_stamp = stamp;
+    } else if app.prompt.is_none() {
+        // another question keeps the buffer, saving still warns
+        ask_about_disk_change(app);
+    }
+}
+
+fn ask_about_disk_change(app: &mut App) {
+    let name = status_bar::file_text(&app.buffer.file_path, false);
+    app.prompt = Some(Prompt::new(
+        Question::ChangedOnDisk,
+        format!("{} changed on disk", name),
+        vec!["Reload", "Diff", "Keep mine"],
+    ));
+    app.should_rerender = true;
+}
+
+fn show_disk_diff(app: &mut App) {
+    let on_disk = match fs::read(&app.buffer.file_path) {
+        Ok(bytes) => app.buffer.text.encoding.decode(&bytes),
+        Err(_) => return,
+    };
+    let ours = app.buffer.content_text();
+    show_diff(
+        app,
+        Question::DiskDiff,
+        (&on_disk, "on disk"),
+        (&ours, "in the editor"),
+    );
+}
+
+// Changed lines from `old` to `new` above a summary, each comes with the name
+// of where it is from
+fn show_diff(app: &mut App, question: Question, old: (&str, &str), new: (&str, &str)) {
+    let summary = diff_summary(old.0, new.0, old.1, new.1);
+    let mut prompt = Prompt::new(question, summary, vec!["Back"]);
+    prompt.lines = diff_lines(old.0, new.0);
+    app.prompt = Some(prompt);
+    app.should_rerender = true;
+}
+
+fn reload_from_disk(app: &mut App) {
+    match fs::read(&app.buffer.file_path) {
+        Ok(bytes) => {
+            reload_keeping_position(app, &bytes);
+            mark_loaded(app);
+        }
+        Err(error) => {
+            let name = status_bar::file_text(&app.buffer.file_path, false);
+            show_message(app, format!("Couldn't read {}: {}", name, error));
+        }
+    }
+}
+
+// Replaces the buffer with `bytes`, the cursor stays on the same line and
+// column, or as close as the new text allows, and the scroll stays put
+fn reload_keeping_position(app: &mut App, bytes: &[u8]) {
+    let hex_cursor = app.buffer.hex.as_ref().map(|hex| hex.cursor);
+    let position = app.buffer.text.get_cursor().position;
+    let mut text = text::Text::decode(bytes, app.buffer.text.encoding);
+
+    let rope = text.get_text();
+    let y = (position.y as usize).min(rope.len_lines() - 1);
+    let line = rope.line(y);
+    let mut line_len = line.len_chars();
+    if line_len > 0 && line.char(line_len - 1) == '\n' {
+        line_len -= 1;
+        if line_len > 0 && line.char(line_len - 1) == '\r' {
+            line_len -= 1;
+        }
+    }
+    let x = (position.x as usize).min(line_len) as i64;
+    let cursor = text.get_cursor();
+    cursor.position = Point { x, y: y as i64 };
+    cursor.remembered_x = x;
+
+    set_text(app, text);
+    if let Some(cursor) = hex_cursor {
+        let mut hex = HexBuffer::new(bytes.to_vec(), true);
+        hex.cursor = cursor.min(bytes.len().saturating_sub(1) * 2);
+        app.buffer.hex = Some(hex);
+    }
+}
+
+// Size of a UI element given in logical pixels, in framebuffer pixels
+pub fn scaled(app: &App, size: f32) -> f32 {
+    (size * app.content_scale).round().max(1.0)
 }
 
 impl App {
+    // Opens a tab for each of `file_paths`, the first one is shown
     pub fn new(
         window: glfw::Window,
         glfw: glfw::Glfw,
         width: i32,
         height: i32,
-        file_path: String,
+        file_paths: Vec<String>,
+        config: Config,
     ) -> App {
-        let text = text::Text::new(File::open(file_path.clone()).unwrap());
-
         unsafe {
             gl::Viewport(0, 0, width, height);
         }
 
-        return App {
-            file_path: file_path,
-            font_renderer: FontRenderer::new(),
+        let (content_scale, _) = window.get_content_scale();
+        let content_scale = if content_scale > 0.0 {
+            content_scale
+        } else {
+            1.0
+        };
+
+        let mut font_renderer = FontRenderer::new(
+            load_fonts(&config),
+            config.font_size,
+            content_scale,
+            config.antialiasing,
+            config.hinting,
+        );
+        font_renderer.shaping = config.shaping;
+        font_renderer.show_whitespace = config.show_whitespace;
+        font_renderer.raw_byte_color = config.theme.raw_byte;
+
+        let mut buffers = Vec::new();
+        let mut read_errors = Vec::new();
+        for file_path in file_paths {
+            let (buffer, read_error) = Buffer::open(file_path, &config);
+            if let Some(error) = read_error {
+                let name = status_bar::file_text(&buffer.file_path, false);
+                read_errors.push(format!("Couldn't read {}: {}", name, error));
+            }
+            buffers.push(buffer);
+        }
+        if config.recovery {
+            buffers.extend(adopt_untitled_recovery(&config));
+        }
+        if buffers.is_empty() {
+            buffers.push(Buffer::open(String::new(), &config).0);
+        }
+        let buffer = buffers.remove(0);
+
+        let mut app = App {
+            config,
+            font_renderer,
             rect_renderer: RectRenderer::new(),
             should_rerender: true,
-            window: window,
-            glfw: glfw,
-            scroll: Scroll::new(),
+            window,
+            glfw,
             projection: projection_from_size(width, height),
-            text: text,
+            content_scale,
             shortcuts: KeyBoardShortcuts::new(),
+            buffer,
+            background: buffers,
+            active: 0,
+            closed: Vec::new(),
+            minimap_drag: false,
+            scrollbar: Scrollbar::new(),
+            caret: Caret::new(),
+            picker: None,
+            prompt: None,
+            title: String::new(),
         };
+        check_recovery(&mut app);
+        // the first error is shown, the others would only replace it
+        for message in read_errors.iter().skip(1) {
+            println!("{}", message);
+        }
+        if let Some(message) = read_errors.into_iter().next() {
+            show_message(&mut app, message);
+        }
+        app
     }
 }
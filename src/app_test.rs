@@ -5,15 +5,15 @@ use crate::text::Selection;
 use crate::{app::App, program::Program, text::DeleteDirection};
 
 fn select_all(app: &mut App) {
-    app.text.move_to_begging(Selection::NotSelect);
-    app.text.move_to_end(Selection::Select);
+    app.buffer.text.move_to_begging(Selection::NotSelect);
+    app.buffer.text.move_to_end(Selection::Select);
 }
 
 fn reset_text(app: &mut App) {
     select_all(app);
-    app.text.delete_text(DeleteDirection::Back);
-    app.text.insert_text("line 0\nline 1\nline 2\n");
-    app.text.move_to_begging(Selection::NotSelect);
+    app.buffer.text.delete_text(DeleteDirection::Back);
+    app.buffer.text.insert_text("line 0\nline 1\nline 2\n");
+    app.buffer.text.move_to_begging(Selection::NotSelect);
 }
 
 #[test]
@@ -68,7 +68,7 @@ fn should_launch() {
         );
 
         assert_eq!(
-            program.app.text.get_string(),
+            program.app.buffer.text.get_string(),
             "stringline 0\nline 1\nline 2\n",
             "paste text"
         );
@@ -87,7 +87,7 @@ fn should_launch() {
         );
 
         assert_eq!(
-            program.app.text.get_string(),
+            program.app.buffer.text.get_string(),
             "\nline 1\n line2line 0\nline 1\nline 2\n",
             "paste text remove crlf"
         );
@@ -107,7 +107,7 @@ fn should_launch() {
         );
 
         assert_eq!(
-            program.app.text.get_string(),
+            program.app.buffer.text.get_string(),
             "\nline 1\nline 2\n",
             "cut selection"
         );
@@ -128,7 +128,7 @@ fn should_launch() {
         );
 
         assert_eq!(
-            program.app.text.get_string(),
+            program.app.buffer.text.get_string(),
             "line 1\nline 2\n",
             "cut selection"
         );
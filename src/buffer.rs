@@ -0,0 +1,148 @@
+extern crate glfw;
+
+use crate::config::{Config, Indent};
+use crate::display_map::DisplayMap;
+use crate::encoding::detect;
+use crate::file_io::FileStamp;
+use crate::folding::Folds;
+use crate::hex::{self, HexBuffer};
+use crate::minimap::Minimap;
+use crate::recovery::{recovery_dir, recovery_file_name, untitled_recovery_file_name, Autosave};
+use crate::scroll::Scroll;
+use crate::status_bar::language_for;
+use crate::text;
+use crate::watcher::Watcher;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// A file open in a tab, with everything that goes with it rather than with
+// the window
+pub struct Buffer {
+    // empty until a buffer created by New File is saved
+    pub file_path: String,
+    pub text: text::Text,
+    pub scroll: Scroll,
+    pub soft_wrap: bool,
+    pub folds: Folds,
+    // screen rows of the text, scroll and cursor movement work on these
+    pub display_map: DisplayMap,
+    pub minimap: Minimap,
+    pub language: &'static str,
+    pub indent: Indent,
+    // typed characters replace the ones after the cursor
+    pub overwrite: bool,
+    // bytes shown in the hex view, None while the text is shown
+    pub hex: Option<HexBuffer>,
+    pub autosave: Autosave,
+    pub watcher: Watcher,
+    // version of the file on disk the buffer was loaded from or saved to
+    pub disk_stamp: Option<FileStamp>,
+    // version on disk last looked at, changes are only looked at once
+    pub seen_stamp: Option<FileStamp>,
+    // a recovery file was looked for, which happens once it is first shown
+    pub recovery_checked: bool,
+    // names the recovery file while there is no path
+    untitled_id: u64,
+}
+
+static NEXT_UNTITLED_ID: AtomicU64 = AtomicU64::new(0);
+
+// Called from the watcher thread, the event loop waits for events
+fn wake_event_loop() {
+    unsafe { glfw::ffi::glfwPostEmptyEvent() }
+}
+
+impl Buffer {
+    // A path that doesn't exist yet opens empty and the first save creates
+    // it. Any other error reading it is given back with an empty buffer.
+    pub fn open(file_path: String, config: &Config) -> (Buffer, Option<io::Error>) {
+        let (bytes, read_error) = match fs::read(&file_path) {
+            Ok(bytes) => (bytes, None),
+            Err(_) if file_path.is_empty() => (Vec::new(), None),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (Vec::new(), None),
+            Err(error) => (Vec::new(), Some(error)),
+        };
+        let encoding = detect(&bytes);
+        let text = text::Text::decode(&bytes, encoding);
+        let hex = if hex::looks_binary(&bytes, encoding) {
+            Some(HexBuffer::new(bytes, true))
+        } else {
+            None
+        };
+
+        let disk_stamp = FileStamp::of(Path::new(&file_path));
+        let buffer = Buffer {
+            language: language_for(&file_path),
+            watcher: Watcher::new(Path::new(&file_path), wake_event_loop),
+            file_path,
+            text,
+            scroll: Scroll::new(),
+            soft_wrap: config.soft_wrap,
+            folds: Folds::new(),
+            display_map: DisplayMap::new(),
+            minimap: Minimap::new(),
+            indent: config.indent,
+            overwrite: false,
+            hex,
+            autosave: Autosave::new(Duration::from_secs(config.recovery_interval)),
+            disk_stamp,
+            seen_stamp: disk_stamp,
+            recovery_checked: false,
+            untitled_id: NEXT_UNTITLED_ID.fetch_add(1, Ordering::Relaxed),
+        };
+        (buffer, read_error)
+    }
+
+    // A buffer that was never saved has no path yet
+    pub fn is_untitled(&self) -> bool {
+        self.file_path.is_empty()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        match &self.hex {
+            Some(hex) => hex.is_modified(),
+            None => self.text.is_modified(),
+        }
+    }
+
+    // Starts watching `file_path` again after it changed
+    pub fn watch(&mut self) {
+        self.watcher = Watcher::new(Path::new(&self.file_path), wake_event_loop);
+    }
+
+    // What a save would write, None when the encoding can't store the text
+    pub fn content_bytes(&self) -> Option<Vec<u8>> {
+        match &self.hex {
+            Some(hex) => Some(hex.bytes.clone()),
+            None => self.text.to_bytes().ok(),
+        }
+    }
+
+    // What `content_bytes` would hold, decoded the way the file is
+    pub fn content_text(&self) -> String {
+        match &self.hex {
+            Some(hex) => self.text.encoding.decode(&hex.bytes),
+            None => self.text.to_file_string(),
+        }
+    }
+
+    pub fn content_revision(&self) -> u64 {
+        match &self.hex {
+            Some(hex) => hex.revision(),
+            None => self.text.revision(),
+        }
+    }
+
+    pub fn recovery_path(&self) -> Option<PathBuf> {
+        let name = if self.is_untitled() {
+            untitled_recovery_file_name(self.untitled_id)
+        } else {
+            recovery_file_name(Path::new(&self.file_path))
+        };
+        Some(recovery_dir()?.join(name))
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use crate::scroll::{lerp, PointF};
+
+// The caret is shown and hidden for this long each
+pub const BLINK_INTERVAL: f32 = 0.5;
+// Blinking stops after this long without input so an idle editor can sleep
+pub const BLINK_TIMEOUT: f32 = 15.0;
+// Seconds a smooth caret takes to reach a new position
+pub const MOVE_TIME: f32 = 0.08;
+
+pub fn blink_on(idle: f32) -> bool {
+    idle >= BLINK_TIMEOUT || (idle / BLINK_INTERVAL) as u64 % 2 == 0
+}
+
+// Seconds until the caret has to be drawn again, None once it stopped blinking
+pub fn next_blink_in(idle: f32) -> Option<f32> {
+    if idle >= BLINK_TIMEOUT {
+        return None;
+    }
+    Some((BLINK_INTERVAL - idle % BLINK_INTERVAL).min(BLINK_TIMEOUT - idle))
+}
+
+// Where the caret is drawn, x in pixels from the start of the text and y in
+// screen rows, so it scrolls along with the text while moving
+pub struct Caret {
+    pub current: PointF,
+    pub target: PointF,
+    base: PointF,
+    animation_time: f32,
+    last_input: Instant,
+}
+
+impl Caret {
+    pub fn new() -> Caret {
+        Caret {
+            current: PointF { x: 0.0, y: 0.0 },
+            target: PointF { x: 0.0, y: 0.0 },
+            base: PointF { x: 0.0, y: 0.0 },
+            animation_time: 0.0,
+            last_input: Instant::now(),
+        }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.current != self.target
+    }
+
+    // Seconds since the last key press or click
+    pub fn idle(&self) -> f32 {
+        self.last_input.elapsed().as_secs_f32()
+    }
+}
+
+// Keeps the caret solid while typing
+pub fn reset_blink(caret: &mut Caret) {
+    caret.last_input = Instant::now();
+}
+
+pub fn move_caret(caret: &mut Caret, target: PointF) {
+    if caret.target == target {
+        return;
+    }
+    caret.base = caret.current;
+    caret.target = target;
+    caret.animation_time = 0.0;
+}
+
+pub fn jump_caret(caret: &mut Caret, target: PointF) {
+    caret.base = target;
+    caret.current = target;
+    caret.target = target;
+    caret.animation_time = 0.0;
+}
+
+pub fn advance_caret(caret: &mut Caret, time: f32) -> bool {
+    if !caret.is_moving() {
+        return false;
+    }
+
+    caret.animation_time += time;
+    if caret.animation_time >= MOVE_TIME {
+        caret.current = caret.target;
+        caret.base = caret.target;
+        caret.animation_time = 0.0;
+    } else {
+        let by = caret.animation_time / MOVE_TIME;
+        caret.current = PointF {
+            x: lerp(caret.base.x, caret.target.x, by),
+            y: lerp(caret.base.y, caret.target.y, by),
+        };
+    }
+
+    true
+}
@@ -0,0 +1,34 @@
+use crate::caret::{
+    advance_caret, blink_on, jump_caret, move_caret, next_blink_in, Caret, BLINK_INTERVAL,
+    BLINK_TIMEOUT, MOVE_TIME,
+};
+use crate::scroll::PointF;
+
+#[test]
+fn blinks_until_timeout() {
+    assert!(blink_on(0.0));
+    assert!(!blink_on(BLINK_INTERVAL * 1.5));
+    assert!(blink_on(BLINK_INTERVAL * 2.5));
+    assert!(blink_on(BLINK_TIMEOUT + BLINK_INTERVAL));
+}
+
+#[test]
+fn wakes_up_for_next_blink() {
+    let next = next_blink_in(BLINK_INTERVAL * 0.25).unwrap();
+    assert!((next - BLINK_INTERVAL * 0.75).abs() < 0.001);
+    assert_eq!(next_blink_in(BLINK_TIMEOUT), None);
+}
+
+#[test]
+fn caret_moves_smoothly() {
+    let mut caret = Caret::new();
+    jump_caret(&mut caret, PointF { x: 0.0, y: 0.0 });
+    move_caret(&mut caret, PointF { x: 10.0, y: 2.0 });
+
+    assert!(advance_caret(&mut caret, MOVE_TIME / 2.0));
+    assert_eq!(caret.current, PointF { x: 5.0, y: 1.0 });
+
+    assert!(advance_caret(&mut caret, MOVE_TIME));
+    assert_eq!(caret.current, PointF { x: 10.0, y: 2.0 });
+    assert!(!advance_caret(&mut caret, MOVE_TIME));
+}
@@ -0,0 +1,321 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::theme::{parse_color, Theme};
+
+pub const DEFAULT_FONT_SIZE: u32 = 14;
+pub const DEFAULT_INDENT_WIDTH: usize = 4;
+// seconds between recovery file writes while typing doesn't stop
+pub const DEFAULT_RECOVERY_INTERVAL: u64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Antialiasing {
+    Grayscale,
+    LcdRgb,
+    LcdBgr,
+    // subpixels stacked top to bottom, for monitors rotated into portrait
+    LcdVertical,
+}
+
+impl Antialiasing {
+    fn parse(value: &str) -> Option<Antialiasing> {
+        match value {
+            "grayscale" | "gray" => Some(Antialiasing::Grayscale),
+            "lcd" | "lcd_rgb" | "rgb" => Some(Antialiasing::LcdRgb),
+            "lcd_bgr" | "bgr" => Some(Antialiasing::LcdBgr),
+            "lcd_vertical" | "vrgb" => Some(Antialiasing::LcdVertical),
+            _ => None,
+        }
+    }
+
+    pub fn is_subpixel(self) -> bool {
+        self != Antialiasing::Grayscale
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hinting {
+    None,
+    Light,
+    Full,
+}
+
+impl Hinting {
+    fn parse(value: &str) -> Option<Hinting> {
+        match value {
+            "none" => Some(Hinting::None),
+            "light" | "slight" => Some(Hinting::Light),
+            "full" => Some(Hinting::Full),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorStyle {
+    Bar,
+    // the glyph under it is drawn inverted
+    Block,
+    Underline,
+}
+
+impl CursorStyle {
+    fn parse(value: &str) -> Option<CursorStyle> {
+        match value {
+            "bar" | "beam" | "line" => Some(CursorStyle::Bar),
+            "block" => Some(CursorStyle::Block),
+            "underline" => Some(CursorStyle::Underline),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indent {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Indent {
+    pub const CHOICES: [Indent; 4] = [
+        Indent::Tabs,
+        Indent::Spaces(2),
+        Indent::Spaces(4),
+        Indent::Spaces(8),
+    ];
+
+    // `tabs`, `spaces` or the number of spaces
+    fn parse(value: &str) -> Option<Indent> {
+        match value {
+            "tabs" | "tab" => Some(Indent::Tabs),
+            "spaces" | "space" => Some(Indent::Spaces(DEFAULT_INDENT_WIDTH)),
+            _ => match value.parse::<usize>() {
+                Ok(width) if width > 0 => Some(Indent::Spaces(width)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Indent::Tabs => "Tabs".to_owned(),
+            Indent::Spaces(width) => format!("Spaces: {}", width),
+        }
+    }
+
+    // What the tab key inserts at visual `column`, spaces go to the next stop
+    pub fn text_at(self, column: usize) -> String {
+        match self {
+            Indent::Tabs => "\t".to_owned(),
+            Indent::Spaces(width) => " ".repeat(width - column % width),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub font_family: Option<String>,
+    pub font_path: Option<String>,
+    pub font_size: u32,
+    pub font_fallback: Vec<String>,
+    pub shaping: bool,
+    pub antialiasing: Antialiasing,
+    pub hinting: Hinting,
+    pub soft_wrap: bool,
+    // wrap at this column instead of the window width
+    pub wrap_column: Option<usize>,
+    pub minimap: bool,
+    pub scrollbar: bool,
+    // hide the scrollbar after a while without scrolling
+    pub scrollbar_fade: bool,
+    // draw spaces as dots and tabs as arrows
+    pub show_whitespace: bool,
+    pub trailing_whitespace: bool,
+    pub indent_guides: bool,
+    pub highlight_current_line: bool,
+    // other visible occurrences of the word under the cursor or the selection
+    pub highlight_occurrences: bool,
+    // columns to draw a vertical line at
+    pub rulers: Vec<usize>,
+    pub cursor_style: CursorStyle,
+    pub cursor_blink: bool,
+    // animate the caret between positions
+    pub smooth_caret: bool,
+    // what the tab key inserts
+    pub indent: Indent,
+    pub status_bar: bool,
+    pub tab_bar: bool,
+    // keep unsaved changes in a recovery file
+    pub recovery: bool,
+    pub recovery_interval: u64,
+    // save every open file whenever the window loses focus
+    pub autosave_on_focus_loss: bool,
+    pub theme: Theme,
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(base.join("editor").join("config"))
+}
+
+fn set_bool(field: &mut bool, key: &str, value: &str) {
+    match value {
+        "true" | "on" | "yes" => *field = true,
+        "false" | "off" | "no" => *field = false,
+        _ => println!("config: invalid {} `{}`", key, value),
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            font_family: None,
+            font_path: None,
+            font_size: DEFAULT_FONT_SIZE,
+            font_fallback: Vec::new(),
+            shaping: false,
+            antialiasing: Antialiasing::LcdRgb,
+            hinting: Hinting::Full,
+            soft_wrap: false,
+            wrap_column: None,
+            minimap: false,
+            scrollbar: true,
+            scrollbar_fade: false,
+            show_whitespace: false,
+            trailing_whitespace: false,
+            indent_guides: false,
+            highlight_current_line: true,
+            highlight_occurrences: true,
+            rulers: Vec::new(),
+            cursor_style: CursorStyle::Bar,
+            cursor_blink: true,
+            smooth_caret: false,
+            indent: Indent::Tabs,
+            status_bar: true,
+            tab_bar: true,
+            recovery: true,
+            recovery_interval: DEFAULT_RECOVERY_INTERVAL,
+            autosave_on_focus_loss: false,
+            theme: Theme::new(),
+        }
+    }
+
+    pub fn load() -> Config {
+        let src = config_path().and_then(|path| fs::read_to_string(path).ok());
+
+        match src {
+            Some(src) => Config::parse(&src),
+            None => Config::new(),
+        }
+    }
+
+    // Lines are `key = value`, everything after `#` is a comment
+    pub fn parse(src: &str) -> Config {
+        let mut config = Config::new();
+
+        for line in src.lines() {
+            let line = line.splitn(2, '#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(value) => value.trim().trim_matches('"'),
+                None => {
+                    println!("config: expected `key = value`, got `{}`", line);
+                    continue;
+                }
+            };
+
+            config.set(key, value);
+        }
+
+        return config;
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "font_family" => self.font_family = Some(value.to_owned()),
+            "font_path" => self.font_path = Some(value.to_owned()),
+            "font_size" => match value.parse::<u32>() {
+                Ok(size) if size > 0 => self.font_size = size,
+                _ => println!("config: invalid font_size `{}`", value),
+            },
+            "font_fallback" => {
+                self.font_fallback = value
+                    .split(',')
+                    .map(|name| name.trim().trim_matches('"'))
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_owned())
+                    .collect()
+            }
+            "shaping" => set_bool(&mut self.shaping, key, value),
+            "soft_wrap" => set_bool(&mut self.soft_wrap, key, value),
+            "minimap" => set_bool(&mut self.minimap, key, value),
+            "scrollbar" => set_bool(&mut self.scrollbar, key, value),
+            "scrollbar_fade" => set_bool(&mut self.scrollbar_fade, key, value),
+            "show_whitespace" => set_bool(&mut self.show_whitespace, key, value),
+            "trailing_whitespace" => set_bool(&mut self.trailing_whitespace, key, value),
+            "indent_guides" => set_bool(&mut self.indent_guides, key, value),
+            "highlight_current_line" => set_bool(&mut self.highlight_current_line, key, value),
+            "highlight_occurrences" => set_bool(&mut self.highlight_occurrences, key, value),
+            "rulers" => {
+                let rulers: Result<Vec<usize>, _> = value
+                    .split(',')
+                    .map(|column| column.trim())
+                    .filter(|column| !column.is_empty())
+                    .map(|column| column.parse::<usize>())
+                    .collect();
+                match rulers {
+                    Ok(rulers) => self.rulers = rulers,
+                    Err(_) => println!("config: invalid rulers `{}`", value),
+                }
+            }
+            "wrap_column" => match value.parse::<usize>() {
+                Ok(0) => self.wrap_column = None,
+                Ok(column) => self.wrap_column = Some(column),
+                _ => println!("config: invalid wrap_column `{}`", value),
+            },
+            "cursor_style" => match CursorStyle::parse(value) {
+                Some(style) => self.cursor_style = style,
+                None => println!("config: invalid cursor_style `{}`", value),
+            },
+            "cursor_blink" => set_bool(&mut self.cursor_blink, key, value),
+            "smooth_caret" => set_bool(&mut self.smooth_caret, key, value),
+            "indent" => match Indent::parse(value) {
+                Some(indent) => self.indent = indent,
+                None => println!("config: invalid indent `{}`", value),
+            },
+            "status_bar" => set_bool(&mut self.status_bar, key, value),
+            "tab_bar" => set_bool(&mut self.tab_bar, key, value),
+            "recovery" => set_bool(&mut self.recovery, key, value),
+            "recovery_interval" => match value.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => self.recovery_interval = seconds,
+                _ => println!("config: invalid recovery_interval `{}`", value),
+            },
+            "autosave_on_focus_loss" => set_bool(&mut self.autosave_on_focus_loss, key, value),
+            "antialiasing" => match Antialiasing::parse(value) {
+                Some(antialiasing) => self.antialiasing = antialiasing,
+                None => println!("config: invalid antialiasing `{}`", value),
+            },
+            "hinting" => match Hinting::parse(value) {
+                Some(hinting) => self.hinting = hinting,
+                None => println!("config: invalid hinting `{}`", value),
+            },
+            _ if key.starts_with("color.") => match parse_color(value) {
+                Some(color) => {
+                    if !self.theme.set(&key["color.".len()..], color) {
+                        println!("config: unknown color `{}`", key)
+                    }
+                }
+                None => println!("config: invalid color `{}`", value),
+            },
+            _ => println!("config: unknown key `{}`", key),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::config::{
+    Antialiasing, Config, CursorStyle, Hinting, Indent, DEFAULT_FONT_SIZE,
+    DEFAULT_RECOVERY_INTERVAL,
+};
+use crate::theme::Theme;
+
+#[test]
+fn parse_empty_config() {
+    let config = Config::parse("");
+
+    assert_eq!(config.font_size, DEFAULT_FONT_SIZE);
+    assert_eq!(config.font_family, None);
+    assert_eq!(config.font_path, None);
+}
+
+#[test]
+fn parse_font_settings() {
+    let config = Config::parse(
+        "# fonts\nfont_family = \"Fira Code\"\nfont_path=/tmp/font.ttf\nfont_size = 18 # big\n",
+    );
+
+    assert_eq!(config.font_family, Some("Fira Code".to_owned()));
+    assert_eq!(config.font_path, Some("/tmp/font.ttf".to_owned()));
+    assert_eq!(config.font_size, 18);
+}
+
+#[test]
+fn parse_ignores_invalid_values() {
+    let config = Config::parse("font_size = big\nfont_size = 0\nnot a setting\nunknown = 1");
+
+    assert_eq!(config.font_size, DEFAULT_FONT_SIZE);
+}
+
+#[test]
+fn parse_font_fallback_list() {
+    let config = Config::parse(
+        "font_fallback = Noto Sans CJK JP, \"Noto Color Emoji\",, ~/fonts/Symbola.ttf",
+    );
+
+    assert_eq!(
+        config.font_fallback,
+        vec![
+            "Noto Sans CJK JP",
+            "Noto Color Emoji",
+            "~/fonts/Symbola.ttf"
+        ]
+    );
+}
+
+#[test]
+fn parse_bool_settings() {
+    assert_eq!(Config::new().shaping, false);
+    assert_eq!(Config::parse("shaping = true").shaping, true);
+    assert_eq!(Config::parse("shaping = off").shaping, false);
+    assert_eq!(Config::parse("shaping = maybe").shaping, false);
+}
+
+#[test]
+fn parse_rendering_settings() {
+    let config = Config::parse("antialiasing = lcd_bgr\nhinting = light");
+
+    assert_eq!(config.antialiasing, Antialiasing::LcdBgr);
+    assert_eq!(config.hinting, Hinting::Light);
+
+    let config = Config::parse("antialiasing = smooth\nhinting = grayscale");
+
+    assert_eq!(config.antialiasing, Antialiasing::LcdRgb);
+    assert_eq!(config.hinting, Hinting::Full);
+}
+
+#[test]
+fn parse_wrap_settings() {
+    let config = Config::parse("soft_wrap = on\nwrap_column = 80");
+
+    assert!(config.soft_wrap);
+    assert_eq!(config.wrap_column, Some(80));
+
+    let config = Config::parse("wrap_column = 0");
+
+    assert!(!config.soft_wrap);
+    assert_eq!(config.wrap_column, None);
+}
+
+#[test]
+fn parse_decorations() {
+    let config = Config::parse(
+        "show_whitespace = on\nindent_guides = true\nrulers = 80, 100\ncolor.ruler = 102030\ncolor.nope = 102030",
+    );
+
+    assert!(config.show_whitespace);
+    assert!(!config.trailing_whitespace);
+    assert!(config.indent_guides);
+    assert_eq!(config.rulers, vec![80, 100]);
+    assert_eq!(
+        config.theme.ruler,
+        [16.0 / 255.0, 32.0 / 255.0, 48.0 / 255.0]
+    );
+
+    let config = Config::parse("rulers = 80, x\ncolor.ruler = 1020");
+
+    assert!(config.rulers.is_empty());
+    assert_eq!(config.theme.ruler, Theme::new().ruler);
+}
+
+#[test]
+fn parse_cursor_settings() {
+    let config = Config::parse("cursor_style = block\ncursor_blink = off\nsmooth_caret = on");
+
+    assert_eq!(config.cursor_style, CursorStyle::Block);
+    assert!(!config.cursor_blink);
+    assert!(config.smooth_caret);
+
+    let config = Config::parse("cursor_style = triangle");
+
+    assert_eq!(config.cursor_style, CursorStyle::Bar);
+}
+
+#[test]
+fn parse_indent() {
+    assert_eq!(Config::parse("indent = spaces").indent, Indent::Spaces(4));
+    assert_eq!(Config::parse("indent = 2").indent, Indent::Spaces(2));
+    assert_eq!(Config::parse("indent = tabs").indent, Indent::Tabs);
+    assert_eq!(Config::parse("indent = 0").indent, Indent::Tabs);
+    assert!(!Config::parse("status_bar = off").status_bar);
+    assert!(!Config::parse("tab_bar = off").tab_bar);
+}
+
+#[test]
+fn indent_goes_to_next_stop() {
+    assert_eq!(Indent::Tabs.text_at(3), "\t");
+    assert_eq!(Indent::Spaces(4).text_at(0), "    ");
+    assert_eq!(Indent::Spaces(4).text_at(5), "   ");
+    assert_eq!(Indent::Spaces(2).text_at(1), " ");
+}
+
+#[test]
+fn parse_recovery_settings() {
+    let config =
+        Config::parse("recovery = off\nrecovery_interval = 10\nautosave_on_focus_loss = on");
+
+    assert!(!config.recovery);
+    assert_eq!(config.recovery_interval, 10);
+    assert!(config.autosave_on_focus_loss);
+
+    let config = Config::parse("recovery_interval = 0");
+
+    assert!(config.recovery);
+    assert_eq!(config.recovery_interval, DEFAULT_RECOVERY_INTERVAL);
+    assert!(!config.autosave_on_focus_loss);
+}
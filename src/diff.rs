@@ -0,0 +1,172 @@
+// Lines keep their line ending, a file that only went from CRLF to LF
+// differs on every line
+fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+// Where two versions of a text first differ and how many lines each has from
+// there to where they are the same again, the names say where they come from
+pub fn diff_summary(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old = lines(old);
+    let new = lines(new);
+    let common = old.len().min(new.len());
+
+    let prefix = (0..common).take_while(|&i| old[i] == new[i]).count();
+    let suffix = (0..common - prefix)
+        .take_while(|&i| old[old.len() - 1 - i] == new[new.len() - 1 - i])
+        .count();
+    if prefix == common && old.len() == new.len() {
+        return "No differences".to_owned();
+    }
+
+    format!(
+        "From line {}: {} lines {}, {} {}",
+        prefix + 1,
+        old.len() - prefix - suffix,
+        old_name,
+        new.len() - prefix - suffix,
+        new_name
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+    // `@@ -old +new @@` line a hunk starts with
+    Hunk,
+}
+
+// One row of a diff as it is shown
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffLine {
+    pub change: Change,
+    pub text: String,
+}
+
+// Unchanged lines shown around each change
+const CONTEXT: usize = 2;
+// Changed parts with more line pairs than this aren't matched up, they show
+// as all removed and then all added
+const MAX_MATCHED: usize = 4_000_000;
+
+// What happens to each line going from `old` to `new`, in order
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let common = old.len().min(new.len());
+    let prefix = (0..common).take_while(|&i| old[i] == new[i]).count();
+    let suffix = (0..common - prefix)
+        .take_while(|&i| old[old.len() - 1 - i] == new[new.len() - 1 - i])
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    let mut script = vec![Change::Same; prefix];
+    let (mut i, mut j) = (0, 0);
+    if old.len() * new.len() <= MAX_MATCHED {
+        // length of the longest common subsequence of `old[i..]` and
+        // `new[j..]` at `i * width + j`
+        let width = new.len() + 1;
+        let mut common = vec![0u32; (old.len() + 1) * width];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i * width + j] = if old[i] == new[j] {
+                    common[(i + 1) * width + j + 1] + 1
+                } else {
+                    common[(i + 1) * width + j].max(common[i * width + j + 1])
+                };
+            }
+        }
+
+        while i < old.len() && j < new.len() {
+            if old[i] == new[j] {
+                script.push(Change::Same);
+                i += 1;
+                j += 1;
+            } else if common[(i + 1) * width + j] >= common[i * width + j + 1] {
+                script.push(Change::Removed);
+                i += 1;
+            } else {
+                script.push(Change::Added);
+                j += 1;
+            }
+        }
+    }
+    script.extend((i..old.len()).map(|_| Change::Removed));
+    script.extend((j..new.len()).map(|_| Change::Added));
+    script.extend((0..suffix).map(|_| Change::Same));
+    script
+}
+
+// A line without its '\n', a '\r' is spelled out so line ending changes
+// show
+fn shown(line: &str) -> String {
+    line.trim_end_matches('\n').replace('\r', "\\r")
+}
+
+// The changed lines with a few unchanged ones around them, in hunks that
+// each start with a `Change::Hunk` header. Empty when nothing changed.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old = lines(old);
+    let new = lines(new);
+    let script = edit_script(&old, &new);
+    let changed = |k: usize| script[k] != Change::Same;
+    let is_shown = |k: usize| {
+        let start = k.saturating_sub(CONTEXT);
+        let end = (k + CONTEXT + 1).min(script.len());
+        (start..end).any(changed)
+    };
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut k = 0;
+    while k < script.len() {
+        if !is_shown(k) {
+            match script[k] {
+                Change::Removed => i += 1,
+                Change::Added => j += 1,
+                _ => {
+                    i += 1;
+                    j += 1;
+                }
+            }
+            k += 1;
+            continue;
+        }
+
+        let end = (k..script.len())
+            .find(|&end| !is_shown(end))
+            .unwrap_or(script.len());
+        let removed = script[k..end]
+            .iter()
+            .filter(|&&change| change != Change::Added)
+            .count();
+        let added = script[k..end]
+            .iter()
+            .filter(|&&change| change != Change::Removed)
+            .count();
+        result.push(DiffLine {
+            change: Change::Hunk,
+            text: format!("@@ -{},{} +{},{} @@", i + 1, removed, j + 1, added),
+        });
+        for &change in &script[k..end] {
+            let (sign, line) = match change {
+                Change::Removed => ('-', old[i]),
+                Change::Added => ('+', new[j]),
+                _ => (' ', old[i]),
+            };
+            if change != Change::Added {
+                i += 1;
+            }
+            if change != Change::Removed {
+                j += 1;
+            }
+            result.push(DiffLine {
+                change,
+                text: format!("{}{}", sign, shown(line)),
+            });
+        }
+        k = end;
+    }
+    result
+}
@@ -0,0 +1,66 @@
+use crate::diff::{diff_lines, diff_summary, Change, DiffLine};
+
+#[test]
+fn summarizes_differences() {
+    assert_eq!(
+        diff_summary("a\nb\nc\nd\n", "a\nx\ny\nd\n", "in the file", "recovered"),
+        "From line 2: 2 lines in the file, 2 recovered"
+    );
+    assert_eq!(
+        diff_summary("a\nb\n", "a\nb\nc\n", "on disk", "here"),
+        "From line 3: 0 lines on disk, 1 here"
+    );
+    assert_eq!(diff_summary("a\nb\n", "a\nb\n", "", ""), "No differences");
+}
+
+#[test]
+fn line_endings_are_differences() {
+    assert_eq!(
+        diff_summary("a\r\n", "a\n", "on disk", "here"),
+        "From line 1: 1 lines on disk, 1 here"
+    );
+    assert_eq!(
+        diff_lines("a\r\n", "a\n")
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>(),
+        vec!["@@ -1,1 +1,1 @@", "-a\\r", "+a"]
+    );
+}
+
+fn line(change: Change, text: &str) -> DiffLine {
+    DiffLine {
+        change,
+        text: text.to_owned(),
+    }
+}
+
+#[test]
+fn shows_changed_lines_with_context() {
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let new = "1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n12\n13\n";
+
+    assert_eq!(
+        diff_lines(old, new),
+        vec![
+            line(Change::Hunk, "@@ -2,5 +2,5 @@"),
+            line(Change::Same, " 2"),
+            line(Change::Same, " 3"),
+            line(Change::Removed, "-4"),
+            line(Change::Added, "+four"),
+            line(Change::Same, " 5"),
+            line(Change::Same, " 6"),
+            line(Change::Hunk, "@@ -9,4 +9,4 @@"),
+            line(Change::Same, " 9"),
+            line(Change::Same, " 10"),
+            line(Change::Removed, "-11"),
+            line(Change::Same, " 12"),
+            line(Change::Added, "+13"),
+        ]
+    );
+}
+
+#[test]
+fn no_lines_without_differences() {
+    assert!(diff_lines("a\nb", "a\nb").is_empty());
+}
@@ -0,0 +1,273 @@
+use std::{borrow::Cow, cmp::Ordering};
+
+use ropey::{Rope, RopeSlice};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::cursor::Point;
+use crate::folding::Folds;
+use crate::grapheme::grapheme_width;
+use crate::text::LineEdit;
+
+// One screen row, either a whole text line or a part of a soft wrapped one.
+// `start..end` are char offsets inside the line, the last row of a line also
+// holds its line break. `folded` is set on the last row of a fold header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayLine {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    pub folded: bool,
+}
+
+impl DisplayLine {
+    pub fn is_continuation(&self) -> bool {
+        self.start > 0
+    }
+}
+
+// Char offsets where the rows after the first one start. Rows are broken after
+// whitespace, whitespace itself may hang past the edge, words longer than
+// `width` are broken wherever they hit it.
+pub fn wrap_line(line: RopeSlice, width: usize) -> Vec<usize> {
+    let width = width.max(1);
+    let line: Cow<str> = line.into();
+
+    let mut breaks = Vec::new();
+    let mut chars = 0;
+    let mut column = 0;
+    let mut row_start = 0;
+    // char offset and row column right after the last whitespace
+    let mut word_start: Option<(usize, usize)> = None;
+    let mut in_indent = true;
+
+    for grapheme in line.graphemes(true) {
+        if grapheme == "\n" || grapheme == "\r\n" {
+            break;
+        }
+
+        let grapheme_chars = grapheme.chars().count();
+        let width_of = grapheme_width(grapheme);
+
+        if grapheme.chars().all(char::is_whitespace) {
+            chars += grapheme_chars;
+            column += width_of;
+            // leading indentation is not a place to break at
+            if !in_indent {
+                word_start = Some((chars, column));
+            }
+            continue;
+        }
+        in_indent = false;
+
+        if column + width_of > width && chars > row_start {
+            if let Some((at, at_column)) = word_start {
+                if at > row_start {
+                    breaks.push(at);
+                    row_start = at;
+                    column -= at_column;
+                }
+            }
+            if column + width_of > width && chars > row_start {
+                breaks.push(chars);
+                row_start = chars;
+                column = 0;
+            }
+            word_start = None;
+        }
+
+        chars += grapheme_chars;
+        column += width_of;
+    }
+
+    breaks
+}
+
+// Maps text lines to screen rows, folded lines take no rows. Without a wrap
+// width and folds every line is one row and nothing is stored.
+pub struct DisplayMap {
+    wrap_width: Option<usize>,
+    revision: Option<(u64, u64)>,
+    identity: bool,
+    // text revision `breaks` belong to, None while they aren't kept
+    breaks_revision: Option<u64>,
+    // wrap breaks of every line, hidden ones included
+    breaks: Vec<Vec<usize>>,
+    // first row of every line, one extra entry holds the row count
+    first_rows: Vec<usize>,
+    folded_headers: Vec<usize>,
+}
+
+impl DisplayMap {
+    pub fn new() -> DisplayMap {
+        DisplayMap {
+            wrap_width: None,
+            revision: None,
+            identity: true,
+            breaks_revision: None,
+            breaks: Vec::new(),
+            first_rows: Vec::new(),
+            folded_headers: Vec::new(),
+        }
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.wrap_width.is_some()
+    }
+
+    // Every line is exactly one row
+    pub fn is_identity(&self) -> bool {
+        self.identity
+    }
+
+    // Follows the text through `edits`, the changes that brought it to
+    // `revision` with one revision each, and only rewraps the lines they
+    // touched. A new width or missed edits rewrap every line. Rows are
+    // counted again whenever anything changed.
+    pub fn update(
+        &mut self,
+        text: &Rope,
+        revision: u64,
+        edits: &[LineEdit],
+        wrap_width: Option<usize>,
+        folds: &Folds,
+    ) {
+        if wrap_width == self.wrap_width && Some((revision, folds.revision())) == self.revision {
+            return;
+        }
+        let follows = wrap_width == self.wrap_width
+            && self.breaks_revision.map(|r| r + edits.len() as u64) == Some(revision);
+        self.wrap_width = wrap_width;
+        self.revision = Some((revision, folds.revision()));
+        self.identity = wrap_width.is_none() && folds.is_empty();
+        self.first_rows.clear();
+        self.folded_headers.clear();
+
+        if self.identity {
+            self.breaks.clear();
+            self.breaks_revision = None;
+            return;
+        }
+
+        let wrap = |line| match wrap_width {
+            Some(width) => wrap_line(line, width),
+            None => Vec::new(),
+        };
+        let edit = match edits.split_first() {
+            Some((first, rest)) => Some(rest.iter().fold(*first, |edit, &next| edit.then(next))),
+            None => None,
+        };
+        match edit {
+            Some(edit) if follows && edit.old_end <= self.breaks.len() => {
+                let changed = (edit.start..edit.new_end).map(|line| wrap(text.line(line)));
+                self.breaks.splice(edit.start..edit.old_end, changed);
+            }
+            // only the folds changed
+            None if follows => {}
+            _ => self.breaks.clear(),
+        }
+        if self.breaks.len() != text.len_lines() {
+            self.breaks = text.lines().map(|line| wrap(line)).collect();
+        }
+        self.breaks_revision = Some(revision);
+
+        let mut hidden = vec![false; text.len_lines()];
+        for fold in folds.iter() {
+            if fold.start >= hidden.len() {
+                continue;
+            }
+            self.folded_headers.push(fold.start);
+            for line in fold.start + 1..=fold.end.min(hidden.len() - 1) {
+                hidden[line] = true;
+            }
+        }
+        self.folded_headers.retain(|&line| !hidden[line]);
+
+        let mut row = 0;
+        for (line, breaks) in self.breaks.iter().enumerate() {
+            self.first_rows.push(row);
+            if !hidden[line] {
+                row += breaks.len() + 1;
+            }
+        }
+        self.first_rows.push(row);
+    }
+
+    // Folded away, takes no rows
+    fn is_hidden(&self, line: usize) -> bool {
+        line + 1 < self.first_rows.len() && self.first_rows[line] == self.first_rows[line + 1]
+    }
+
+    pub fn row_count(&self, text: &Rope) -> usize {
+        match self.first_rows.last() {
+            Some(&count) if !self.identity => count,
+            _ => text.len_lines(),
+        }
+    }
+
+    pub fn line_to_row(&self, line: usize) -> usize {
+        if self.identity {
+            return line;
+        }
+        self.first_rows[line.min(self.first_rows.len() - 1)]
+    }
+
+    // Row the caret at `point` is drawn on, a caret on a break belongs to the
+    // row starting there
+    pub fn point_to_row(&self, point: Point) -> usize {
+        if self.identity {
+            return point.y as usize;
+        }
+        let line = point.y as usize;
+        let x = point.x as usize;
+        let sub_row = match self.breaks.get(line) {
+            Some(breaks) if !self.is_hidden(line) => breaks.iter().take_while(|&&b| b <= x).count(),
+            _ => 0,
+        };
+        self.line_to_row(line) + sub_row
+    }
+
+    pub fn display_line(&self, text: &Rope, row: usize) -> DisplayLine {
+        if self.identity {
+            let line = row.min(text.len_lines() - 1);
+            return DisplayLine {
+                line,
+                start: 0,
+                end: text.line(line).len_chars(),
+                folded: false,
+            };
+        }
+
+        let row = row.min(self.row_count(text) - 1);
+        // hidden lines share their first row with the next visible line, so
+        // take the last line starting at or before `row`
+        let line = match self.first_rows.binary_search_by(|&first| {
+            if first <= row {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }) {
+            Ok(line) | Err(line) => line - 1,
+        };
+
+        let sub_row = row - self.first_rows[line];
+        let breaks = &self.breaks[line];
+        let is_last_row = sub_row == breaks.len();
+        DisplayLine {
+            line,
+            start: if sub_row == 0 { 0 } else { breaks[sub_row - 1] },
+            end: match breaks.get(sub_row) {
+                Some(&end) => end,
+                None => text.line(line).len_chars(),
+            },
+            folded: is_last_row && self.folded_headers.binary_search(&line).is_ok(),
+        }
+    }
+
+    pub fn display_lines(&self, text: &Rope, rows: std::ops::Range<usize>) -> Vec<DisplayLine> {
+        let end = rows.end.min(self.row_count(text));
+        (rows.start.min(end)..end)
+            .map(|row| self.display_line(text, row))
+            .collect()
+    }
+}
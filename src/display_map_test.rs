@@ -0,0 +1,153 @@
+use ropey::Rope;
+
+use crate::cursor::Point;
+use crate::display_map::{wrap_line, DisplayLine, DisplayMap};
+use crate::folding::{Fold, Folds};
+use crate::text::Text;
+
+fn breaks(line: &str, width: usize) -> Vec<usize> {
+    wrap_line(Rope::from_str(line).slice(..), width)
+}
+
+fn sync(map: &mut DisplayMap, text: &mut Text, folds: &Folds) {
+    let edits = text.take_edits();
+    map.update(text.get_text(), text.revision(), &edits, Some(8), folds);
+}
+
+fn rows(map: &DisplayMap, text: &Text) -> Vec<DisplayLine> {
+    let text = text.get_text();
+    map.display_lines(text, 0..map.row_count(text))
+}
+
+#[test]
+fn wrap_at_word_boundaries() {
+    assert_eq!(breaks("hello world foo\n", 8), vec![6, 12]);
+    assert_eq!(breaks("short\n", 8), Vec::<usize>::new());
+}
+
+#[test]
+fn wrap_lets_whitespace_hang() {
+    assert_eq!(breaks("abcd    efgh", 4), vec![8]);
+}
+
+#[test]
+fn wrap_keeps_indentation_with_first_word() {
+    assert_eq!(breaks("    abcdef", 6), vec![6]);
+}
+
+#[test]
+fn wrap_breaks_long_words() {
+    assert_eq!(breaks("abcdefghij", 4), vec![4, 8]);
+    assert_eq!(breaks("ab cdefghij", 4), vec![3, 7]);
+}
+
+#[test]
+fn wrap_counts_wide_characters() {
+    assert_eq!(breaks("日本語です", 4), vec![2, 4]);
+}
+
+#[test]
+fn map_rows_of_wrapped_text() {
+    let text = Rope::from_str("one\nhello world foo\nend");
+    let mut map = DisplayMap::new();
+    map.update(&text, 0, &[], Some(8), &Folds::new());
+
+    assert_eq!(map.row_count(&text), 5);
+    assert_eq!(map.line_to_row(2), 4);
+    assert_eq!(
+        map.display_line(&text, 2),
+        DisplayLine {
+            line: 1,
+            start: 6,
+            end: 12,
+            folded: false
+        }
+    );
+    assert!(map.display_line(&text, 2).is_continuation());
+    assert_eq!(map.point_to_row(Point { x: 5, y: 1 }), 1);
+    assert_eq!(map.point_to_row(Point { x: 6, y: 1 }), 2);
+}
+
+#[test]
+fn map_without_wrapping_is_line_per_row() {
+    let text = Rope::from_str("one\nhello world foo\nend");
+    let mut map = DisplayMap::new();
+    map.update(&text, 0, &[], None, &Folds::new());
+
+    assert_eq!(map.row_count(&text), 3);
+    assert_eq!(map.point_to_row(Point { x: 12, y: 1 }), 1);
+    assert_eq!(
+        map.display_line(&text, 1),
+        DisplayLine {
+            line: 1,
+            start: 0,
+            end: 16,
+            folded: false
+        }
+    );
+}
+
+#[test]
+fn map_skips_folded_lines() {
+    let text = Rope::from_str("fn a() {\n    x\n    y\n}\nend");
+    let mut folds = Folds::new();
+    folds.add(Fold { start: 0, end: 2 });
+
+    let mut map = DisplayMap::new();
+    map.update(&text, 0, &[], None, &folds);
+
+    assert_eq!(map.row_count(&text), 3);
+    assert_eq!(map.line_to_row(3), 1);
+    assert!(map.display_line(&text, 0).folded);
+    assert_eq!(map.display_line(&text, 1).line, 3);
+    assert_eq!(map.display_line(&text, 2).line, 4);
+}
+
+#[test]
+fn map_folds_wrapped_lines() {
+    let text = Rope::from_str("a:\n  hello world\nb");
+    let mut folds = Folds::new();
+    folds.add(Fold { start: 0, end: 1 });
+
+    let mut map = DisplayMap::new();
+    map.update(&text, 0, &[], Some(8), &folds);
+    assert_eq!(map.row_count(&text), 2);
+
+    map.update(&text, 0, &[], Some(8), &Folds::new());
+    assert_eq!(map.row_count(&text), 4);
+    assert_eq!(map.display_line(&text, 2).start, 8);
+}
+
+#[test]
+fn map_follows_edits() {
+    let mut text = Text::new("one\nhello world foo\nend".as_bytes());
+    let mut folds = Folds::new();
+    let mut map = DisplayMap::new();
+    sync(&mut map, &mut text, &folds);
+
+    text.get_cursor().position.y = 2;
+    text.insert_text("long line here\nx ");
+    folds.add(Fold { start: 0, end: 1 });
+    sync(&mut map, &mut text, &folds);
+
+    let mut fresh = DisplayMap::new();
+    fresh.update(text.get_text(), 0, &[], Some(8), &folds);
+    assert_eq!(rows(&map, &text), rows(&fresh, &text));
+    assert_eq!(map.row_count(text.get_text()), 5);
+}
+
+#[test]
+fn map_rewraps_after_missed_edits() {
+    let mut text = Text::new("a\nb".as_bytes());
+    let folds = Folds::new();
+    let mut map = DisplayMap::new();
+    sync(&mut map, &mut text, &folds);
+
+    text.insert_text("hello world ");
+    text.take_edits();
+    text.get_cursor().position = Point { x: 0, y: 1 };
+    text.insert_text("x");
+    sync(&mut map, &mut text, &folds);
+
+    assert_eq!(map.row_count(text.get_text()), 3);
+}
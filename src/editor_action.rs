@@ -1,8 +1,5 @@
-use std::fs;
-
-use memchr::memchr_iter;
-
-use crate::{app::App, scroll::scroll_to, text::{remove_crlf_from_buff, DeleteDirection, Selection}};
+use crate::file_format::{split_line_endings, LineEnding};
+use crate::{app::{close_tab, find_bytes, find_next_bytes, fold_at_cursor, new_file, next_tab, prev_tab, reopen_closed_tab, save, save_as, set_font_size, sync_display_map, toggle_hex_view, toggle_soft_wrap, unfold_at_cursor, App}, scroll::scroll_to, text::{DeleteDirection, Selection}};
 
 #[derive(Clone, Copy)]
 pub enum EditorAction {
@@ -52,125 +49,232 @@ pub enum EditorAction {
     Redo,
 
     Save,
+    SaveAs,
+    NewFile,
+
+    NextTab,
+    PrevTab,
+    CloseTab,
+    ReopenClosedTab,
+
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+
+    ToggleSoftWrap,
+    ToggleLineEnding,
+
+    Fold,
+    Unfold,
+
+    ToggleHexView,
+    FindBytes,
+    FindNext,
+}
+
+// Up and down go by screen rows, which differ from lines when wrapping
+fn move_cursor_rows(app: &mut App, by: i64, selection: Selection) {
+    sync_display_map(app);
+    app.buffer.text.move_cursor_rows(by, selection, &app.buffer.display_map);
+}
+
+// The hex view only moves and overwrites, anything that would change the
+// length of the bytes does nothing. False for actions that work the same in
+// both views.
+fn dispatch_hex_action(app: &mut App, action: EditorAction) -> bool {
+    let hex = match &mut app.buffer.hex {
+        Some(hex) => hex,
+        None => return false,
+    };
+    match action {
+        EditorAction::CursorUp | EditorAction::CursorUpSelect => hex.move_rows(-1),
+        EditorAction::CursorDown | EditorAction::CursorDownSelect => hex.move_rows(1),
+        EditorAction::CursorLeft | EditorAction::CursorLeftSelect => hex.move_nibbles(-1),
+        EditorAction::CursorRight | EditorAction::CursorRightSelect => hex.move_nibbles(1),
+        EditorAction::CursorPrevWord | EditorAction::CursorPrevWordSelect => hex.move_bytes(-1),
+        EditorAction::CursorNextWord | EditorAction::CursorNextWordSelect => hex.move_bytes(1),
+        EditorAction::CursorHome | EditorAction::CursorHomeSelect => hex.move_to_row_start(),
+        EditorAction::CursorEnd | EditorAction::CursorEndSelect => hex.move_to_row_end(),
+        EditorAction::CursorPageDown | EditorAction::CursorPageDownSelect => {
+            hex.move_rows(20);
+            let scroll_target = app.buffer.scroll.target_scroll.y + 20.0;
+            scroll_to(&mut app.buffer.scroll, scroll_target);
+        }
+        EditorAction::CursorPageUp | EditorAction::CursorPageUpSelect => {
+            hex.move_rows(-20);
+            let scroll_target = (app.buffer.scroll.target_scroll.y - 20.0).max(0.0);
+            scroll_to(&mut app.buffer.scroll, scroll_target);
+        }
+        EditorAction::CursorEndOfFile | EditorAction::CursorEndOfFileSelect => {
+            hex.cursor = hex.bytes.len().saturating_sub(1) * 2;
+        }
+        EditorAction::CursorBeginningOfFile | EditorAction::CursorBeginningOfFileSelect => hex.cursor = 0,
+        EditorAction::Undo => hex.undo(),
+        EditorAction::Redo => hex.redo(),
+        EditorAction::Save
+        | EditorAction::SaveAs
+        | EditorAction::NewFile
+        | EditorAction::NextTab
+        | EditorAction::PrevTab
+        | EditorAction::CloseTab
+        | EditorAction::ReopenClosedTab
+        | EditorAction::ZoomIn
+        | EditorAction::ZoomOut
+        | EditorAction::ZoomReset
+        | EditorAction::ToggleHexView
+        | EditorAction::FindBytes
+        | EditorAction::FindNext => return false,
+        _ => {}
+    }
+    true
 }
 
 pub fn dispatch_action(app: &mut App, action: EditorAction) {
+    if dispatch_hex_action(app, action) {
+        return;
+    }
+
     match action {
         EditorAction::CursorUp => {
-            app.text.move_cursor_y(-1, Selection::NotSelect);
+            move_cursor_rows(app, -1, Selection::NotSelect);
         }
         EditorAction::CursorUpSelect => {
-            app.text.move_cursor_y(-1, Selection::Select);
+            move_cursor_rows(app, -1, Selection::Select);
         }
         EditorAction::CursorDown => {
-            app.text.move_cursor_y(1, Selection::NotSelect);
+            move_cursor_rows(app, 1, Selection::NotSelect);
         }
         EditorAction::CursorDownSelect => {
-            app.text.move_cursor_y(1, Selection::Select);
+            move_cursor_rows(app, 1, Selection::Select);
         }
         EditorAction::CursorLeft => {
-            app.text.move_cursor(-1, Selection::NotSelect);
+            app.buffer.text.move_cursor(-1, Selection::NotSelect);
         }
         EditorAction::CursorLeftSelect => {
-            app.text.move_cursor(-1, Selection::Select);
+            app.buffer.text.move_cursor(-1, Selection::Select);
         }
         EditorAction::CursorPrevWord => {
-            app.text.move_to_prev_word(Selection::NotSelect);
+            app.buffer.text.move_to_prev_word(Selection::NotSelect);
         }
         EditorAction::CursorPrevWordSelect => {
-            app.text.move_to_prev_word(Selection::Select);
+            app.buffer.text.move_to_prev_word(Selection::Select);
         }
         EditorAction::CursorRight => {
-            app.text.move_cursor(1, Selection::NotSelect);
+            app.buffer.text.move_cursor(1, Selection::NotSelect);
         }
         EditorAction::CursorRightSelect => {
-            app.text.move_cursor(1, Selection::Select);
+            app.buffer.text.move_cursor(1, Selection::Select);
         }
-        EditorAction::CursorNextWord => app.text.move_to_next_word(Selection::NotSelect),
-        EditorAction::CursorNextWordSelect => app.text.move_to_next_word(Selection::Select),
+        EditorAction::CursorNextWord => app.buffer.text.move_to_next_word(Selection::NotSelect),
+        EditorAction::CursorNextWordSelect => app.buffer.text.move_to_next_word(Selection::Select),
         EditorAction::CursorHome => {
-            app.text.move_to_beginning_of_line(Selection::NotSelect);
+            app.buffer.text.move_to_beginning_of_line(Selection::NotSelect);
         }
         EditorAction::CursorHomeSelect => {
-            app.text.move_to_beginning_of_line(Selection::Select);
+            app.buffer.text.move_to_beginning_of_line(Selection::Select);
         }
-        EditorAction::CursorEnd => app.text.move_to_end_of_line(Selection::NotSelect),
-        EditorAction::CursorEndSelect => app.text.move_to_end_of_line(Selection::Select),
+        EditorAction::CursorEnd => app.buffer.text.move_to_end_of_line(Selection::NotSelect),
+        EditorAction::CursorEndSelect => app.buffer.text.move_to_end_of_line(Selection::Select),
         EditorAction::CursorPageDown => {
-			app.text.move_cursor_y(20, Selection::NotSelect);
-			let scroll_target = app.scroll.target_scroll.y + 20.0;
-			scroll_to(&mut app.scroll, scroll_target);
+			move_cursor_rows(app, 20, Selection::NotSelect);
+			let scroll_target = app.buffer.scroll.target_scroll.y + 20.0;
+			scroll_to(&mut app.buffer.scroll, scroll_target);
         }
         EditorAction::CursorPageDownSelect => {
-			app.text.move_cursor_y(20, Selection::Select);
-			let scroll_target = app.scroll.target_scroll.y + 20.0;
-			scroll_to(&mut app.scroll, scroll_target);
+			move_cursor_rows(app, 20, Selection::Select);
+			let scroll_target = app.buffer.scroll.target_scroll.y + 20.0;
+			scroll_to(&mut app.buffer.scroll, scroll_target);
         }
         EditorAction::CursorPageUp => {
-			app.text.move_cursor_y(-20, Selection::NotSelect);
-			let scroll_target = (app.scroll.target_scroll.y - 20.0).max(0.0);
-			scroll_to(&mut app.scroll, scroll_target);
+			move_cursor_rows(app, -20, Selection::NotSelect);
+			let scroll_target = (app.buffer.scroll.target_scroll.y - 20.0).max(0.0);
+			scroll_to(&mut app.buffer.scroll, scroll_target);
         }
         EditorAction::CursorPageUpSelect => {
-			app.text.move_cursor_y(-20, Selection::Select);
-			let scroll_target = (app.scroll.target_scroll.y - 20.0).max(0.0);
-			scroll_to(&mut app.scroll, scroll_target);
+			move_cursor_rows(app, -20, Selection::Select);
+			let scroll_target = (app.buffer.scroll.target_scroll.y - 20.0).max(0.0);
+			scroll_to(&mut app.buffer.scroll, scroll_target);
         }
         EditorAction::CursorEndOfFile => {
-            app.text.move_to_end(Selection::NotSelect);
+            app.buffer.text.move_to_end(Selection::NotSelect);
         }
         EditorAction::CursorEndOfFileSelect => {
-            app.text.move_to_end(Selection::Select);
+            app.buffer.text.move_to_end(Selection::Select);
         }
         EditorAction::CursorBeginningOfFile => {
-            app.text.move_to_begging(Selection::NotSelect);
+            app.buffer.text.move_to_begging(Selection::NotSelect);
         }
         EditorAction::CursorBeginningOfFileSelect => {
-            app.text.move_to_begging(Selection::Select);
+            app.buffer.text.move_to_begging(Selection::Select);
         }
         EditorAction::Copy => {
-            if let Some(selection_text) = app.text.get_selection_str() {
+            if let Some(selection_text) = app.buffer.text.get_selection_str() {
                 app.window.set_clipboard_string(selection_text.as_str());
             } else {
-                let selection_str = app.text.get_current_line();
+                let selection_str = app.buffer.text.get_current_line();
                 app.window.set_clipboard_string(selection_str.as_str());
             }
         }
         EditorAction::Cut => {
-            if let Some(removed_text) = app.text.remove_selection() {
+            if let Some(removed_text) = app.buffer.text.remove_selection() {
                 app.window.set_clipboard_string(removed_text.as_str());
             } else {
-                let removed_text = app.text.remove_current_line();
+                let removed_text = app.buffer.text.remove_current_line();
                 app.window.set_clipboard_string(removed_text.as_str());
             }
         }
         EditorAction::Paste => {
-            if let Some(mut s) = app.window.get_clipboard_string() {
-                unsafe {
-                    let m = s.as_bytes_mut();
-                    let mut matches: Vec<usize> = memchr_iter('\r' as u8, m).collect();
-                    matches.push(m.len());
-                    remove_crlf_from_buff(m, &matches);
-                    s.truncate(s.len() - (matches.len() - 1));
-                }
-                app.text.insert_text(s.as_str())
+            if let Some(s) = app.window.get_clipboard_string() {
+                let (s, _, _) = split_line_endings(&s);
+                app.buffer.text.insert_text(s.as_str())
             }
         }
         EditorAction::DeleteForward => {
-            app.text.delete_text(DeleteDirection::Forward);
+            app.buffer.text.delete_text(DeleteDirection::Forward);
         }
         EditorAction::DeleteBackward => {
-            app.text.delete_text(DeleteDirection::Back);
+            app.buffer.text.delete_text(DeleteDirection::Back);
         }
 
         EditorAction::Undo => {
-            app.text.undo();
+            app.buffer.text.undo();
         }
         EditorAction::Redo => {
-            app.text.redo();
+            app.buffer.text.redo();
         }
         EditorAction::Save => {
-            fs::write(&app.file_path, &app.text.get_text().to_string()).unwrap();
+            save(app);
+        }
+        EditorAction::SaveAs => save_as(app),
+        EditorAction::NewFile => new_file(app),
+        EditorAction::NextTab => next_tab(app),
+        EditorAction::PrevTab => prev_tab(app),
+        EditorAction::CloseTab => close_tab(app),
+        EditorAction::ReopenClosedTab => reopen_closed_tab(app),
+        EditorAction::ZoomIn => {
+            let font_size = app.font_renderer.font_size + 1;
+            set_font_size(app, font_size);
+        }
+        EditorAction::ZoomOut => {
+            let font_size = app.font_renderer.font_size.saturating_sub(1);
+            set_font_size(app, font_size);
+        }
+        EditorAction::ZoomReset => {
+            let font_size = app.config.font_size;
+            set_font_size(app, font_size);
+        }
+        EditorAction::ToggleSoftWrap => toggle_soft_wrap(app),
+        EditorAction::ToggleLineEnding => {
+            let line_ending = match app.buffer.text.line_ending {
+                LineEnding::Crlf => LineEnding::Lf,
+                LineEnding::Lf | LineEnding::Cr => LineEnding::Crlf,
+            };
+            app.buffer.text.set_line_ending(line_ending);
         }
+        EditorAction::Fold => fold_at_cursor(app),
+        EditorAction::Unfold => unfold_at_cursor(app),
+        EditorAction::ToggleHexView => toggle_hex_view(app),
+        EditorAction::FindBytes => find_bytes(app),
+        EditorAction::FindNext => find_next_bytes(app),
     }
 }
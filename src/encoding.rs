@@ -0,0 +1,326 @@
+use crate::file_format::LONE_CR;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+    Latin1,
+}
+
+// Windows-1252 characters for bytes 0x80..0xA0, the five bytes it leaves
+// undefined map to the C1 control with the same value so they round trip
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+// Bytes that don't decode are kept as one character each from the end of the
+// last private use plane and turn back into the same byte on save. Real
+// characters from that range are kept as their bytes too, so decoding and
+// encoding again always gives back the file.
+const RAW_BYTES: u32 = 0x10FF00;
+
+// Characters files can't bring in as themselves, `LONE_CR` and the raw bytes
+fn is_reserved(c: char) -> bool {
+    c >= LONE_CR
+}
+
+pub fn raw_byte_char(byte: u8) -> char {
+    std::char::from_u32(RAW_BYTES + byte as u32).unwrap()
+}
+
+pub fn raw_byte(c: char) -> Option<u8> {
+    let c = c as u32;
+    if c >= RAW_BYTES {
+        Some((c - RAW_BYTES) as u8)
+    } else {
+        None
+    }
+}
+
+// Byte a character is drawn as, a kept '\r' shows as the byte it saves to
+pub fn shown_byte(c: char) -> Option<u8> {
+    match c {
+        LONE_CR => Some(b'\r'),
+        c => raw_byte(c),
+    }
+}
+
+pub fn has_raw_bytes(text: &str) -> bool {
+    text.chars().any(|c| shown_byte(c).is_some())
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+// How a file is stored on disk, the byte order mark is kept as it was found
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileEncoding {
+    pub encoding: Encoding,
+    pub bom: bool,
+}
+
+impl FileEncoding {
+    pub const UTF8: FileEncoding = FileEncoding {
+        encoding: Encoding::Utf8,
+        bom: false,
+    };
+
+    pub const CHOICES: [FileEncoding; 6] = [
+        FileEncoding::UTF8,
+        FileEncoding {
+            encoding: Encoding::Utf8,
+            bom: true,
+        },
+        FileEncoding {
+            encoding: Encoding::Utf16Le,
+            bom: true,
+        },
+        FileEncoding {
+            encoding: Encoding::Utf16Be,
+            bom: true,
+        },
+        FileEncoding {
+            encoding: Encoding::Windows1252,
+            bom: false,
+        },
+        FileEncoding {
+            encoding: Encoding::Latin1,
+            bom: false,
+        },
+    ];
+
+    pub fn name(self) -> String {
+        let name = match self.encoding {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::Latin1 => "ISO-8859-1",
+        };
+        match (self.encoding, self.bom) {
+            (Encoding::Utf8, true) => format!("{} with BOM", name),
+            (Encoding::Utf16Le, false) | (Encoding::Utf16Be, false) => {
+                format!("{} without BOM", name)
+            }
+            _ => name.to_owned(),
+        }
+    }
+
+    fn bom_bytes(self) -> &'static [u8] {
+        match self.encoding {
+            _ if !self.bom => &[],
+            Encoding::Utf8 => UTF8_BOM,
+            Encoding::Utf16Le => UTF16_LE_BOM,
+            Encoding::Utf16Be => UTF16_BE_BOM,
+            Encoding::Windows1252 | Encoding::Latin1 => &[],
+        }
+    }
+
+    // Bytes are cut short of the byte order mark if there is one
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let bom = self.bom_bytes();
+        let bytes = if bytes.starts_with(bom) {
+            &bytes[bom.len()..]
+        } else {
+            bytes
+        };
+
+        match self.encoding {
+            Encoding::Utf8 => decode_utf8(bytes),
+            Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Encoding::Windows1252 => bytes.iter().map(|&byte| windows_1252_char(byte)).collect(),
+            Encoding::Latin1 => bytes.iter().map(|&byte| byte as char).collect(),
+        }
+    }
+
+    // The first character that can't be stored is the error
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        let mut bytes = self.bom_bytes().to_vec();
+        match self.encoding {
+            Encoding::Utf8 => {
+                let mut buffer = [0; 4];
+                for c in text.chars() {
+                    match raw_byte(c) {
+                        Some(byte) => bytes.push(byte),
+                        None => bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes()),
+                    }
+                }
+            }
+            Encoding::Utf16Le => encode_utf16(text, &mut bytes, u16::to_le_bytes),
+            Encoding::Utf16Be => encode_utf16(text, &mut bytes, u16::to_be_bytes),
+            Encoding::Windows1252 | Encoding::Latin1 => {
+                for c in text.chars() {
+                    let byte = match (raw_byte(c), self.encoding) {
+                        (Some(byte), _) => Some(byte),
+                        (None, Encoding::Windows1252) => windows_1252_byte(c),
+                        (None, _) if (c as u32) <= 0xFF => Some(c as u8),
+                        (None, _) => None,
+                    };
+                    bytes.push(byte.ok_or(c)?);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+fn push_raw_bytes(text: &mut String, bytes: &[u8]) {
+    text.extend(bytes.iter().map(|&byte| raw_byte_char(byte)));
+}
+
+fn decode_utf8(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(error) => {
+                let valid = std::str::from_utf8(&rest[..error.valid_up_to()]).unwrap();
+                (valid, error.error_len().unwrap_or(rest.len() - valid.len()))
+            }
+        };
+
+        for c in valid.chars() {
+            if is_reserved(c) {
+                let mut buffer = [0; 4];
+                push_raw_bytes(&mut text, c.encode_utf8(&mut buffer).as_bytes());
+            } else {
+                text.push(c);
+            }
+        }
+        let end = valid.len() + invalid;
+        push_raw_bytes(&mut text, &rest[valid.len()..end]);
+        rest = &rest[end..];
+    }
+    text
+}
+
+// Unpaired surrogates and an odd last byte are kept as raw bytes
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let mut text = String::with_capacity(bytes.len() / 2);
+    let pairs = bytes.chunks_exact(2);
+    let odd = pairs.remainder();
+    let units = pairs.map(|pair| unit([pair[0], pair[1]]));
+
+    let mut offset = 0;
+    for c in std::char::decode_utf16(units) {
+        let len = c.as_ref().map_or(1, |c| c.len_utf16()) * 2;
+        match c {
+            Ok(c) if !is_reserved(c) => text.push(c),
+            _ => push_raw_bytes(&mut text, &bytes[offset..offset + len]),
+        }
+        offset += len;
+    }
+    push_raw_bytes(&mut text, odd);
+    text
+}
+
+fn encode_utf16(text: &str, bytes: &mut Vec<u8>, unit_bytes: fn(u16) -> [u8; 2]) {
+    let mut buffer = [0; 2];
+    for c in text.chars() {
+        match raw_byte(c) {
+            Some(byte) => bytes.push(byte),
+            None => {
+                for &unit in c.encode_utf16(&mut buffer).iter() {
+                    bytes.extend_from_slice(&unit_bytes(unit));
+                }
+            }
+        }
+    }
+}
+
+fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252[byte as usize - 0x80],
+        _ => byte as char,
+    }
+}
+
+fn windows_1252_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0..=0x7F | 0xA0..=0xFF => Some(c as u8),
+        _ => WINDOWS_1252
+            .iter()
+            .position(|&special| special == c)
+            .map(|index| 0x80 + index as u8),
+    }
+}
+
+// UTF-16 without a byte order mark shows up as every other byte being zero
+fn looks_like_utf16(bytes: &[u8]) -> Option<Encoding> {
+    if bytes.len() < 2 || bytes.len() % 2 != 0 {
+        return None;
+    }
+
+    let pairs = bytes.len() / 2;
+    let even_zeros = bytes.iter().step_by(2).filter(|&&byte| byte == 0).count();
+    let odd_zeros = bytes[1..]
+        .iter()
+        .step_by(2)
+        .filter(|&&byte| byte == 0)
+        .count();
+    if odd_zeros * 2 > pairs && even_zeros * 10 < pairs {
+        Some(Encoding::Utf16Le)
+    } else if even_zeros * 2 > pairs && odd_zeros * 10 < pairs {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+// Invalid UTF-8 still counts as UTF-8 with a few stray bytes when there are
+// valid multi byte sequences in it
+fn has_utf8_sequences(bytes: &[u8]) -> bool {
+    let mut rest = bytes;
+    loop {
+        let valid = match std::str::from_utf8(rest) {
+            Ok(valid) => valid,
+            Err(error) => std::str::from_utf8(&rest[..error.valid_up_to()]).unwrap(),
+        };
+        if !valid.is_ascii() {
+            return true;
+        }
+        if valid.len() == rest.len() {
+            return false;
+        }
+        rest = &rest[valid.len() + 1..];
+    }
+}
+
+// Byte order mark first, then UTF-16 by its zero bytes, then UTF-8 unless
+// the file looks like some other single byte encoding, which is taken as
+// Windows-1252
+pub fn detect(bytes: &[u8]) -> FileEncoding {
+    let with_bom = |encoding| FileEncoding {
+        encoding,
+        bom: true,
+    };
+    if bytes.starts_with(UTF8_BOM) {
+        return with_bom(Encoding::Utf8);
+    }
+    if bytes.starts_with(UTF16_LE_BOM) {
+        return with_bom(Encoding::Utf16Le);
+    }
+    if bytes.starts_with(UTF16_BE_BOM) {
+        return with_bom(Encoding::Utf16Be);
+    }
+
+    let encoding = if let Some(encoding) = looks_like_utf16(bytes) {
+        encoding
+    } else if std::str::from_utf8(bytes).is_ok() || has_utf8_sequences(bytes) {
+        Encoding::Utf8
+    } else {
+        Encoding::Windows1252
+    };
+    FileEncoding {
+        encoding,
+        bom: false,
+    }
+}
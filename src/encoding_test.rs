@@ -0,0 +1,139 @@
+use crate::encoding::{detect, raw_byte, raw_byte_char, Encoding, FileEncoding};
+
+fn encoding(encoding: Encoding, bom: bool) -> FileEncoding {
+    FileEncoding { encoding, bom }
+}
+
+#[test]
+fn detects_byte_order_marks() {
+    assert_eq!(detect(b"\xEF\xBB\xBFabc"), encoding(Encoding::Utf8, true));
+    assert_eq!(detect(b"\xFF\xFEa\x00"), encoding(Encoding::Utf16Le, true));
+    assert_eq!(detect(b"\xFE\xFF\x00a"), encoding(Encoding::Utf16Be, true));
+}
+
+#[test]
+fn detects_without_byte_order_mark() {
+    assert_eq!(detect("zażółć".as_bytes()), FileEncoding::UTF8);
+    assert_eq!(detect(b""), FileEncoding::UTF8);
+    assert_eq!(
+        detect(b"a\x00b\x00c\x00"),
+        encoding(Encoding::Utf16Le, false)
+    );
+    assert_eq!(
+        detect(b"\x00a\x00b\x00c"),
+        encoding(Encoding::Utf16Be, false)
+    );
+    assert_eq!(detect(b"caf\xE9"), encoding(Encoding::Windows1252, false));
+}
+
+#[test]
+fn decodes_and_skips_byte_order_mark() {
+    assert_eq!(
+        encoding(Encoding::Utf8, true).decode(b"\xEF\xBB\xBFabc"),
+        "abc"
+    );
+    assert_eq!(
+        encoding(Encoding::Utf16Le, true).decode(b"\xFF\xFE\x42\x01a\x00"),
+        "\u{142}a"
+    );
+    assert_eq!(
+        encoding(Encoding::Utf16Be, false).decode(b"\xD8\x3D\xDE\x00"),
+        "\u{1F600}"
+    );
+    assert_eq!(
+        encoding(Encoding::Windows1252, false).decode(b"\x80 \xE9"),
+        "\u{20AC} \u{E9}"
+    );
+    assert_eq!(encoding(Encoding::Latin1, false).decode(b"\x80"), "\u{80}");
+}
+
+#[test]
+fn encodes_with_byte_order_mark() {
+    assert_eq!(
+        encoding(Encoding::Utf8, true).encode("a"),
+        Ok(b"\xEF\xBB\xBFa".to_vec())
+    );
+    assert_eq!(
+        encoding(Encoding::Utf16Be, true).encode("a"),
+        Ok(b"\xFE\xFF\x00a".to_vec())
+    );
+    assert_eq!(
+        encoding(Encoding::Utf16Le, false).encode("\u{1F600}"),
+        Ok(b"\x3D\xD8\x00\xDE".to_vec())
+    );
+}
+
+#[test]
+fn single_byte_encodings_round_trip() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    for &single in [Encoding::Windows1252, Encoding::Latin1].iter() {
+        let encoding = encoding(single, false);
+        assert_eq!(encoding.encode(&encoding.decode(&bytes)), Ok(bytes.clone()));
+    }
+}
+
+#[test]
+fn reports_characters_that_dont_fit() {
+    assert_eq!(
+        encoding(Encoding::Windows1252, false).encode("a\u{20AC}\u{142}"),
+        Err('\u{142}')
+    );
+    assert_eq!(
+        encoding(Encoding::Latin1, false).encode("\u{20AC}"),
+        Err('\u{20AC}')
+    );
+}
+
+#[test]
+fn keeps_invalid_utf8_as_raw_bytes() {
+    let bytes = b"a\xFFb\xC3\xA9\xE2\x82";
+    let text = FileEncoding::UTF8.decode(bytes);
+
+    assert_eq!(
+        text,
+        format!(
+            "a{}b\u{E9}{}{}",
+            raw_byte_char(0xFF),
+            raw_byte_char(0xE2),
+            raw_byte_char(0x82)
+        )
+    );
+    assert_eq!(
+        text.chars().filter_map(raw_byte).collect::<Vec<u8>>(),
+        vec![0xFF, 0xE2, 0x82]
+    );
+    assert_eq!(FileEncoding::UTF8.encode(&text), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn raw_byte_characters_in_the_file_round_trip() {
+    let bytes = "x\u{10FF41}y".as_bytes();
+    let text = FileEncoding::UTF8.decode(bytes);
+
+    assert_eq!(text.chars().count(), 6);
+    assert_eq!(FileEncoding::UTF8.encode(&text), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn kept_cr_character_in_the_file_round_trips() {
+    let bytes = "x\u{10FEFF}y".as_bytes();
+    let text = FileEncoding::UTF8.decode(bytes);
+
+    assert_eq!(text.chars().count(), 6);
+    assert_eq!(FileEncoding::UTF8.encode(&text), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn unpaired_surrogates_round_trip() {
+    let bytes = b"a\x00\x00\xD8b\x00\xFF";
+    let encoding = encoding(Encoding::Utf16Le, false);
+    let text = encoding.decode(bytes);
+
+    assert!(text.starts_with('a'));
+    assert_eq!(encoding.encode(&text), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn stray_bytes_in_utf8_stay_utf8() {
+    assert_eq!(detect(b"caf\xC3\xA9 \xFF"), FileEncoding::UTF8);
+}
@@ -0,0 +1,124 @@
+// Ropey breaks lines at a lone '\r' as well, one that doesn't end a line is
+// kept as this character and turns back into '\r' on save. It sits right
+// below the raw byte characters and is reserved the same way, so it can't be
+// mistaken for an undecodable 0x0D byte.
+pub const LONE_CR: char = '\u{10FEFF}';
+
+// Line breaks are always `\n` inside `Text`, this is what they turn into on
+// save
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub const ALL: [LineEnding; 3] = [LineEnding::Lf, LineEnding::Crlf, LineEnding::Cr];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+            LineEnding::Cr => "CR",
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineEndingCount {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl LineEndingCount {
+    pub fn of(text: &str) -> LineEndingCount {
+        let mut count = LineEndingCount::default();
+        let mut bytes = text.bytes().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'\r' if bytes.peek() == Some(&b'\n') => {
+                    bytes.next();
+                    count.crlf += 1;
+                }
+                b'\r' => count.cr += 1,
+                b'\n' => count.lf += 1,
+                _ => {}
+            }
+        }
+        count
+    }
+
+    // The most used one, LF when there are none. Ties go to LF, then CRLF.
+    pub fn dominant(&self) -> LineEnding {
+        if self.cr > self.lf && self.cr > self.crlf {
+            LineEnding::Cr
+        } else if self.crlf > self.lf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn is_mixed(&self) -> bool {
+        [self.lf, self.crlf, self.cr]
+            .iter()
+            .filter(|&&count| count > 0)
+            .count()
+            > 1
+    }
+}
+
+// Text with `\n` line breaks, the line ending it had and whether it had more
+// than one kind. A lone '\r' in a file that doesn't end lines with it is
+// kept as `LONE_CR`. A file with more than one kind breaks lines at LF if it
+// has any and keeps the '\r' of its other lines as `LONE_CR`, so saving
+// leaves every line as it was.
+pub fn split_line_endings(text: &str) -> (String, LineEnding, bool) {
+    let count = LineEndingCount::of(text);
+    let line_ending = match (count.is_mixed(), count.lf > 0) {
+        (true, true) => LineEnding::Lf,
+        (true, false) => LineEnding::Crlf,
+        (false, _) => count.dominant(),
+    };
+    if count.cr == 0 && count.crlf == 0 {
+        return (text.to_owned(), line_ending, false);
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' if chars.peek() == Some(&'\n') && line_ending == LineEnding::Crlf => {
+                chars.next();
+                result.push('\n');
+            }
+            '\r' if line_ending == LineEnding::Cr => result.push('\n'),
+            '\r' => result.push(LONE_CR),
+            c => result.push(c),
+        }
+    }
+    (result, line_ending, count.is_mixed())
+}
+
+// Text as it is written to disk
+pub fn with_line_ending(text: &str, line_ending: LineEnding) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' => result.push_str(line_ending.as_str()),
+            LONE_CR => result.push('\r'),
+            c => result.push(c),
+        }
+    }
+    result
+}
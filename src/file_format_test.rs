@@ -0,0 +1,74 @@
+use crate::file_format::{
+    split_line_endings, with_line_ending, LineEnding, LineEndingCount, LONE_CR,
+};
+
+#[test]
+fn counts_line_endings() {
+    let count = LineEndingCount::of("a\r\nb\nc\rd\r\n");
+
+    assert_eq!(
+        count,
+        LineEndingCount {
+            lf: 1,
+            crlf: 2,
+            cr: 1
+        }
+    );
+    assert_eq!(count.dominant(), LineEnding::Crlf);
+    assert!(count.is_mixed());
+}
+
+#[test]
+fn dominant_defaults_to_lf() {
+    assert_eq!(LineEndingCount::of("").dominant(), LineEnding::Lf);
+    assert_eq!(LineEndingCount::of("a\nb\r\n").dominant(), LineEnding::Lf);
+    assert_eq!(LineEndingCount::of("a\rb\r").dominant(), LineEnding::Cr);
+    assert!(!LineEndingCount::of("a\rb\r").is_mixed());
+}
+
+#[test]
+fn splits_crlf() {
+    let (text, line_ending, mixed) = split_line_endings("a\r\nb\r\n");
+
+    assert_eq!(text, "a\nb\n");
+    assert_eq!(line_ending, LineEnding::Crlf);
+    assert!(!mixed);
+}
+
+#[test]
+fn keeps_lone_cr_inside_lines() {
+    let (text, line_ending, mixed) = split_line_endings("a\rb\r\nc\r\n");
+
+    assert_eq!(text, format!("a{}b\nc\n", LONE_CR));
+    assert_eq!(line_ending, LineEnding::Crlf);
+    assert!(mixed);
+    assert_eq!(with_line_ending(&text, line_ending), "a\rb\r\nc\r\n");
+}
+
+#[test]
+fn cr_files_break_lines_at_cr() {
+    let (text, line_ending, _) = split_line_endings("a\rb\r");
+
+    assert_eq!(text, "a\nb\n");
+    assert_eq!(with_line_ending(&text, line_ending), "a\rb\r");
+}
+
+#[test]
+fn line_endings_on_save() {
+    let text = "a\nb\n";
+
+    assert_eq!(with_line_ending(text, LineEnding::Lf), "a\nb\n");
+    assert_eq!(with_line_ending(text, LineEnding::Crlf), "a\r\nb\r\n");
+    assert_eq!(with_line_ending(text, LineEnding::Cr), "a\rb\r");
+}
+
+#[test]
+fn mixed_files_keep_every_line_ending() {
+    let file = "a\r\nb\nc\r\nd\re\n";
+    let (text, line_ending, mixed) = split_line_endings(file);
+
+    assert_eq!(text, format!("a{0}\nb\nc{0}\nd{0}e\n", LONE_CR));
+    assert_eq!(line_ending, LineEnding::Lf);
+    assert!(mixed);
+    assert_eq!(with_line_ending(&text, line_ending), file);
+}
@@ -0,0 +1,231 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// What tells one version of a file from the next without reading it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    // replacing the file by a rename gives it a new inode
+    inode: u64,
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+impl FileStamp {
+    // None when there is no file
+    pub fn of(path: &Path) -> Option<FileStamp> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            inode: inode(&metadata),
+        })
+    }
+}
+
+// Same limit as the kernel puts on following links
+const MAX_SYMLINKS: usize = 40;
+
+// The file a chain of symlinks ends at, which doesn't have to exist yet
+pub fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINKS {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(dir) => dir.join(target),
+                    None => target,
+                };
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "too many levels of symbolic links",
+    ))
+}
+
+#[cfg(unix)]
+fn set_mode(options: &mut OpenOptions, mode: u32) {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(mode);
+}
+
+#[cfg(not(unix))]
+fn set_mode(_options: &mut OpenOptions, _mode: u32) {}
+
+// Opens a file next to `path` that nothing else uses, created with `mode`
+fn create_temp_file(path: &Path, mode: u32) -> io::Result<(PathBuf, File)> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .map_or("file".into(), |name| name.to_string_lossy());
+
+    let mut attempt = 0;
+    loop {
+        let temp_path = dir.join(format!(".{}.{}.{}.tmp", name, std::process::id(), attempt));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        set_mode(&mut options, mode);
+        match options.open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// False when the file at `path` can't be given the owner of `metadata`
+#[cfg(unix)]
+fn copy_owner(metadata: &fs::Metadata, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::{chown, MetadataExt};
+
+    if chown(path, Some(metadata.uid()), Some(metadata.gid())).is_ok() {
+        return Ok(true);
+    }
+    // only root can give a file away, which is fine while it is ours anyway
+    if fs::metadata(path)?.uid() != metadata.uid() {
+        return Ok(false);
+    }
+    let _ = chown(path, None, Some(metadata.gid()));
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_metadata: &fs::Metadata, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+// The owner and permissions of the file it replaces are copied before
+// anything is written, false when the owner can't be and nothing was
+// written. Closes the file when done, some systems can't rename open files.
+fn write_temp_file(
+    mut file: File,
+    temp_path: &Path,
+    metadata: Option<&fs::Metadata>,
+    bytes: &[u8],
+) -> io::Result<bool> {
+    if let Some(metadata) = metadata {
+        if !copy_owner(metadata, temp_path)? {
+            return Ok(false);
+        }
+        fs::set_permissions(temp_path, metadata.permissions())?;
+    }
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    Ok(true)
+}
+
+// Keeps the file and so its owner, a crash halfway leaves it partly written
+fn write_in_place(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+// The rename has to reach the disk too, not every system lets a directory
+// be synced so that is best effort
+fn sync_dir(path: &Path) {
+    if let Some(dir) = path.parent().filter(|dir| *dir != Path::new("")) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+// Writes to a new file next to the one at `path` and renames it over that,
+// so a crash or a full disk leaves either the old or the new contents.
+// Symlinks stay and the file they point at is replaced, keeping its
+// permissions and owner. Another user's file we may write to can't be given
+// back to them, so it is written in place instead.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let target = resolve_symlinks(path)?;
+    // the rename only needs the directory to be writable, the file itself
+    // has to be too
+    let metadata = fs::metadata(&target).ok();
+    if let Some(metadata) = &metadata {
+        if metadata.permissions().readonly() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the file is read-only",
+            ));
+        }
+        OpenOptions::new().write(true).open(&target)?;
+    }
+    // only the owner can read what replaces an existing file until it has
+    // that file's permissions, a new one gets the usual ones
+    let mode = if metadata.is_some() { 0o600 } else { 0o666 };
+    let (temp_path, file) = create_temp_file(&target, mode)?;
+
+    let written = write_temp_file(file, &temp_path, metadata.as_ref(), bytes).and_then(|owned| {
+        if owned {
+            fs::rename(&temp_path, &target)?;
+        }
+        Ok(owned)
+    });
+    if written.as_ref().map_or(true, |&owned| !owned) {
+        let _ = fs::remove_file(&temp_path);
+    }
+    if !written? {
+        return write_in_place(&target, bytes);
+    }
+
+    sync_dir(&target);
+    Ok(())
+}
+
+// Like `write_atomically`, but only the owner can ever read the file, which
+// is made anew each time. For copies of what may be private.
+pub fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let (temp_path, file) = create_temp_file(path, 0o600)?;
+    let written =
+        write_temp_file(file, &temp_path, None, bytes).and_then(|_| fs::rename(&temp_path, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written?;
+
+    sync_dir(path);
+    Ok(())
+}
+
+// Creates `dir` and what is missing above it, `dir` itself only the owner can
+// look inside
+#[cfg(unix)]
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    // it may be from before it was made private
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(not(unix))]
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
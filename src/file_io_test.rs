@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::file_io::{
+    create_private_dir, resolve_symlinks, write_atomically, write_private, FileStamp,
+};
+
+// Empty directory of its own for every test
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("editor-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn dir_entries(dir: &PathBuf) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn replaces_contents_without_leftovers() {
+    let dir = test_dir("replace");
+    let path = dir.join("a.txt");
+    fs::write(&path, "old contents").unwrap();
+
+    write_atomically(&path, b"new").unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    assert_eq!(dir_entries(&dir), vec!["a.txt"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn creates_missing_file() {
+    let dir = test_dir("create");
+    let path = dir.join("new.txt");
+
+    write_atomically(&path, b"hello").unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"hello");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fails_without_leaving_temp_files() {
+    let dir = test_dir("fail");
+    let path = dir.join("missing").join("a.txt");
+
+    assert!(write_atomically(&path, b"x").is_err());
+    assert!(dir_entries(&dir).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn refuses_read_only_files() {
+    let dir = test_dir("read-only");
+    let path = dir.join("a.txt");
+    fs::write(&path, "keep").unwrap();
+    let mut permissions = fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&path, permissions).unwrap();
+
+    assert!(write_atomically(&path, b"new").is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"keep");
+    assert_eq!(dir_entries(&dir), vec!["a.txt"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn keeps_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = test_dir("permissions");
+    let path = dir.join("run.sh");
+    fs::write(&path, "echo").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+    write_atomically(&path, b"echo hi").unwrap();
+
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn new_files_get_the_usual_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = test_dir("new-permissions");
+    let usual = dir.join("usual.txt");
+    let path = dir.join("new.txt");
+    fs::write(&usual, "").unwrap();
+
+    write_atomically(&path, b"new").unwrap();
+
+    let mode = |path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&path), mode(&usual));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn private_files_stay_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = test_dir("private").join("recovery");
+    let path = dir.join("a.txt");
+    create_private_dir(&dir).unwrap();
+    fs::write(&path, "old").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    write_private(&path, b"new").unwrap();
+
+    let mode = |path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&dir), 0o700);
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn writes_through_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let dir = test_dir("symlink");
+    fs::create_dir(dir.join("real")).unwrap();
+    let target = dir.join("real").join("a.txt");
+    let link = dir.join("link.txt");
+    fs::write(&target, "old").unwrap();
+    symlink("real/a.txt", &link).unwrap();
+
+    assert_eq!(resolve_symlinks(&link).unwrap(), dir.join("real/a.txt"));
+    write_atomically(&link, b"new").unwrap();
+
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(fs::read(&target).unwrap(), b"new");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stamps_change_with_the_file() {
+    let dir = test_dir("stamp");
+    let path = dir.join("a.txt");
+    assert_eq!(FileStamp::of(&path), None);
+
+    fs::write(&path, "one").unwrap();
+    let stamp = FileStamp::of(&path);
+    assert!(stamp.is_some());
+    assert_eq!(FileStamp::of(&path), stamp);
+
+    write_atomically(&path, b"two").unwrap();
+    assert_ne!(FileStamp::of(&path), stamp);
+    fs::remove_dir_all(&dir).unwrap();
+}
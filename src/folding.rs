@@ -0,0 +1,178 @@
+use ropey::{Rope, RopeSlice};
+
+use crate::grapheme::TAB_WIDTH;
+use crate::text::LineEdit;
+
+// Lines `start + 1..=end` are hidden behind the `start` line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FoldMarker {
+    None,
+    Open,
+    Folded,
+}
+
+// None for lines with nothing but whitespace
+pub fn indent_of(line: RopeSlice) -> Option<usize> {
+    let mut indent = 0;
+    for char in line.chars() {
+        match char {
+            ' ' => indent += 1,
+            '\t' => indent += TAB_WIDTH,
+            '\n' | '\r' => return None,
+            _ => return Some(indent),
+        }
+    }
+    None
+}
+
+// A line can be folded when the next non blank line is indented deeper
+pub fn is_foldable(text: &Rope, line: usize) -> bool {
+    let indent = match indent_of(text.line(line)) {
+        Some(indent) => indent,
+        None => return false,
+    };
+
+    for next in line + 1..text.len_lines() {
+        if let Some(next_indent) = indent_of(text.line(next)) {
+            return next_indent > indent;
+        }
+    }
+    false
+}
+
+// Everything below `line` that is indented deeper than it, blank lines at the
+// end of the block stay visible
+pub fn indent_fold(text: &Rope, line: usize) -> Option<Fold> {
+    let indent = indent_of(text.line(line))?;
+    let mut end = line;
+
+    for next in line + 1..text.len_lines() {
+        match indent_of(text.line(next)) {
+            Some(next_indent) if next_indent > indent => end = next,
+            Some(_) => break,
+            None => {}
+        }
+    }
+
+    if end > line {
+        Some(Fold { start: line, end })
+    } else {
+        None
+    }
+}
+
+// The block starting at `line`, or the innermost one it is part of
+pub fn enclosing_fold(text: &Rope, line: usize) -> Option<Fold> {
+    if let Some(fold) = indent_fold(text, line) {
+        return Some(fold);
+    }
+
+    let mut indent = indent_of(text.line(line)).unwrap_or(usize::MAX);
+    for header in (0..line).rev() {
+        match indent_of(text.line(header)) {
+            Some(header_indent) if header_indent < indent => match indent_fold(text, header) {
+                Some(fold) if fold.end >= line => return Some(fold),
+                _ => indent = header_indent,
+            },
+            _ => {}
+        }
+    }
+    None
+}
+
+pub struct Folds {
+    // sorted by start, folds may be nested
+    folds: Vec<Fold>,
+    revision: u64,
+}
+
+impl Folds {
+    pub fn new() -> Folds {
+        Folds {
+            folds: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Fold> {
+        self.folds.iter()
+    }
+
+    pub fn add(&mut self, fold: Fold) {
+        match self.folds.binary_search_by_key(&fold.start, |f| f.start) {
+            Ok(index) => self.folds[index] = fold,
+            Err(index) => self.folds.insert(index, fold),
+        }
+        self.revision += 1;
+    }
+
+    // Removes the fold with its header on `line`
+    pub fn remove(&mut self, line: usize) -> bool {
+        match self.folds.binary_search_by_key(&line, |f| f.start) {
+            Ok(index) => {
+                self.folds.remove(index);
+                self.revision += 1;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.folds.binary_search_by_key(&line, |f| f.start).is_ok()
+    }
+
+    // Unfolds everything that hides `line`
+    pub fn reveal(&mut self, line: usize) {
+        let count = self.folds.len();
+        self.folds.retain(|f| !(f.start < line && line <= f.end));
+        if self.folds.len() != count {
+            self.revision += 1;
+        }
+    }
+
+    // Folds below the edit move with it, folds whose hidden lines were
+    // touched are dropped. Editing the header line itself keeps the fold
+    // as long as no lines were added or removed.
+    pub fn apply_edit(&mut self, edit: LineEdit) {
+        if self.folds.is_empty() {
+            return;
+        }
+        let count = self.folds.len();
+        let mut changed = false;
+
+        self.folds.retain(|f| {
+            edit.start > f.end
+                || edit.old_end <= f.start
+                || (edit.start == f.start
+                    && edit.old_end == f.start + 1
+                    && edit.new_end == f.start + 1)
+        });
+
+        for fold in self.folds.iter_mut() {
+            if edit.old_end <= fold.start && edit.new_end != edit.old_end {
+                fold.start = fold.start + edit.new_end - edit.old_end;
+                fold.end = fold.end + edit.new_end - edit.old_end;
+                changed = true;
+            }
+        }
+
+        if changed || self.folds.len() != count {
+            self.revision += 1;
+        }
+    }
+}
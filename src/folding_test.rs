@@ -0,0 +1,76 @@
+use ropey::Rope;
+
+use crate::folding::{enclosing_fold, indent_fold, is_foldable, Fold, Folds};
+use crate::text::LineEdit;
+
+const SOURCE: &str = "fn a() {\n    if x {\n        y();\n\n    }\n}\n\nfn b() {}\n";
+
+#[test]
+fn fold_by_indentation() {
+    let text = Rope::from_str(SOURCE);
+
+    assert_eq!(indent_fold(&text, 0), Some(Fold { start: 0, end: 4 }));
+    assert_eq!(indent_fold(&text, 1), Some(Fold { start: 1, end: 2 }));
+    assert_eq!(indent_fold(&text, 7), None);
+    assert!(is_foldable(&text, 1));
+    assert!(!is_foldable(&text, 2));
+}
+
+#[test]
+fn find_enclosing_fold() {
+    let text = Rope::from_str(SOURCE);
+
+    assert_eq!(enclosing_fold(&text, 2), Some(Fold { start: 1, end: 2 }));
+    assert_eq!(enclosing_fold(&text, 4), Some(Fold { start: 0, end: 4 }));
+    assert_eq!(enclosing_fold(&text, 7), None);
+}
+
+#[test]
+fn folds_move_with_edits_above() {
+    let mut folds = Folds::new();
+    folds.add(Fold { start: 4, end: 6 });
+
+    folds.apply_edit(LineEdit {
+        start: 1,
+        old_end: 2,
+        new_end: 4,
+    });
+
+    assert_eq!(
+        folds.iter().cloned().collect::<Vec<_>>(),
+        vec![Fold { start: 6, end: 8 }]
+    );
+}
+
+#[test]
+fn folds_survive_edits_below_and_on_header() {
+    let mut folds = Folds::new();
+    folds.add(Fold { start: 4, end: 6 });
+
+    folds.apply_edit(LineEdit {
+        start: 7,
+        old_end: 9,
+        new_end: 8,
+    });
+    folds.apply_edit(LineEdit {
+        start: 4,
+        old_end: 5,
+        new_end: 5,
+    });
+
+    assert!(folds.is_folded(4));
+}
+
+#[test]
+fn folds_open_on_edits_inside() {
+    let mut folds = Folds::new();
+    folds.add(Fold { start: 4, end: 6 });
+
+    folds.apply_edit(LineEdit {
+        start: 5,
+        old_end: 6,
+        new_end: 6,
+    });
+
+    assert!(folds.is_empty());
+}
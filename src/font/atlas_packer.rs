@@ -0,0 +1,272 @@
+use std::{collections::HashMap, hash::Hash};
+
+const PADDING: i32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Allocation {
+    pub page: usize,
+    pub x: i32,
+    pub y: i32,
+}
+
+struct Slot<K> {
+    x: i32,
+    width: i32,
+    key: Option<K>,
+    last_used: u64,
+}
+
+struct Shelf<K> {
+    y: i32,
+    height: i32,
+    next_x: i32,
+    slots: Vec<Slot<K>>,
+}
+
+impl<K> Shelf<K> {
+    fn last_used(&self) -> u64 {
+        self.slots.iter().map(|s| s.last_used).max().unwrap_or(0)
+    }
+}
+
+struct Page<K> {
+    shelves: Vec<Shelf<K>>,
+    next_y: i32,
+}
+
+impl<K> Page<K> {
+    fn new() -> Page<K> {
+        Page {
+            shelves: Vec::new(),
+            next_y: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Location {
+    page: usize,
+    shelf: usize,
+    // free slot to reuse, None appends at the end of the shelf
+    slot: Option<usize>,
+}
+
+// Shelf packer over square pages. Rectangles are placed on rows ("shelves")
+// of similar height, new pages are opened up to `max_pages` and after that
+// the least recently used shelf is evicted to make room. Shelves used since
+// `begin_frame` are never evicted, the frame being drawn still points at them.
+pub struct AtlasPacker<K> {
+    page_size: i32,
+    max_pages: usize,
+    pages: Vec<Page<K>>,
+    locations: HashMap<K, (usize, usize, usize)>,
+    clock: u64,
+    // slots used at or after this are in the current frame
+    frame_start: u64,
+}
+
+impl<K: Copy + Eq + Hash> AtlasPacker<K> {
+    pub fn new(page_size: i32, max_pages: usize) -> AtlasPacker<K> {
+        AtlasPacker {
+            page_size,
+            max_pages: max_pages.max(1),
+            pages: vec![Page::new()],
+            locations: HashMap::new(),
+            clock: 0,
+            frame_start: 0,
+        }
+    }
+
+    pub fn page_size(&self) -> i32 {
+        self.page_size
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = self.clock + 1;
+    }
+
+    pub fn touch(&mut self, key: K) {
+        self.clock += 1;
+        if let Some(&(page, shelf, slot)) = self.locations.get(&key) {
+            self.pages[page].shelves[shelf].slots[slot].last_used = self.clock;
+        }
+    }
+
+    // Returns where the rectangle went and the keys that had to be evicted
+    // to make room for it, None if it can never fit on a page or the current
+    // frame uses everything it could replace
+    pub fn allocate(&mut self, key: K, width: i32, height: i32) -> Option<(Allocation, Vec<K>)> {
+        let (width, height) = (width + PADDING, height + PADDING);
+        if width > self.page_size || height > self.page_size {
+            return None;
+        }
+
+        self.clock += 1;
+        let mut evicted = Vec::new();
+
+        loop {
+            if let Some(location) = self.find_space(width, height) {
+                return Some((self.place(key, location, width), evicted));
+            }
+
+            if self.pages.len() < self.max_pages {
+                self.pages.push(Page::new());
+            } else if !self.evict(height, &mut evicted) {
+                return None;
+            }
+        }
+    }
+
+    fn find_in_shelves(&self, width: i32, height: i32, max_height: i32) -> Option<Location> {
+        for (p, page) in self.pages.iter().enumerate() {
+            for (s, shelf) in page.shelves.iter().enumerate() {
+                if shelf.height < height || shelf.height > max_height {
+                    continue;
+                }
+
+                let free_slot = shelf
+                    .slots
+                    .iter()
+                    .position(|slot| slot.key.is_none() && slot.width >= width);
+
+                if free_slot.is_some() || shelf.next_x + width <= self.page_size {
+                    return Some(Location {
+                        page: p,
+                        shelf: s,
+                        slot: free_slot,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    fn find_space(&mut self, width: i32, height: i32) -> Option<Location> {
+        // prefer shelves that don't waste too much height
+        if let Some(location) = self.find_in_shelves(width, height, height + height / 2) {
+            return Some(location);
+        }
+
+        for (p, page) in self.pages.iter_mut().enumerate() {
+            if page.next_y + height <= self.page_size {
+                page.shelves.push(Shelf {
+                    y: page.next_y,
+                    height,
+                    next_x: 0,
+                    slots: Vec::new(),
+                });
+                page.next_y += height;
+
+                return Some(Location {
+                    page: p,
+                    shelf: page.shelves.len() - 1,
+                    slot: None,
+                });
+            }
+        }
+
+        self.find_in_shelves(width, height, i32::MAX)
+    }
+
+    fn place(&mut self, key: K, location: Location, width: i32) -> Allocation {
+        let clock = self.clock;
+        let shelf = &mut self.pages[location.page].shelves[location.shelf];
+
+        let index = match location.slot {
+            Some(index) => {
+                let slot = &mut shelf.slots[index];
+                let rest = slot.width - width;
+                let x = slot.x;
+
+                slot.width = width;
+                slot.key = Some(key);
+                slot.last_used = clock;
+
+                if rest > 0 {
+                    shelf.slots.push(Slot {
+                        x: x + width,
+                        width: rest,
+                        key: None,
+                        last_used: 0,
+                    });
+                }
+                index
+            }
+            None => {
+                shelf.slots.push(Slot {
+                    x: shelf.next_x,
+                    width,
+                    key: Some(key),
+                    last_used: clock,
+                });
+                shelf.next_x += width;
+                shelf.slots.len() - 1
+            }
+        };
+
+        let allocation = Allocation {
+            page: location.page,
+            x: shelf.slots[index].x,
+            y: shelf.y,
+        };
+
+        self.locations
+            .insert(key, (location.page, location.shelf, index));
+        allocation
+    }
+
+    // False when everything that could make room is used by the current frame
+    fn evict(&mut self, height: i32, evicted: &mut Vec<K>) -> bool {
+        let mut lru_shelf: Option<(usize, usize, u64)> = None;
+        for (p, page) in self.pages.iter().enumerate() {
+            for (s, shelf) in page.shelves.iter().enumerate() {
+                let last_used = shelf.last_used();
+                if shelf.height < height || last_used >= self.frame_start {
+                    continue;
+                }
+                if lru_shelf.map_or(true, |(_, _, lru)| last_used < lru) {
+                    lru_shelf = Some((p, s, last_used));
+                }
+            }
+        }
+
+        match lru_shelf {
+            Some((p, s, _)) => {
+                let shelf = &mut self.pages[p].shelves[s];
+                for slot in shelf.slots.drain(..) {
+                    if let Some(key) = slot.key {
+                        evicted.push(key);
+                    }
+                }
+                shelf.next_x = 0;
+            }
+            None => {
+                // every free shelf is too short, start the least recently used
+                // page over
+                let page_last_used =
+                    |page: &Page<K>| page.shelves.iter().map(|s| s.last_used()).max();
+                let p = (0..self.pages.len())
+                    .filter(|&p| page_last_used(&self.pages[p]).unwrap_or(0) < self.frame_start)
+                    .min_by_key(|&p| page_last_used(&self.pages[p]).unwrap_or(0));
+                let p = match p {
+                    Some(p) => p,
+                    None => return false,
+                };
+
+                for shelf in self.pages[p].shelves.drain(..) {
+                    evicted.extend(shelf.slots.into_iter().filter_map(|slot| slot.key));
+                }
+                self.pages[p].next_y = 0;
+            }
+        }
+
+        for key in evicted.iter() {
+            self.locations.remove(key);
+        }
+        true
+    }
+}
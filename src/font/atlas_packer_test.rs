@@ -0,0 +1,138 @@
+use crate::font::atlas_packer::{Allocation, AtlasPacker};
+
+#[test]
+fn pack_glyphs_in_rows() {
+    let mut packer = AtlasPacker::new(32, 1);
+
+    let (a, _) = packer.allocate('a', 15, 9).unwrap();
+    let (b, _) = packer.allocate('b', 15, 9).unwrap();
+    let (c, _) = packer.allocate('c', 15, 9).unwrap();
+
+    assert_eq!(
+        a,
+        Allocation {
+            page: 0,
+            x: 0,
+            y: 0
+        }
+    );
+    assert_eq!(
+        b,
+        Allocation {
+            page: 0,
+            x: 16,
+            y: 0
+        }
+    );
+    assert_eq!(
+        c,
+        Allocation {
+            page: 0,
+            x: 0,
+            y: 10
+        }
+    );
+}
+
+#[test]
+fn open_new_page_when_full() {
+    let mut packer = AtlasPacker::new(16, 2);
+
+    let (a, _) = packer.allocate('a', 15, 15).unwrap();
+    let (b, evicted) = packer.allocate('b', 15, 15).unwrap();
+
+    assert_eq!(a.page, 0);
+    assert_eq!(b.page, 1);
+    assert_eq!(packer.page_count(), 2);
+    assert!(evicted.is_empty());
+}
+
+#[test]
+fn evict_least_recently_used() {
+    let mut packer = AtlasPacker::new(16, 1);
+
+    packer.allocate('a', 7, 7).unwrap();
+    packer.allocate('b', 7, 7).unwrap();
+    packer.allocate('c', 7, 7).unwrap();
+    packer.allocate('d', 7, 7).unwrap();
+
+    packer.touch('a');
+    packer.touch('b');
+    packer.begin_frame();
+
+    let (e, evicted) = packer.allocate('e', 7, 7).unwrap();
+
+    let mut evicted = evicted;
+    evicted.sort();
+    assert_eq!(evicted, vec!['c', 'd']);
+    assert_eq!(
+        e,
+        Allocation {
+            page: 0,
+            x: 0,
+            y: 8
+        }
+    );
+}
+
+#[test]
+fn reuse_freed_slot_for_shorter_glyph() {
+    let mut packer = AtlasPacker::new(16, 1);
+
+    packer.allocate('a', 15, 13).unwrap();
+    packer.begin_frame();
+    let (b, evicted) = packer.allocate('b', 3, 3).unwrap();
+
+    assert_eq!(evicted, vec!['a']);
+    assert_eq!(
+        b,
+        Allocation {
+            page: 0,
+            x: 0,
+            y: 0
+        }
+    );
+
+    let (c, evicted) = packer.allocate('c', 3, 3).unwrap();
+    assert!(evicted.is_empty());
+    assert_eq!(
+        c,
+        Allocation {
+            page: 0,
+            x: 4,
+            y: 0
+        }
+    );
+}
+
+#[test]
+fn keep_glyphs_the_frame_uses() {
+    let mut packer = AtlasPacker::new(16, 1);
+
+    packer.allocate('a', 7, 7).unwrap();
+    packer.allocate('b', 7, 7).unwrap();
+    packer.allocate('c', 7, 7).unwrap();
+    packer.allocate('d', 7, 7).unwrap();
+
+    packer.begin_frame();
+    packer.touch('c');
+    packer.touch('d');
+    let (_, evicted) = packer.allocate('e', 7, 7).unwrap();
+    let mut evicted = evicted;
+    evicted.sort();
+    assert_eq!(evicted, vec!['a', 'b']);
+
+    // 'c', 'd' and 'e' fill the page and are all in this frame
+    packer.allocate('f', 7, 7).unwrap();
+    assert!(packer.allocate('g', 7, 7).is_none());
+
+    packer.begin_frame();
+    assert!(packer.allocate('g', 7, 7).is_some());
+}
+
+#[test]
+fn reject_glyph_bigger_than_page() {
+    let mut packer = AtlasPacker::new(16, 4);
+
+    assert!(packer.allocate('a', 16, 4).is_none());
+}
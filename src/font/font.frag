@@ -1,16 +1,26 @@
 #version 330 core
-in vec2 TexCoords;
+in vec3 TexCoords;
 in vec3 vertexColor;
+in float isColored;
 
 layout(location = 0, index = 0) out vec4 outColor;
 layout(location = 0, index = 1) out vec4 outAlpha;
 
-uniform sampler2D text;
+uniform sampler2DArray text;
+// 0 for grayscale atlases, blend every channel by the same coverage
+uniform bool subpixel;
 
 void main()
 {
     vec4 tex_col = texture(text, TexCoords);
-    outColor = vec4(vertexColor.xyz, 1.0);
-    outAlpha = tex_col;
+
+    if (isColored > 0.5) {
+        // color bitmaps (emoji) are premultiplied
+        outColor = vec4(tex_col.rgb / max(tex_col.a, 0.001), 1.0);
+        outAlpha = vec4(tex_col.a);
+    } else {
+        outColor = vec4(vertexColor.xyz, 1.0);
+        outAlpha = subpixel ? tex_col : vec4(tex_col.a);
+    }
 
 }
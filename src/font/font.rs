@@ -1,6 +1,11 @@
 extern crate freetype as ft;
 
+use std::{collections::HashMap, path::Path};
+
 use crate::check_error;
+use crate::config::{Antialiasing, Hinting};
+use crate::font::atlas_packer::AtlasPacker;
+use crate::font::font_lookup::{system_font_files, Coverage, FontSet, SystemFonts};
 
 #[derive(Debug, Clone, Copy)]
 struct Coroners {
@@ -17,6 +22,8 @@ pub struct AtlasGlyph {
     pub advance_width: f32,
     uv_pos: [f32; 2],
     uv_dimensions: [f32; 2],
+    layer: f32,
+    colored: bool,
 }
 
 #[repr(C)]
@@ -25,7 +32,9 @@ pub struct GlyphInstance {
     pub dimensions: [f32; 2],
     pub uv_pos: [f32; 2],
     pub uv_dimensions: [f32; 2],
+    pub layer: f32,
     pub color: [f32; 3],
+    pub colored: f32,
 }
 
 impl AtlasGlyph {
@@ -36,155 +45,500 @@ impl AtlasGlyph {
             dimensions: self.size,
             uv_dimensions: self.uv_dimensions,
             uv_pos: self.uv_pos,
+            layer: self.layer,
+            colored: if self.colored { 1.0 } else { 0.0 },
         }
     }
 }
 
+// (index into FontAtlas::faces, glyph index inside that face)
+type GlyphKey = (usize, u32);
+
+const PAGE_SIZE: i32 = 1024;
+const MAX_PAGES: usize = 4;
+
 pub struct FontAtlas {
     pub texture: gl::types::GLuint,
-    glyphs: std::collections::HashMap<char, AtlasGlyph>,
+    glyphs: HashMap<GlyphKey, AtlasGlyph>,
+    char_glyphs: HashMap<char, GlyphKey>,
     pub advance_height: f32,
+    pub ascender: f32,
+
+    // one texture array layer per packer page
+    packer: AtlasPacker<GlyphKey>,
+    texture_layers: usize,
+
+    library: ft::Library,
+    // primary face first, then configured fallbacks, then system fonts
+    // in the order they were needed
+    faces: Vec<ft::Face>,
+    scale: u32,
+    // read on the first char the faces above don't have
+    system_fonts: Option<SystemFonts>,
+
+    antialiasing: Antialiasing,
+    hinting: Hinting,
+}
 
-    texture_height: i32,
-    texture_width: i32,
-    occupied_width: i32,
+fn select_fixed_size(face: &mut ft::Face, scale: u32) -> bool {
+    let raw = face.raw_mut();
+    if raw.num_fixed_sizes <= 0 {
+        return false;
+    }
 
-    pub face: ft::Face,
+    let sizes =
+        unsafe { std::slice::from_raw_parts(raw.available_sizes, raw.num_fixed_sizes as usize) };
+
+    // smallest strike that is not smaller than requested, otherwise the biggest one
+    let mut best = 0;
+    for (i, size) in sizes.iter().enumerate() {
+        let height = size.height as u32;
+        let best_height = sizes[best].height as u32;
+        if (best_height < scale && height > best_height)
+            || (height >= scale && height < best_height)
+        {
+            best = i;
+        }
+    }
+
+    unsafe { ft::ffi::FT_Select_Size(raw, best as i32) == 0 }
 }
 
-impl FontAtlas {
-    pub fn new(scale: u32) -> FontAtlas {
-        let cache_width = 2000;
-        let cache_height = 100;
+fn prepare_face(face: &mut ft::Face, scale: u32) -> bool {
+    if face.is_scalable() {
+        face.set_pixel_sizes(0, scale).is_ok()
+    } else {
+        select_fixed_size(face, scale)
+    }
+}
+
+// Walks the charmap without loading any glyphs
+fn face_coverage(face: &mut ft::Face) -> Coverage {
+    let raw = face.raw_mut();
+    let mut index = 0;
+    let mut next = unsafe { ft::ffi::FT_Get_First_Char(raw, &mut index) };
+    Coverage::from_chars(std::iter::from_fn(|| {
+        if index == 0 {
+            return None;
+        }
+        let char = next;
+        next = unsafe { ft::ffi::FT_Get_Next_Char(raw, char, &mut index) };
+        Some(char as u32)
+    }))
+}
 
-        let texture = generate_texture(cache_width, cache_height);
+// Every mode ends up as RGBA coverage in the atlas, grayscale repeats the
+// single sample in all channels so the same texture can hold color glyphs
+fn bitmap_to_rgba(bitmap: &ft::Bitmap, antialiasing: Antialiasing) -> (Vec<u8>, i32, i32) {
+    let buffer = bitmap.buffer();
+    let pitch = bitmap.pitch();
+    let (width, rows) = match antialiasing {
+        Antialiasing::Grayscale => (bitmap.width(), bitmap.rows()),
+        Antialiasing::LcdRgb | Antialiasing::LcdBgr => (bitmap.width() / 3, bitmap.rows()),
+        Antialiasing::LcdVertical => (bitmap.width(), bitmap.rows() / 3),
+    };
+
+    let mut dest: Vec<u8> = Vec::with_capacity((width * rows * 4) as usize);
+    for y in 0..rows {
+        for x in 0..width {
+            let (r, g, b) = match antialiasing {
+                Antialiasing::Grayscale => {
+                    let v = buffer[(x + pitch * y) as usize];
+                    (v, v, v)
+                }
+                Antialiasing::LcdRgb | Antialiasing::LcdBgr => {
+                    let index = (x * 3 + pitch * y) as usize;
+                    let (first, second, third) =
+                        (buffer[index], buffer[index + 1], buffer[index + 2]);
+                    // the leftmost subpixel is blue on BGR panels
+                    if antialiasing == Antialiasing::LcdBgr {
+                        (third, second, first)
+                    } else {
+                        (first, second, third)
+                    }
+                }
+                Antialiasing::LcdVertical => {
+                    let index = (x + pitch * y * 3) as usize;
+                    (
+                        buffer[index],
+                        buffer[index + pitch as usize],
+                        buffer[index + 2 * pitch as usize],
+                    )
+                }
+            };
+            dest.extend_from_slice(&[r, g, b, r.max(g).max(b)]);
+        }
+    }
+    (dest, width, rows)
+}
 
-        let library = ft::Library::init().unwrap();
-        library
-            .set_lcd_filter(ft::LcdFilter::LcdFilterDefault)
-            .unwrap();
+// Box filter from premultiplied BGRA into premultiplied RGBA, emoji strikes
+// are usually ~109px and need to be shrunk to the line height
+fn scale_bgra_to_rgba(bitmap: &ft::Bitmap, width: i32, rows: i32) -> Vec<u8> {
+    let buffer = bitmap.buffer();
+    let (src_width, src_rows) = (bitmap.width(), bitmap.rows());
+
+    let mut dest: Vec<u8> = Vec::with_capacity((width * rows * 4) as usize);
+    for y in 0..rows {
+        let y0 = y * src_rows / rows;
+        let y1 = ((y + 1) * src_rows / rows).max(y0 + 1);
+        for x in 0..width {
+            let x0 = x * src_width / width;
+            let x1 = ((x + 1) * src_width / width).max(x0 + 1);
+
+            let mut sum = [0u32; 4];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let index = (sx * 4 + bitmap.pitch() * sy) as usize;
+                    for c in 0..4 {
+                        sum[c] += buffer[index + c] as u32;
+                    }
+                }
+            }
+
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+            let [b, g, r, a] = sum;
+            dest.extend_from_slice(&[
+                (r / count) as u8,
+                (g / count) as u8,
+                (b / count) as u8,
+                (a / count) as u8,
+            ]);
+        }
+    }
+    dest
+}
+
+impl FontAtlas {
+    // `system_fonts` is what an atlas this one replaces found out about them
+    pub fn new(
+        fonts: &FontSet,
+        scale: u32,
+        antialiasing: Antialiasing,
+        hinting: Hinting,
+        system_fonts: Option<SystemFonts>,
+    ) -> FontAtlas {
+        let texture = generate_texture(PAGE_SIZE, 1);
 
-        let m = std::rc::Rc::new(include_bytes!("../../assets/hack.ttf").to_vec());
+        let library = ft::Library::init().unwrap();
+        if antialiasing.is_subpixel() {
+            library
+                .set_lcd_filter(ft::LcdFilter::LcdFilterDefault)
+                .unwrap();
+        }
 
-        let face = library.new_memory_face(m, 0).unwrap();
+        let face = library.new_memory_face(fonts.primary.clone(), 0).unwrap();
 
         face.set_pixel_sizes(0, scale).unwrap();
+        let metrics = face.size_metrics().unwrap();
+
+        let mut atlas = FontAtlas {
+            texture,
+            glyphs: HashMap::new(),
+            char_glyphs: HashMap::new(),
+            advance_height: (metrics.height / 64) as f32,
+            ascender: (metrics.ascender >> 6) as f32,
+            packer: AtlasPacker::new(PAGE_SIZE, MAX_PAGES),
+            texture_layers: 1,
+            library,
+            faces: vec![face],
+            scale,
+            system_fonts,
+            antialiasing,
+            hinting,
+        };
+
+        for path in &fonts.fallback {
+            match atlas.open_face(path) {
+                Some(face) => atlas.faces.push(face),
+                None => println!("could not load fallback font {}", path.display()),
+            }
+        }
+
+        atlas
+    }
+
+    pub fn take_system_fonts(&mut self) -> Option<SystemFonts> {
+        self.system_fonts.take()
+    }
+
+    fn open_face(&self, path: &Path) -> Option<ft::Face> {
+        let mut face = self.library.new_face(path, 0).ok()?;
+        if prepare_face(&mut face, self.scale) {
+            Some(face)
+        } else {
+            None
+        }
+    }
 
-        FontAtlas {
-            texture: texture,
-            glyphs: std::collections::HashMap::new(),
-            advance_height: (face.size_metrics().unwrap().height / 64) as f32,
-            texture_height: cache_height,
-            texture_width: cache_width,
-            occupied_width: 0,
-            face: face,
+    fn find_glyph(&mut self, char: char) -> Option<GlyphKey> {
+        for (i, face) in self.faces.iter().enumerate() {
+            let index = face.get_char_index(char as usize);
+            if index != 0 {
+                return Some((i, index));
+            }
+        }
+
+        if char.is_control() {
+            return None;
+        }
+
+        let mut system_fonts = self
+            .system_fonts
+            .take()
+            .unwrap_or_else(|| SystemFonts::new(system_font_files()));
+        let found = self.find_system_glyph(&mut system_fonts, char);
+        self.system_fonts = Some(system_fonts);
+        found
+    }
+
+    // Only a face that has the glyph is kept, the others are dropped once
+    // their coverage is known
+    fn find_system_glyph(
+        &mut self,
+        system_fonts: &mut SystemFonts,
+        char: char,
+    ) -> Option<GlyphKey> {
+        let library = &self.library;
+        loop {
+            let file = system_fonts.find(char, |path| match library.new_face(path, 0) {
+                Ok(mut face) => face_coverage(&mut face),
+                Err(_) => Coverage::empty(),
+            })?;
+
+            if let Some(face) = self.open_face(system_fonts.path(file)) {
+                let index = face.get_char_index(char as usize);
+                if index != 0 {
+                    self.faces.push(face);
+                    return Some((self.faces.len() - 1, index));
+                }
+            }
+            system_fonts.forget(file);
         }
     }
 
     pub fn get_glyph(&mut self, char: char) -> AtlasGlyph {
-        let g = self.glyphs.get(&char);
+        let key = match self.char_glyphs.get(&char) {
+            Some(key) => *key,
+            None => {
+                // nobody has it, fall back to .notdef of the primary face
+                let key = self.find_glyph(char).unwrap_or((0, 0));
+                self.char_glyphs.insert(char, key);
+                key
+            }
+        };
+
+        self.glyph_for_key(key)
+    }
 
-        match g {
-            Some(g) => g.clone(),
+    // Glyph ids coming out of the shaper always refer to the primary face
+    pub fn get_glyph_by_index(&mut self, glyph_index: u32) -> AtlasGlyph {
+        self.glyph_for_key((0, glyph_index))
+    }
+
+    // Glyphs looked up from here on keep their place in the texture until the
+    // next frame
+    pub fn begin_frame(&mut self) {
+        self.packer.begin_frame();
+    }
+
+    pub fn units_per_em(&self) -> f32 {
+        self.faces[0].em_size() as f32
+    }
+
+    fn glyph_for_key(&mut self, key: GlyphKey) -> AtlasGlyph {
+        match self.glyphs.get(&key) {
+            Some(g) => {
+                let g = g.clone();
+                self.packer.touch(key);
+                g
+            }
             None => {
-                let glyph = self.load_char(char);
-                self.glyphs.insert(char, glyph.clone());
+                let glyph = self.load_glyph(key);
+                self.glyphs.insert(key, glyph.clone());
                 glyph
             }
         }
     }
 
-    fn load_char(&mut self, char: char) -> AtlasGlyph {
-        self.face
-            .load_char(char as usize, ft::face::LoadFlag::DEFAULT)
+    fn load_flags(&self) -> ft::face::LoadFlag {
+        match (self.hinting, self.antialiasing) {
+            (Hinting::None, _) => ft::face::LoadFlag::NO_HINTING,
+            (Hinting::Light, _) => ft::face::LoadFlag::TARGET_LIGHT,
+            (Hinting::Full, Antialiasing::Grayscale) => ft::face::LoadFlag::TARGET_NORMAL,
+            (Hinting::Full, Antialiasing::LcdRgb) | (Hinting::Full, Antialiasing::LcdBgr) => {
+                ft::face::LoadFlag::TARGET_LCD
+            }
+            (Hinting::Full, Antialiasing::LcdVertical) => ft::face::LoadFlag::TARGET_LCD_V,
+        }
+    }
+
+    fn render_mode(&self) -> ft::RenderMode {
+        match self.antialiasing {
+            Antialiasing::Grayscale => ft::RenderMode::Normal,
+            Antialiasing::LcdRgb | Antialiasing::LcdBgr => ft::RenderMode::Lcd,
+            Antialiasing::LcdVertical => ft::RenderMode::LcdV,
+        }
+    }
+
+    fn load_glyph(&mut self, key: GlyphKey) -> AtlasGlyph {
+        let (face_index, glyph_index) = key;
+        let face = &self.faces[face_index];
+
+        if face.has_color() && !face.is_scalable() {
+            return self.load_color_glyph(key);
+        }
+
+        face.load_glyph(glyph_index, self.load_flags()).unwrap();
+
+        let glyph = face.glyph();
+        glyph.render_glyph(self.render_mode()).unwrap();
+
+        let bitmap = glyph.bitmap();
+        let (pixels, width, rows) = bitmap_to_rgba(&bitmap, self.antialiasing);
+        let size = [width as f32, rows as f32];
+        let bearing = [glyph.bitmap_left() as f32, glyph.bitmap_top() as f32];
+        let advance_width = (glyph.advance().x >> 6) as f32;
+
+        let (uv_pos, uv_dimensions, layer) = self.load_bitmap_to_texture(key, &pixels, width, rows);
+
+        return AtlasGlyph {
+            size,
+            bearing,
+            advance_width,
+            uv_dimensions,
+            uv_pos,
+            layer,
+            colored: false,
+        };
+    }
+
+    fn load_color_glyph(&mut self, key: GlyphKey) -> AtlasGlyph {
+        let (face_index, glyph_index) = key;
+        let face = &self.faces[face_index];
+        face.load_glyph(glyph_index, ft::face::LoadFlag::COLOR)
             .unwrap();
 
-        self.face.glyph().render_glyph(ft::RenderMode::Lcd).unwrap();
-        let (uv_pos, uv_dimensions) = self.load_bitmap_to_texture(&self.face.glyph().bitmap());
+        let strike_height = face.size_metrics().unwrap().y_ppem as f32;
+        let ratio = self.scale as f32 / strike_height;
+
+        let glyph = face.glyph();
+        let bitmap = glyph.bitmap();
+        let width = ((bitmap.width() as f32 * ratio).round() as i32).max(1);
+        let rows = ((bitmap.rows() as f32 * ratio).round() as i32).max(1);
+
+        let bearing = [
+            glyph.bitmap_left() as f32 * ratio,
+            glyph.bitmap_top() as f32 * ratio,
+        ];
+        let advance_width = ((glyph.advance().x >> 6) as f32 * ratio).round();
+        let pixels = scale_bgra_to_rgba(&bitmap, width, rows);
+
+        let (uv_pos, uv_dimensions, layer) = self.load_bitmap_to_texture(key, &pixels, width, rows);
 
         return AtlasGlyph {
-            size: [
-                (self.face.glyph().bitmap().width() / 3) as f32,
-                self.face.glyph().bitmap().rows() as f32,
-            ],
-            bearing: [
-                self.face.glyph().bitmap_left() as f32,
-                self.face.glyph().bitmap_top() as f32,
-            ],
-            advance_width: (self.face.glyph().advance().x >> 6) as f32,
+            size: [width as f32, rows as f32],
+            bearing,
+            advance_width,
             uv_dimensions,
             uv_pos,
+            layer,
+            colored: true,
         };
     }
 
-    fn load_bitmap_to_texture(&mut self, bitmap: &ft::Bitmap) -> ([f32; 2], [f32; 2]) {
-        let mut dest: Vec<u8> = Vec::new();
-        dest.reserve((bitmap.rows() * bitmap.width()) as usize);
+    fn load_bitmap_to_texture(
+        &mut self,
+        key: GlyphKey,
+        pixels: &[u8],
+        width: i32,
+        rows: i32,
+    ) -> ([f32; 2], [f32; 2], f32) {
+        if width == 0 || rows == 0 {
+            return ([0.0, 0.0], [0.0, 0.0], 0.0);
+        }
 
-        for y in 0..bitmap.rows() {
-            for x in 0..bitmap.width() {
-                let index = (x + (bitmap.pitch()) * y) as usize;
-                dest.push(bitmap.buffer()[index]);
+        let (allocation, evicted) = match self.packer.allocate(key, width, rows) {
+            Some(allocation) => allocation,
+            None => {
+                println!("no room in the atlas for glyph {}x{}", width, rows);
+                return ([0.0, 0.0], [0.0, 0.0], 0.0);
             }
+        };
+
+        for key in evicted {
+            self.glyphs.remove(&key);
         }
 
-        // let img: image::ImageBuffer<image::Rgb<u8>, Vec<u8>> =
-        //     image::ImageBuffer::from_raw((bitmap.width() / 3) as u32, (bitmap.rows() as u32), dest.clone())
-        //         .unwrap();
-        // img.save("example_image.png");
+        if allocation.page >= self.texture_layers {
+            self.grow_texture(self.packer.page_count());
+        }
 
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.texture);
-            gl::TexSubImage2D(
-                gl::TEXTURE_2D,
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.texture);
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
                 0,
-                self.occupied_width,
-                0,
-                bitmap.width() / 3,
-                bitmap.rows(),
-                gl::RGB,
+                allocation.x,
+                allocation.y,
+                allocation.page as i32,
+                width,
+                rows,
+                1,
+                gl::RGBA,
                 gl::UNSIGNED_BYTE,
-                dest.as_ptr() as *const gl::types::GLvoid,
+                pixels.as_ptr() as *const gl::types::GLvoid,
             );
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            check_error!();
+        }
 
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        let size = self.packer.page_size() as f32;
+        let uv_dimensions = [width as f32 / size, rows as f32 / size];
+        let uv_pos = [allocation.x as f32 / size, allocation.y as f32 / size];
+        (uv_pos, uv_dimensions, allocation.page as f32)
+    }
+
+    fn grow_texture(&mut self, layers: usize) {
+        let size = self.packer.page_size();
+        let texture = generate_texture(size, layers as i32);
+
+        unsafe {
+            let mut fbo: gl::types::GLuint = 0;
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+
+            for layer in 0..self.texture_layers as i32 {
+                gl::FramebufferTextureLayer(
+                    gl::READ_FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    self.texture,
+                    0,
+                    layer,
+                );
+                gl::CopyTexSubImage3D(gl::TEXTURE_2D_ARRAY, 0, 0, 0, layer, 0, 0, size, size);
+            }
+
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::DeleteFramebuffers(1, &fbo);
+            gl::DeleteTextures(1, &self.texture);
             check_error!();
         }
 
-        let width = bitmap.width() / 3;
+        self.texture = texture;
+        self.texture_layers = layers;
+    }
+}
 
-        let uv_dimensions = [
-            width as f32 / self.texture_width as f32,
-            bitmap.rows() as f32 / self.texture_height as f32,
-        ];
-        let uv_pos = [self.occupied_width as f32 / self.texture_width as f32, 0.0];
-        self.occupied_width += width + 1;
-        (uv_pos, uv_dimensions)
-
-        // let uv = Coroners {
-        //     top_left: (
-        //         self.occupied_width as f32 / self.texture_width as f32,
-        //         bitmap.rows() as f32 / self.texture_height as f32,
-        //     ),
-        //     top_right: (
-        //         (self.occupied_width + width) as f32 / self.texture_width as f32,
-        //         bitmap.rows() as f32 / self.texture_height as f32,
-        //     ),
-        //     bottom_left: (self.occupied_width as f32 / self.texture_width as f32, 0.0),
-        //     bottom_right: (
-        //         (self.occupied_width + width) as f32 / self.texture_width as f32,
-        //         0.0,
-        //     ),
-        // };
-
-        // uv
+impl Drop for FontAtlas {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
     }
 }
 
-fn generate_texture(width: i32, height: i32) -> gl::types::GLuint {
+fn generate_texture(size: i32, layers: i32) -> gl::types::GLuint {
     let mut texture: gl::types::GLuint = 0;
 
     unsafe {
@@ -194,11 +548,33 @@ fn generate_texture(width: i32, height: i32) -> gl::types::GLuint {
         gl::GenTextures(1, &mut texture);
         check_error!();
 
-        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
         check_error!();
 
-        gl::TexStorage2D(gl::TEXTURE_2D, 1, gl::RGB8, width, height);
+        gl::TexStorage3D(gl::TEXTURE_2D_ARRAY, 1, gl::RGBA8, size, size, layers);
         check_error!();
+
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_WRAP_S,
+            gl::CLAMP_TO_EDGE as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_EDGE as i32,
+        );
+
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MIN_FILTER,
+            gl::NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MAG_FILTER,
+            gl::NEAREST as i32,
+        );
     }
 
     check_error!();
@@ -16,9 +16,12 @@ layout (location = 1) in vec2 dim;
 layout (location = 2) in vec2 uv_pos;
 layout (location = 3) in vec2 uv_dim;
 layout (location = 4) in vec3 color;
+layout (location = 5) in float colored;
+layout (location = 6) in float layer;
 
 out vec3 vertexColor;
-out vec2 TexCoords;
+out vec3 TexCoords;
+out float isColored;
 
 uniform mat4 projection;
 
@@ -31,9 +34,10 @@ void main()
     );
 
     vec2 position = pos + dim * v;
-    TexCoords = uv_pos + uv_dim * v;
+    TexCoords = vec3(uv_pos + uv_dim * v, layer);
 
     vertexColor = color;
+    isColored = colored;
 
     gl_Position = projection * vec4(position.xy, 0.0, 1.0);
 }
@@ -0,0 +1,250 @@
+use std::{cmp::Ordering, env, fs, path::Path, path::PathBuf, rc::Rc};
+
+use crate::config::Config;
+
+const EMBEDDED_FONT: &[u8] = include_bytes!("../../assets/hack.ttf");
+
+#[derive(Clone)]
+pub struct FontSet {
+    pub primary: Rc<Vec<u8>>,
+    pub fallback: Vec<PathBuf>,
+}
+
+pub fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = vec![
+        "/usr/share/fonts".into(),
+        "/usr/local/share/fonts".into(),
+        "/Library/Fonts".into(),
+        "/System/Library/Fonts".into(),
+    ];
+
+    if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join("Library/Fonts"));
+    }
+
+    if let Some(windir) = env::var_os("WINDIR") {
+        dirs.push(PathBuf::from(windir).join("Fonts"));
+    }
+
+    dirs
+}
+
+fn is_font_file(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => {
+            let ext = ext.to_ascii_lowercase();
+            ext == "ttf" || ext == "otf" || ext == "ttc"
+        }
+        None => false,
+    }
+}
+
+pub fn collect_font_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_font_files(&path, out);
+        } else if is_font_file(&path) {
+            out.push(path);
+        }
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+// Picks the file whose name is closest to the family, "Fira Code" matches
+// FiraCode-Regular.ttf before FiraCode-Bold.ttf
+pub fn find_font_by_family(family: &str, files: &[PathBuf]) -> Option<PathBuf> {
+    let family = normalize_name(family);
+    if family.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(usize, &PathBuf)> = None;
+
+    for file in files {
+        let stem = match file.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) => normalize_name(stem),
+            None => continue,
+        };
+
+        if !stem.starts_with(&family) {
+            continue;
+        }
+
+        let rank = match &stem[family.len()..] {
+            "" | "regular" => 0,
+            rest if rest.ends_with("regular") => 1,
+            rest => 2 + rest.len(),
+        };
+
+        if best.map_or(true, |(best_rank, _)| rank < best_rank) {
+            best = Some((rank, file));
+        }
+    }
+
+    best.map(|(_, file)| file.clone())
+}
+
+pub fn system_font_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for dir in font_dirs() {
+        collect_font_files(&dir, &mut files);
+    }
+    files
+}
+
+// Chars a font has glyphs for, as sorted inclusive ranges
+pub struct Coverage {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl Coverage {
+    // `chars` in increasing order, the way FreeType walks a charmap
+    pub fn from_chars<I: IntoIterator<Item = u32>>(chars: I) -> Coverage {
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for char in chars {
+            match ranges.last_mut() {
+                Some(last) if char.wrapping_sub(last.1) == 1 => last.1 = char,
+                _ => ranges.push((char, char)),
+            }
+        }
+        Coverage { ranges }
+    }
+
+    pub fn empty() -> Coverage {
+        Coverage { ranges: Vec::new() }
+    }
+
+    pub fn contains(&self, char: char) -> bool {
+        let char = char as u32;
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < char {
+                    Ordering::Less
+                } else if start > char {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+// System fonts searched for glyphs missing everywhere else. Every file is
+// read once for its coverage, which doesn't depend on the font size, so it
+// is kept when the atlas is rebuilt.
+pub struct SystemFonts {
+    files: Vec<PathBuf>,
+    // coverage of the first `coverage.len()` files
+    coverage: Vec<Coverage>,
+}
+
+impl SystemFonts {
+    pub fn new(files: Vec<PathBuf>) -> SystemFonts {
+        SystemFonts {
+            files,
+            coverage: Vec::new(),
+        }
+    }
+
+    // Index of the first file that has `char`, reading the files not looked
+    // at yet only as far as needed
+    pub fn find<F: FnMut(&Path) -> Coverage>(
+        &mut self,
+        char: char,
+        mut read_coverage: F,
+    ) -> Option<usize> {
+        if let Some(file) = self.coverage.iter().position(|c| c.contains(char)) {
+            return Some(file);
+        }
+
+        while self.coverage.len() < self.files.len() {
+            let file = self.coverage.len();
+            self.coverage.push(read_coverage(&self.files[file]));
+            if self.coverage[file].contains(char) {
+                return Some(file);
+            }
+        }
+        None
+    }
+
+    pub fn path(&self, file: usize) -> &Path {
+        &self.files[file]
+    }
+
+    // The file turned out to be unusable, it won't be offered again
+    pub fn forget(&mut self, file: usize) {
+        self.coverage[file] = Coverage::empty();
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn looks_like_path(name: &str) -> bool {
+    name.contains('/') || name.contains('\\') || is_font_file(Path::new(name))
+}
+
+fn load_primary(config: &Config, files: &mut Option<Vec<PathBuf>>) -> Rc<Vec<u8>> {
+    let path = match (&config.font_path, &config.font_family) {
+        (Some(path), _) => Some(expand_home(path)),
+        (None, Some(family)) => {
+            let found = find_font_by_family(family, files.get_or_insert_with(system_font_files));
+            if found.is_none() {
+                println!("font family `{}` not found, using default font", family);
+            }
+            found
+        }
+        (None, None) => None,
+    };
+
+    if let Some(path) = path {
+        match fs::read(&path) {
+            Ok(data) => return Rc::new(data),
+            Err(e) => println!("could not read font {}: {}", path.display(), e),
+        }
+    }
+
+    Rc::new(EMBEDDED_FONT.to_vec())
+}
+
+// Fallback entries are either paths or family names, families that are not
+// installed are skipped
+pub fn load_fonts(config: &Config) -> FontSet {
+    let mut files = None;
+    let primary = load_primary(config, &mut files);
+
+    let mut fallback = Vec::new();
+    for name in &config.font_fallback {
+        if looks_like_path(name) {
+            fallback.push(expand_home(name));
+            continue;
+        }
+
+        match find_font_by_family(name, files.get_or_insert_with(system_font_files)) {
+            Some(path) => fallback.push(path),
+            None => println!("fallback font family `{}` not found", name),
+        }
+    }
+
+    FontSet { primary, fallback }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use crate::font::font_lookup::{Coverage, SystemFonts};
+
+#[test]
+fn coverage_merges_consecutive_chars() {
+    let coverage = Coverage::from_chars(vec![0x41, 0x42, 0x43, 0x3b1, 0x1f600]);
+
+    assert!(coverage.contains('A'));
+    assert!(coverage.contains('C'));
+    assert!(coverage.contains('α'));
+    assert!(coverage.contains('😀'));
+    assert!(!coverage.contains('D'));
+    assert!(!coverage.contains('β'));
+    assert!(!Coverage::empty().contains('A'));
+}
+
+#[test]
+fn system_fonts_are_read_once_and_only_as_needed() {
+    let mut fonts = SystemFonts::new(vec![
+        PathBuf::from("latin.ttf"),
+        PathBuf::from("greek.ttf"),
+        PathBuf::from("emoji.ttf"),
+    ]);
+    let mut read = Vec::new();
+    let mut read_coverage = |path: &std::path::Path| {
+        read.push(path.to_owned());
+        match path.to_str().unwrap() {
+            "latin.ttf" => Coverage::from_chars(0x41..0x5b),
+            "greek.ttf" => Coverage::from_chars(0x3b1..0x3ca),
+            _ => Coverage::from_chars(vec![0x1f600]),
+        }
+    };
+
+    assert_eq!(fonts.find('β', &mut read_coverage), Some(1));
+    assert_eq!(fonts.find('Q', &mut read_coverage), Some(0));
+    assert_eq!(fonts.find('☃', &mut read_coverage), None);
+    assert_eq!(fonts.find('☃', &mut read_coverage), None);
+    assert_eq!(read.len(), 3);
+
+    fonts.forget(1);
+    assert_eq!(fonts.find('β', |_| unreachable!()), None);
+}
@@ -2,22 +2,53 @@ extern crate freetype as ft;
 extern crate gl;
 
 use ropey::RopeSlice;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 use crate::timer;
 
 use crate::shaders;
-use std::{ffi::CString, mem::MaybeUninit};
+use std::{borrow::Cow, collections::HashMap, ffi::CString, mem::MaybeUninit};
 
 use crate::check_error;
+use crate::config::{Antialiasing, Hinting};
+use crate::display_map::DisplayLine;
+use crate::folding::FoldMarker;
 use crate::offset_of;
 
+use crate::encoding::{has_raw_bytes, shown_byte};
 use crate::font::font::{FontAtlas, GlyphInstance};
+use crate::font::font_lookup::FontSet;
+use crate::font::shaper::{shape_line, ShapedLine, ShapingFace};
+use crate::grapheme::{grapheme_width, visual_column, RAW_BYTE_WIDTH, TAB_WIDTH};
 use crate::matrix;
+use crate::theme::{Color, Theme};
+
+pub struct Label<'a> {
+    pub text: &'a str,
+    pub x: f32,
+    pub baseline: f32,
+    pub color: Color,
+}
 
 pub struct FontRenderer {
     pub char_width: f32,
     pub advance_height: f32,
     pub ascender: f32,
-
+    // font_size is in points, glyphs are rasterized at font_size * content_scale
+    pub font_size: u32,
+    pub content_scale: f32,
+    pub shaping: bool,
+    pub show_whitespace: bool,
+    // `\xNN` of bytes that aren't text
+    pub raw_byte_color: Color,
+
+    fonts: FontSet,
+    // None when the primary font can't be shaped, lines are then laid out
+    // on the grid even with shaping on
+    shaping_face: Option<ShapingFace>,
+    antialiasing: Antialiasing,
+    hinting: Hinting,
+    shape_cache: HashMap<String, ShapedLine>,
     font_atlas: FontAtlas,
     program: shaders::Program,
     vao: gl::types::GLuint,
@@ -27,6 +58,7 @@ pub struct FontRenderer {
     quad_buffer_size: usize,
 
     transform_loc: gl::types::GLint,
+    subpixel_loc: gl::types::GLint,
     index_buffer: gl::types::GLuint,
 }
 
@@ -100,11 +132,74 @@ fn vao_setup() {
             offset_of!(GlyphInstance, color) as *const gl::types::GLvoid,
         );
         gl::VertexAttribDivisor(4, 1);
+
+        gl::EnableVertexAttribArray(5);
+        gl::VertexAttribPointer(
+            5,
+            1,
+            gl::FLOAT,
+            gl::FALSE,
+            std::mem::size_of::<GlyphInstance>() as gl::types::GLint,
+            offset_of!(GlyphInstance, colored) as *const gl::types::GLvoid,
+        );
+        gl::VertexAttribDivisor(5, 1);
+
+        gl::EnableVertexAttribArray(6);
+        gl::VertexAttribPointer(
+            6,
+            1,
+            gl::FLOAT,
+            gl::FALSE,
+            std::mem::size_of::<GlyphInstance>() as gl::types::GLint,
+            offset_of!(GlyphInstance, layer) as *const gl::types::GLvoid,
+        );
+        gl::VertexAttribDivisor(6, 1);
+    }
+}
+
+// Shown after the text of a folded line
+const FOLD_PLACEHOLDER: &str = " \u{2026}";
+
+// Drawn in place of spaces and tabs when whitespace is shown
+fn whitespace_marker(char: char) -> Option<char> {
+    match char {
+        ' ' => Some('\u{00B7}'),
+        '\t' => Some('\u{2192}'),
+        _ => None,
     }
 }
 
+// Glyphs needed to draw `chars`, raw bytes take one per character of `\xNN`
+fn glyph_count(chars: impl Iterator<Item = char>) -> usize {
+    chars
+        .map(|c| match shown_byte(c) {
+            Some(_) => RAW_BYTE_WIDTH,
+            None => 1,
+        })
+        .sum()
+}
+
+fn digit_count(mut n: usize) -> usize {
+    let mut digits = 1;
+    while n >= 10 {
+        n /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+fn pixel_size(font_size: u32, content_scale: f32) -> u32 {
+    ((font_size as f32 * content_scale).round() as u32).max(1)
+}
+
 impl FontRenderer {
-    pub fn new() -> FontRenderer {
+    pub fn new(
+        fonts: FontSet,
+        font_size: u32,
+        content_scale: f32,
+        antialiasing: Antialiasing,
+        hinting: Hinting,
+    ) -> FontRenderer {
         let mut vbo: gl::types::GLuint = 0;
         unsafe {
             gl::GenBuffers(1, &mut vbo);
@@ -138,6 +233,7 @@ impl FontRenderer {
 
         let shader_program = create_shader_program();
         let transform_loc;
+        let subpixel_loc;
         unsafe {
             transform_loc = gl::GetUniformLocation(
                 shader_program.id,
@@ -147,29 +243,96 @@ impl FontRenderer {
             if transform_loc == -1 {
                 panic!("location not found");
             }
+
+            subpixel_loc = gl::GetUniformLocation(
+                shader_program.id,
+                CString::new("subpixel").unwrap().as_ptr(),
+            );
         }
 
-        let mut atlas = FontAtlas::new(14);
-        let char_with = atlas.get_glyph(' ').advance_width;
-        let advance_height = atlas.advance_height;
+        let atlas = FontAtlas::new(
+            &fonts,
+            pixel_size(font_size, content_scale),
+            antialiasing,
+            hinting,
+            None,
+        );
 
-        let ascender = (atlas.face.ascender() >> 6) as f32;
+        let shaping_face = ShapingFace::new(fonts.primary.clone());
+        if shaping_face.is_none() {
+            println!("the font can't be shaped, lines are shown unshaped");
+        }
 
-        FontRenderer {
-            char_width: char_with,
-            advance_height: advance_height,
-            ascender: ascender,
+        let mut font_renderer = FontRenderer {
+            char_width: 0.0,
+            advance_height: 0.0,
+            ascender: 0.0,
+            font_size,
+            content_scale,
+            shaping: false,
+            show_whitespace: false,
+            raw_byte_color: Theme::new().raw_byte,
 
             program: shader_program,
-            vao: vao,
+            vao,
             quad_buffer_object: vbo,
             quad_buffer_size: 0,
             buffer_position: 0,
 
+            shaping_face,
+            fonts,
+            antialiasing,
+            hinting,
+            shape_cache: HashMap::new(),
             font_atlas: atlas,
-            transform_loc: transform_loc,
+            transform_loc,
+            subpixel_loc,
             index_buffer,
+        };
+        font_renderer.update_metrics();
+        font_renderer
+    }
+
+    fn update_metrics(&mut self) {
+        self.char_width = self.font_atlas.get_glyph(' ').advance_width;
+        self.advance_height = self.font_atlas.advance_height;
+        self.ascender = self.font_atlas.ascender;
+    }
+
+    // Glyphs drawn until the next call keep their place in the atlas
+    pub fn begin_frame(&mut self) {
+        self.font_atlas.begin_frame();
+    }
+
+    pub fn set_font_size(&mut self, font_size: u32) {
+        if font_size == self.font_size {
+            return;
         }
+
+        self.font_size = font_size;
+        self.rebuild_atlas();
+    }
+
+    pub fn set_content_scale(&mut self, content_scale: f32) {
+        if content_scale == self.content_scale {
+            return;
+        }
+
+        self.content_scale = content_scale;
+        self.rebuild_atlas();
+    }
+
+    fn rebuild_atlas(&mut self) {
+        let system_fonts = self.font_atlas.take_system_fonts();
+        self.font_atlas = FontAtlas::new(
+            &self.fonts,
+            pixel_size(self.font_size, self.content_scale),
+            self.antialiasing,
+            self.hinting,
+            system_fonts,
+        );
+        self.shape_cache.clear();
+        self.update_metrics();
     }
 
     fn set_projection(&self, projection: &matrix::Matrix) {
@@ -180,35 +343,170 @@ impl FontRenderer {
                 gl::FALSE,
                 projection.as_ptr() as *const f32,
             );
+            gl::Uniform1i(self.subpixel_loc, self.antialiasing.is_subpixel() as i32);
+        }
+    }
+
+    fn is_shaping(&self) -> bool {
+        self.shaping && self.shaping_face.is_some()
+    }
+
+    // Shaping results are cached by line content, the caller puts the line
+    // back once done with it. Only called while `is_shaping()`.
+    fn take_shaped_line(&mut self, line: &str) -> ShapedLine {
+        if let Some(shaped) = self.shape_cache.remove(line) {
+            return shaped;
+        }
+
+        let scale = pixel_size(self.font_size, self.content_scale) as f32
+            / self.font_atlas.units_per_em();
+        let tab_advance = self.char_width * TAB_WIDTH as f32;
+        let face = self.shaping_face.as_ref().unwrap().face();
+
+        shape_line(&face, scale, tab_advance, line)
+    }
+
+    fn put_shaped_line(&mut self, line: String, shaped: ShapedLine) {
+        if self.shape_cache.len() > 4096 {
+            self.shape_cache.clear();
+        }
+        self.shape_cache.insert(line, shaped);
+    }
+
+    // Screen x of the caret in front of char `char_x` of `line`
+    pub fn caret_x(&mut self, line: RopeSlice, char_x: usize) -> f32 {
+        let grid_x = |line| visual_column(line, char_x) as f32 * self.char_width;
+        if !self.is_shaping() {
+            return grid_x(line);
+        }
+
+        let text: Cow<str> = line.into();
+        if has_raw_bytes(&text) {
+            return grid_x(line);
+        }
+        let line = text;
+        let shaped = self.take_shaped_line(&line);
+        let x = shaped.carets[char_x.min(shaped.carets.len() - 1)];
+        self.put_shaped_line(line.into_owned(), shaped);
+        x
+    }
+
+    // Baseline of visible text row `row`
+    fn row_baseline(&self, row: usize) -> f32 {
+        (row + 1) as f32 * self.font_atlas.advance_height
+    }
+
+    fn add_shaped_line(
+        &mut self,
+        buff: *mut GlyphInstance,
+        x: f32,
+        baseline: f32,
+        line: &str,
+        color: Color,
+        whitespace: Option<Color>,
+    ) {
+        let shaped = self.take_shaped_line(line);
+
+        for glyph in &shaped.glyphs {
+            let g = if glyph.glyph_id != 0 {
+                self.font_atlas.get_glyph_by_index(glyph.glyph_id)
+            } else {
+                // the primary font doesn't have it, let the fallback chain try
+                match line[glyph.cluster..].chars().next() {
+                    Some(c) if !c.is_whitespace() && !c.is_control() => {
+                        self.font_atlas.get_glyph(c)
+                    }
+                    _ => continue,
+                }
+            };
+
+            unsafe {
+                *buff.offset(self.buffer_position) =
+                    g.instance(x + glyph.x, baseline + glyph.y, color);
+            }
+            self.buffer_position += 1;
+        }
+
+        if let Some(whitespace) = whitespace {
+            for (i, char) in line.chars().enumerate() {
+                if let Some(marker) = whitespace_marker(char) {
+                    let g = self.font_atlas.get_glyph(marker);
+                    unsafe {
+                        *buff.offset(self.buffer_position) =
+                            g.instance(x + shaped.carets[i], baseline, whitespace);
+                    }
+                    self.buffer_position += 1;
+                }
+            }
         }
+
+        self.put_shaped_line(line.to_owned(), shaped);
     }
 
+    // Glyphs sit on a grid of `char_width` cells, every grapheme cluster takes
+    // as many cells as grapheme_width says so wide glyphs line up with the
+    // cursor and selection
     fn add_line(
         &mut self,
         buff: *mut GlyphInstance,
-        line_number: usize,
-        line: impl Iterator<Item = char>,
+        x: f32,
+        baseline: f32,
+        line: &str,
+        color: Color,
+        whitespace: Option<Color>,
     ) {
-        let mut advance: f32 = 0.0;
-        let line_offset = line_number as f32 * self.font_atlas.advance_height as f32;
-        for char in line {
-            if char == '\n' {
+        let mut column: usize = 0;
+        for grapheme in line.graphemes(true) {
+            let x = x + column as f32 * self.char_width;
+            column += grapheme_width(grapheme);
+
+            if let Some(byte) = grapheme.chars().next().and_then(shown_byte) {
+                for (i, char) in format!("\\x{:02X}", byte).chars().enumerate() {
+                    let g = self.font_atlas.get_glyph(char);
+                    let x = x + i as f32 * self.char_width;
+                    unsafe {
+                        *buff.offset(self.buffer_position) =
+                            g.instance(x, baseline, self.raw_byte_color);
+                    }
+                    self.buffer_position += 1;
+                }
                 continue;
             }
-            if char == '\t' {
-                let g = self.font_atlas.get_glyph(' ');
-                advance += g.advance_width * 4.0;
-            } else {
+
+            let marker = grapheme.chars().next().and_then(whitespace_marker);
+            if let (Some(whitespace), Some(marker)) = (whitespace, marker) {
+                let g = self.font_atlas.get_glyph(marker);
+                unsafe {
+                    *buff.offset(self.buffer_position) = g.instance(x, baseline, whitespace);
+                }
+                self.buffer_position += 1;
+                continue;
+            }
+
+            let mut pen = x;
+            for (i, char) in grapheme.chars().enumerate() {
+                if char.is_whitespace() || char.is_control() {
+                    continue;
+                }
+
+                // without shaping only the base and its combining marks can be
+                // drawn, joiners, variation selectors and the rest of emoji
+                // sequences are skipped
+                let is_mark = UnicodeWidthChar::width(char) == Some(0)
+                    && char != '\u{200D}'
+                    && !('\u{FE00}'..='\u{FE0F}').contains(&char);
+                if i > 0 && !is_mark {
+                    continue;
+                }
+
                 let g = self.font_atlas.get_glyph(char);
                 unsafe {
-                    *buff.offset(self.buffer_position) = g.instance(
-                        advance,
-                        line_offset,
-                        [213.0 / 255.0, 213.0 / 255.0, 213.0 / 255.0],
-                    );
+                    *buff.offset(self.buffer_position) = g.instance(pen, baseline, color);
                 }
                 self.buffer_position += 1;
-                advance += g.advance_width;
+                if i == 0 {
+                    pen += g.advance_width;
+                }
             }
         }
     }
@@ -230,15 +528,33 @@ impl FontRenderer {
         }
     }
 
-    fn fill_buffer<'a>(
+    fn fill_buffer(
         &mut self,
         buffer: *mut GlyphInstance,
-        lines: impl Iterator<Item = RopeSlice<'a>>,
+        text: &ropey::Rope,
+        rows: &[DisplayLine],
+        theme: &Theme,
     ) {
-        let mut current_line: usize = 0;
-        for line in lines {
-            current_line += 1;
-            self.add_line(buffer, current_line, line.chars());
+        let x = self.gutter_width(text.len_lines());
+        let whitespace = if self.show_whitespace {
+            Some(theme.whitespace)
+        } else {
+            None
+        };
+        for (index, row) in rows.iter().enumerate() {
+            let mut line: Cow<str> = text.line(row.line).slice(row.start..row.end).into();
+            if row.folded {
+                let content = line.trim_end_matches(|c| c == '\n' || c == '\r');
+                line = Cow::Owned(format!("{}{}", content, FOLD_PLACEHOLDER));
+            }
+
+            let baseline = self.row_baseline(index);
+            // shaping would measure raw bytes as missing glyphs
+            if self.is_shaping() && !has_raw_bytes(&line) {
+                self.add_shaped_line(buffer, x, baseline, &line, theme.text, whitespace);
+            } else {
+                self.add_line(buffer, x, baseline, &line, theme.text, whitespace);
+            }
         }
     }
 
@@ -246,7 +562,7 @@ impl FontRenderer {
         unsafe {
             gl::BindVertexArray(self.vao);
             gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
-            gl::BindTexture(gl::TEXTURE_2D, self.font_atlas.texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.font_atlas.texture);
 
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_buffer);
             gl::DrawElementsInstanced(
@@ -263,15 +579,20 @@ impl FontRenderer {
     pub fn render_text_with_line_numbers(
         &mut self,
         text: &ropey::Rope,
-        range: std::ops::Range<usize>,
+        rows: &[DisplayLine],
+        markers: &[FoldMarker],
+        current_line: Option<usize>,
+        theme: &Theme,
         projection: &matrix::Matrix,
     ) {
-        let lines = text
-            .lines_at(range.start.min(text.len_lines()))
-            .take(range.end - range.start);
-
-        let car_count: usize = lines.clone().map(|x| x.len_chars()).sum();
-        self.ensure_buffer_size(car_count + range.len() * 15);
+        let mut car_count: usize = rows
+            .iter()
+            .map(|row| glyph_count(text.line(row.line).slice(row.start..row.end).chars()))
+            .sum();
+        if self.show_whitespace {
+            car_count *= 2;
+        }
+        self.ensure_buffer_size(car_count + rows.len() * (15 + FOLD_PLACEHOLDER.len()));
 
         let buffer =
             unsafe { gl::MapBuffer(gl::ARRAY_BUFFER, gl::WRITE_ONLY) as *mut GlyphInstance };
@@ -279,12 +600,12 @@ impl FontRenderer {
 		{
 			
 			timer!("text_buffer");
-			self.fill_buffer(buffer, lines);
+			self.fill_buffer(buffer, text, rows, theme);
 		}
 
 		{
 			timer!("line_numbers_buffer");
-			self.fill_line_numbers(buffer, range);
+			self.fill_line_numbers(buffer, text.len_lines(), rows, markers, current_line, theme);
 		}
 
         unsafe {
@@ -303,46 +624,98 @@ impl FontRenderer {
         check_error!();
     }
 
-    fn fill_line_numbers(&mut self, buffer: *mut GlyphInstance, range: std::ops::Range<usize>) {
-
-		let mut stack_buffer: [u8; 20] = unsafe { MaybeUninit::uninit().assume_init() };
-
-        for (index, range_value) in range.clone().into_iter().enumerate() {
-            let n = itoa::write(&mut stack_buffer[..], range_value + 1).unwrap();
+    // Draws one grapheme cluster on screen row `row`, the glyph under a block
+    // cursor is drawn again on top of the cursor this way
+    pub fn render_cluster(
+        &mut self,
+        cluster: &str,
+        x: f32,
+        row: usize,
+        color: Color,
+        projection: &matrix::Matrix,
+    ) {
+        self.ensure_buffer_size(glyph_count(cluster.chars()));
 
-            self.add_line(
-                buffer,
-                index + 1,
-                CharBytesIterator::new(&stack_buffer[..n]),
-            );
+        let buffer =
+            unsafe { gl::MapBuffer(gl::ARRAY_BUFFER, gl::WRITE_ONLY) as *mut GlyphInstance };
+        let baseline = self.row_baseline(row);
+        self.add_line(buffer, x, baseline, cluster, color, None);
+        unsafe {
+            gl::UnmapBuffer(gl::ARRAY_BUFFER);
         }
+
+        self.program.set_used();
+        self.set_projection(projection);
+        self.draw_buffer();
+        check_error!();
     }
-}
 
-struct CharBytesIterator<'a> {
-    src: &'a [u8],
-    current: usize,
-}
+    // Single line strings anywhere on screen, for the status bar and pickers
+    pub fn render_labels(&mut self, labels: &[Label], projection: &matrix::Matrix) {
+        let char_count: usize = labels
+            .iter()
+            .map(|label| glyph_count(label.text.chars()))
+            .sum();
+        self.ensure_buffer_size(char_count);
 
-impl<'a> CharBytesIterator<'a> {
-    pub fn new(buff: &'a [u8]) -> CharBytesIterator<'a> {
-        CharBytesIterator {
-            src: buff,
-            current: 0,
+        let buffer =
+            unsafe { gl::MapBuffer(gl::ARRAY_BUFFER, gl::WRITE_ONLY) as *mut GlyphInstance };
+        for label in labels {
+            self.add_line(buffer, label.x, label.baseline, label.text, label.color, None);
+        }
+        unsafe {
+            gl::UnmapBuffer(gl::ARRAY_BUFFER);
         }
+
+        self.program.set_used();
+        self.set_projection(projection);
+        self.draw_buffer();
+        check_error!();
     }
-}
 
-impl<'a> Iterator for CharBytesIterator<'a> {
-    type Item = char;
+    // Gutter holds right aligned line numbers and the fold marker, with a
+    // space on both sides of the marker
+    pub fn gutter_width(&self, line_count: usize) -> f32 {
+        (digit_count(line_count).max(2) + 3) as f32 * self.char_width
+    }
 
-    fn next(&mut self) -> Option<char> {
-        if self.current < self.src.len() {
-            let s = Some(self.src[self.current] as char);
-            self.current += 1;
-            return s;
-        } else {
-            return None;
+    // Continuation rows of wrapped lines get no number
+    fn fill_line_numbers(
+        &mut self,
+        buffer: *mut GlyphInstance,
+        line_count: usize,
+        rows: &[DisplayLine],
+        markers: &[FoldMarker],
+        current_line: Option<usize>,
+        theme: &Theme,
+    ) {
+
+		let mut stack_buffer: [u8; 20] = unsafe { MaybeUninit::uninit().assume_init() };
+        let digits = digit_count(line_count).max(2);
+
+        for (index, row) in rows.iter().enumerate() {
+            if row.is_continuation() {
+                continue;
+            }
+            let n = itoa::write(&mut stack_buffer[..], row.line + 1).unwrap();
+
+            let number = std::str::from_utf8(&stack_buffer[..n]).unwrap();
+            let x = (digits - n) as f32 * self.char_width;
+            let color = if current_line == Some(row.line) {
+                theme.current_line_number
+            } else {
+                theme.line_number
+            };
+            let baseline = self.row_baseline(index);
+            self.add_line(buffer, x, baseline, number, color, None);
+
+            let marker = match markers.get(index) {
+                Some(FoldMarker::Open) => "\u{25BE}",
+                Some(FoldMarker::Folded) => "\u{25B8}",
+                _ => continue,
+            };
+            let x = (digits + 1) as f32 * self.char_width;
+            self.add_line(buffer, x, baseline, marker, theme.line_number, None);
         }
     }
 }
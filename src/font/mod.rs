@@ -1,2 +1,12 @@
+pub mod atlas_packer;
 pub mod font;
+pub mod font_lookup;
 pub mod font_renderer;
+pub mod shaper;
+
+#[cfg(test)]
+mod atlas_packer_test;
+#[cfg(test)]
+mod font_lookup_test;
+#[cfg(test)]
+mod shaper_test;
@@ -0,0 +1,151 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use rustybuzz::{Face, UnicodeBuffer};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShapedGlyph {
+    pub glyph_id: u32,
+    pub x: f32,
+    // offset down from the baseline
+    pub y: f32,
+    // byte offset of the first char the glyph was made from
+    pub cluster: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShapedLine {
+    pub glyphs: Vec<ShapedGlyph>,
+    // x of the caret in front of every char, plus one past the last char
+    pub carets: Vec<f32>,
+}
+
+// One shaped glyph in visual order, only what is needed to place carets
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphRun {
+    pub cluster: usize,
+    pub x: f32,
+    pub advance: f32,
+}
+
+// Carets inside a cluster (a ligature like `=>` or `!=`) split its advance
+// evenly between the grapheme clusters it was made from. Chars in the middle
+// of a grapheme share the caret of its start.
+pub fn compute_carets(text: &str, runs: &[GlyphRun], end_x: f32) -> Vec<f32> {
+    let char_count = text.chars().count();
+    let mut carets = vec![end_x; char_count + 1];
+    if runs.is_empty() {
+        return carets;
+    }
+
+    let rtl = runs.len() > 1 && runs[0].cluster > runs[runs.len() - 1].cluster;
+
+    let mut clusters: BTreeMap<usize, (f32, f32)> = BTreeMap::new();
+    for run in runs {
+        let bounds = clusters.entry(run.cluster).or_insert((run.x, run.x));
+        bounds.0 = bounds.0.min(run.x);
+        bounds.1 = bounds.1.max(run.x + run.advance);
+    }
+
+    let starts: Vec<usize> = clusters.keys().cloned().collect();
+    let mut char_idx = text[..starts[0]].chars().count();
+
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).cloned().unwrap_or(text.len());
+        let (left, right) = clusters[&start];
+        let width = right - left;
+
+        let graphemes: Vec<&str> = text[start..end].graphemes(true).collect();
+        for (j, grapheme) in graphemes.iter().enumerate() {
+            let fraction = j as f32 / graphemes.len() as f32;
+            let x = if rtl {
+                right - fraction * width
+            } else {
+                left + fraction * width
+            };
+
+            for _ in grapheme.chars() {
+                carets[char_idx] = x;
+                char_idx += 1;
+            }
+        }
+    }
+
+    if rtl {
+        carets[char_count] = clusters.values().map(|b| b.0).fold(end_x, f32::min);
+    }
+
+    carets
+}
+
+// Font data rustybuzz can read. The face borrows the data, so it is parsed
+// again for every line that isn't in the shaping cache, which is cheap next
+// to the shaping.
+pub struct ShapingFace {
+    data: Rc<Vec<u8>>,
+}
+
+impl ShapingFace {
+    // None when rustybuzz can't read the font
+    pub fn new(data: Rc<Vec<u8>>) -> Option<ShapingFace> {
+        Face::from_slice(&data, 0)?;
+        Some(ShapingFace { data })
+    }
+
+    pub fn face(&self) -> Face<'_> {
+        Face::from_slice(&self.data, 0).expect("the font was read in new")
+    }
+}
+
+// `scale` converts font units to pixels. Tabs are not shaped, they split the
+// line into separately shaped segments and advance by `tab_advance`.
+pub fn shape_line(face: &Face, scale: f32, tab_advance: f32, line: &str) -> ShapedLine {
+    let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+
+    let mut glyphs = Vec::new();
+    let mut runs = Vec::new();
+    let mut x = 0.0;
+    let mut segment_start = 0;
+
+    for segment in line.split('\t') {
+        if !segment.is_empty() {
+            let mut buffer = UnicodeBuffer::new();
+            buffer.push_str(segment);
+            let output = rustybuzz::shape(face, &[], buffer);
+
+            for (info, pos) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+                let cluster = segment_start + info.cluster as usize;
+                let advance = pos.x_advance as f32 * scale;
+
+                glyphs.push(ShapedGlyph {
+                    glyph_id: info.codepoint,
+                    x: x + pos.x_offset as f32 * scale,
+                    y: -pos.y_offset as f32 * scale,
+                    cluster,
+                });
+                runs.push(GlyphRun {
+                    cluster,
+                    x,
+                    advance,
+                });
+                x += advance;
+            }
+        }
+
+        segment_start += segment.len();
+        if segment_start < line.len() {
+            runs.push(GlyphRun {
+                cluster: segment_start,
+                x,
+                advance: tab_advance,
+            });
+            x += tab_advance;
+            segment_start += 1;
+        }
+    }
+
+    ShapedLine {
+        glyphs,
+        carets: compute_carets(line, &runs, x),
+    }
+}
@@ -0,0 +1,55 @@
+use crate::font::shaper::{compute_carets, GlyphRun};
+
+fn run(cluster: usize, x: f32, advance: f32) -> GlyphRun {
+    GlyphRun {
+        cluster,
+        x,
+        advance,
+    }
+}
+
+#[test]
+fn carets_without_ligatures() {
+    let runs = [run(0, 0.0, 10.0), run(1, 10.0, 10.0), run(2, 20.0, 10.0)];
+
+    assert_eq!(
+        compute_carets("abc", &runs, 30.0),
+        vec![0.0, 10.0, 20.0, 30.0]
+    );
+}
+
+#[test]
+fn carets_split_ligature_evenly() {
+    // `=>` shaped into a single glyph two cells wide
+    let runs = [run(0, 0.0, 10.0), run(1, 10.0, 20.0), run(3, 30.0, 10.0)];
+
+    assert_eq!(
+        compute_carets("a=>b", &runs, 40.0),
+        vec![0.0, 10.0, 20.0, 30.0, 40.0]
+    );
+}
+
+#[test]
+fn carets_keep_combining_marks_with_base() {
+    let runs = [run(0, 0.0, 10.0), run(0, 10.0, 0.0), run(3, 10.0, 10.0)];
+
+    assert_eq!(
+        compute_carets("e\u{301}x", &runs, 20.0),
+        vec![0.0, 0.0, 10.0, 20.0]
+    );
+}
+
+#[test]
+fn carets_right_to_left() {
+    let runs = [run(4, 0.0, 10.0), run(2, 10.0, 10.0), run(0, 20.0, 10.0)];
+
+    assert_eq!(
+        compute_carets("\u{0627}\u{0628}\u{062A}", &runs, 30.0),
+        vec![30.0, 20.0, 10.0, 0.0]
+    );
+}
+
+#[test]
+fn carets_for_empty_line() {
+    assert_eq!(compute_carets("", &[], 0.0), vec![0.0]);
+}
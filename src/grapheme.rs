@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use ropey::{Rope, RopeSlice};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
+
+use crate::encoding::shown_byte;
+
+pub const TAB_WIDTH: usize = 4;
+pub const RAW_BYTE_WIDTH: usize = 4;
+
+fn byte_to_char_in_chunk(chunk: &str, byte_idx: usize) -> usize {
+    chunk[..byte_idx].chars().count()
+}
+
+pub fn prev_grapheme_boundary(text: &Rope, char_idx: usize) -> usize {
+    let byte_idx = text.char_to_byte(char_idx);
+    let (mut chunk, mut chunk_byte_idx, mut chunk_char_idx, _) = text.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, text.len_bytes(), true);
+
+    loop {
+        match cursor.prev_boundary(chunk, chunk_byte_idx) {
+            Ok(None) => return 0,
+            Ok(Some(n)) => {
+                return chunk_char_idx + byte_to_char_in_chunk(chunk, n - chunk_byte_idx)
+            }
+            Err(GraphemeIncomplete::PrevChunk) => {
+                let (c, byte, char, _) = text.chunk_at_byte(chunk_byte_idx - 1);
+                chunk = c;
+                chunk_byte_idx = byte;
+                chunk_char_idx = char;
+            }
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let context = text.chunk_at_byte(n - 1).0;
+                cursor.provide_context(context, n - context.len());
+            }
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+pub fn next_grapheme_boundary(text: &Rope, char_idx: usize) -> usize {
+    let byte_idx = text.char_to_byte(char_idx);
+    let (mut chunk, mut chunk_byte_idx, mut chunk_char_idx, _) = text.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, text.len_bytes(), true);
+
+    loop {
+        match cursor.next_boundary(chunk, chunk_byte_idx) {
+            Ok(None) => return text.len_chars(),
+            Ok(Some(n)) => {
+                return chunk_char_idx + byte_to_char_in_chunk(chunk, n - chunk_byte_idx)
+            }
+            Err(GraphemeIncomplete::NextChunk) => {
+                chunk_byte_idx += chunk.len();
+                chunk_char_idx += chunk.chars().count();
+                chunk = text.chunk_at_byte(chunk_byte_idx).0;
+            }
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let context = text.chunk_at_byte(n - 1).0;
+                cursor.provide_context(context, n - context.len());
+            }
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+pub fn is_grapheme_boundary(text: &Rope, char_idx: usize) -> bool {
+    if char_idx == 0 || char_idx >= text.len_chars() {
+        return true;
+    }
+    next_grapheme_boundary(text, prev_grapheme_boundary(text, char_idx)) == char_idx
+}
+
+// Number of cells a cluster takes on screen, wide (CJK, emoji) clusters take
+// two, combining marks don't add anything to their base. Raw bytes show up as
+// `\xNN`.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    if grapheme == "\t" {
+        return TAB_WIDTH;
+    }
+    if grapheme.chars().next().and_then(shown_byte).is_some() {
+        return RAW_BYTE_WIDTH;
+    }
+    grapheme.width().min(2)
+}
+
+pub fn visual_column(line: RopeSlice, char_x: usize) -> usize {
+    let line: Cow<str> = line.into();
+    let mut chars = 0;
+    let mut column = 0;
+
+    for grapheme in line.graphemes(true) {
+        if chars >= char_x {
+            break;
+        }
+        chars += grapheme.chars().count();
+        column += grapheme_width(grapheme);
+    }
+
+    column
+}
+
+// Char offset of the cluster under `column`, a column inside a wide cluster
+// resolves to its start
+pub fn char_x_at_column(line: RopeSlice, column: usize) -> usize {
+    let line: Cow<str> = line.into();
+    let mut chars = 0;
+    let mut current = 0;
+
+    for grapheme in line.graphemes(true) {
+        if grapheme == "\n" || grapheme == "\r\n" {
+            break;
+        }
+
+        let width = grapheme_width(grapheme);
+        if current + width > column {
+            break;
+        }
+        current += width;
+        chars += grapheme.chars().count();
+    }
+
+    chars
+}
@@ -0,0 +1,228 @@
+use crate::encoding::{Encoding, FileEncoding};
+
+pub const BYTES_PER_ROW: usize = 16;
+// A row reads `00000010  48 65 6C 6C 6F 20 77 6F  72 6C 64 0A 00 00 00 00  |Hello world.....|`
+const HEX_START: usize = 10;
+const ASCII_START: usize = 61;
+// Bytes looked at to tell a binary file from text
+const BINARY_SNIFF_LEN: usize = 8000;
+
+// Text files don't have NUL bytes, unless they are UTF-16
+pub fn looks_binary(bytes: &[u8], encoding: FileEncoding) -> bool {
+    match encoding.encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => false,
+        _ => bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0),
+    }
+}
+
+pub fn row_count(len: usize) -> usize {
+    ((len + BYTES_PER_ROW - 1) / BYTES_PER_ROW).max(1)
+}
+
+// Column of the first digit of the `index`th byte of a row, there is an extra
+// space in the middle
+pub fn hex_column(index: usize) -> usize {
+    HEX_START + index * 3 + index / 8
+}
+
+pub fn ascii_column(index: usize) -> usize {
+    ASCII_START + index
+}
+
+pub fn row_text(bytes: &[u8], row: usize) -> String {
+    let start = (row * BYTES_PER_ROW).min(bytes.len());
+    let end = (start + BYTES_PER_ROW).min(bytes.len());
+    let mut text = format!("{:08X}  ", row * BYTES_PER_ROW);
+
+    for index in 0..BYTES_PER_ROW {
+        if index == BYTES_PER_ROW / 2 {
+            text.push(' ');
+        }
+        match bytes.get(start + index) {
+            Some(byte) if start + index < end => text.push_str(&format!("{:02X} ", byte)),
+            _ => text.push_str("   "),
+        }
+    }
+
+    text.push_str(" |");
+    for &byte in bytes[start..end].iter() {
+        let printable = byte.is_ascii_graphic() || byte == b' ';
+        text.push(if printable { byte as char } else { '.' });
+    }
+    text.push('|');
+    text
+}
+
+// Hex digits with any whitespace between them, `DE AD be ef`
+pub fn parse_pattern(pattern: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<_>>()?;
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
+
+pub fn pattern_text(pattern: &[u8]) -> String {
+    let bytes: Vec<String> = pattern.iter().map(|byte| format!("{:02X}", byte)).collect();
+    bytes.join(" ")
+}
+
+// Bytes of a file edited in place, nothing is ever inserted or removed
+pub struct HexBuffer {
+    pub bytes: Vec<u8>,
+    // in nibbles, the high one of every byte comes first
+    pub cursor: usize,
+    // byte and the value it had before every edit
+    undo: Vec<(usize, u8)>,
+    redo: Vec<(usize, u8)>,
+    // length of `undo` when the bytes were saved, None if they never were
+    saved_at: Option<usize>,
+    // last pattern searched for
+    pub pattern: Vec<u8>,
+    // bumped on every change to the bytes
+    revision: u64,
+}
+
+impl HexBuffer {
+    // `saved` when the bytes are what is on disk
+    pub fn new(bytes: Vec<u8>, saved: bool) -> HexBuffer {
+        HexBuffer {
+            bytes,
+            cursor: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved_at: if saved { Some(0) } else { None },
+            pattern: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn cursor_byte(&self) -> usize {
+        self.cursor / 2
+    }
+
+    pub fn cursor_row(&self) -> usize {
+        self.cursor_byte() / BYTES_PER_ROW
+    }
+
+    fn last_nibble(&self) -> usize {
+        (self.bytes.len() * 2).max(1) - 1
+    }
+
+    pub fn move_nibbles(&mut self, by: i64) {
+        let cursor = (self.cursor as i64 + by).max(0) as usize;
+        self.cursor = cursor.min(self.last_nibble());
+    }
+
+    // Lands on the high nibble of the byte
+    pub fn move_bytes(&mut self, by: i64) {
+        let byte = (self.cursor_byte() as i64 + by).max(0) as usize;
+        self.cursor = (byte * 2).min(self.last_nibble() & !1);
+    }
+
+    // Keeps the column, a short last row stops at its last byte
+    pub fn move_rows(&mut self, by: i64) {
+        let row_nibbles = (BYTES_PER_ROW * 2) as i64;
+        let cursor = self.cursor as i64 + by * row_nibbles;
+        if cursor >= 0 {
+            self.cursor = (cursor as usize).min(self.last_nibble());
+        }
+    }
+
+    pub fn move_to_row_start(&mut self) {
+        self.cursor -= self.cursor % (BYTES_PER_ROW * 2);
+    }
+
+    pub fn move_to_row_end(&mut self) {
+        let start = self.cursor - self.cursor % (BYTES_PER_ROW * 2);
+        self.cursor = (start + BYTES_PER_ROW * 2 - 1).min(self.last_nibble());
+    }
+
+    // Overwrites the nibble under the cursor and moves past it
+    pub fn set_nibble(&mut self, value: u8) {
+        let index = self.cursor_byte();
+        let old = match self.bytes.get(index) {
+            Some(&old) => old,
+            None => return,
+        };
+
+        let new = if self.cursor % 2 == 0 {
+            value << 4 | old & 0x0F
+        } else {
+            old & 0xF0 | value
+        };
+        if new != old {
+            if self
+                .saved_at
+                .map_or(false, |saved_at| saved_at > self.undo.len())
+            {
+                self.saved_at = None;
+            }
+            self.undo.push((index, old));
+            self.redo.clear();
+            self.bytes[index] = new;
+            self.revision += 1;
+        }
+        self.move_nibbles(1);
+    }
+
+    fn swap(&mut self, from_undo: bool) {
+        let (from, to) = if from_undo {
+            (&mut self.undo, &mut self.redo)
+        } else {
+            (&mut self.redo, &mut self.undo)
+        };
+        if let Some((index, value)) = from.pop() {
+            to.push((index, self.bytes[index]));
+            self.bytes[index] = value;
+            self.cursor = index * 2;
+            self.revision += 1;
+        }
+    }
+
+    pub fn undo(&mut self) {
+        self.swap(true);
+    }
+
+    pub fn redo(&mut self) {
+        self.swap(false);
+    }
+
+    pub fn has_edits(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.saved_at != Some(self.undo.len())
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo.len());
+    }
+
+    // Next match after the cursor byte, wrapping around the end
+    pub fn find(&self, pattern: &[u8]) -> Option<usize> {
+        if pattern.is_empty() || pattern.len() > self.bytes.len() {
+            return None;
+        }
+
+        let starts = self.bytes.len() - pattern.len() + 1;
+        let from = self.cursor_byte() + 1;
+        (from..starts)
+            .chain(0..from.min(starts))
+            .find(|&start| self.bytes[start..].starts_with(pattern))
+    }
+}
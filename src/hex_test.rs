@@ -0,0 +1,128 @@
+use crate::encoding::{detect, FileEncoding};
+use crate::hex::{
+    hex_column, looks_binary, parse_pattern, pattern_text, row_count, row_text, HexBuffer,
+};
+
+#[test]
+fn formats_rows() {
+    let bytes = b"Hello world\n\x00\x01\x7F\xFFtail";
+
+    assert_eq!(
+        row_text(bytes, 0),
+        "00000000  48 65 6C 6C 6F 20 77 6F  72 6C 64 0A 00 01 7F FF  |Hello world.....|"
+    );
+    assert_eq!(
+        row_text(bytes, 1),
+        "00000010  74 61 69 6C                                       |tail|"
+    );
+    assert_eq!(row_text(b"", 0), format!("00000000{}||", " ".repeat(52)));
+    assert_eq!(&row_text(bytes, 0)[hex_column(9)..hex_column(9) + 2], "6C");
+}
+
+#[test]
+fn counts_rows() {
+    assert_eq!(row_count(0), 1);
+    assert_eq!(row_count(16), 1);
+    assert_eq!(row_count(17), 2);
+}
+
+#[test]
+fn tells_binary_from_text() {
+    let elf = b"\x7FELF\x02\x01\x01\x00";
+    let utf16 = b"h\x00i\x00";
+
+    assert!(looks_binary(elf, detect(elf)));
+    assert!(!looks_binary(utf16, detect(utf16)));
+    assert!(!looks_binary(
+        "plain text, ü".as_bytes(),
+        FileEncoding::UTF8
+    ));
+}
+
+#[test]
+fn parses_byte_patterns() {
+    assert_eq!(
+        parse_pattern("DE AD be ef"),
+        Some(vec![0xDE, 0xAD, 0xBE, 0xEF])
+    );
+    assert_eq!(parse_pattern("0a0"), None);
+    assert_eq!(parse_pattern("zz"), None);
+    assert_eq!(parse_pattern(" "), None);
+    assert_eq!(pattern_text(&[0x0A, 0xFF]), "0A FF");
+}
+
+#[test]
+fn edits_nibbles_in_place() {
+    let mut buffer = HexBuffer::new(vec![0x00, 0x11], true);
+
+    buffer.set_nibble(0xA);
+    buffer.set_nibble(0xB);
+    buffer.set_nibble(0xC);
+
+    assert_eq!(buffer.bytes, vec![0xAB, 0xC1]);
+    assert_eq!(buffer.cursor, 3);
+
+    buffer.set_nibble(0xD);
+    buffer.set_nibble(0xE);
+
+    assert_eq!(buffer.bytes, vec![0xAB, 0xCE]);
+    assert_eq!(buffer.cursor, 3);
+}
+
+#[test]
+fn undo_back_to_saved_is_unmodified() {
+    let mut buffer = HexBuffer::new(vec![0x00, 0x00], true);
+
+    buffer.set_nibble(0x1);
+    assert!(buffer.is_modified());
+
+    buffer.undo();
+    assert!(!buffer.is_modified());
+    assert_eq!(buffer.bytes, vec![0x00, 0x00]);
+
+    buffer.redo();
+    buffer.mark_saved();
+    buffer.undo();
+    buffer.set_nibble(0x2);
+    buffer.undo();
+    assert!(buffer.is_modified());
+    assert!(!HexBuffer::new(vec![], false).has_edits());
+    assert!(HexBuffer::new(vec![], false).is_modified());
+}
+
+#[test]
+fn moves_by_rows_within_the_buffer() {
+    let mut buffer = HexBuffer::new(vec![0; 40], true);
+
+    buffer.move_nibbles(3);
+    buffer.move_rows(2);
+    assert_eq!(buffer.cursor, 67);
+
+    buffer.move_rows(1);
+    assert_eq!(buffer.cursor, 79);
+
+    buffer.move_rows(-5);
+    assert_eq!(buffer.cursor, 79);
+
+    buffer.move_to_row_start();
+    assert_eq!(buffer.cursor, 64);
+    buffer.move_to_row_end();
+    assert_eq!(buffer.cursor, 79);
+
+    buffer.move_bytes(-1);
+    assert_eq!(buffer.cursor, 76);
+    buffer.move_bytes(5);
+    assert_eq!(buffer.cursor, 78);
+}
+
+#[test]
+fn finds_patterns_after_the_cursor() {
+    let mut buffer = HexBuffer::new(vec![1, 2, 3, 1, 2, 3, 4], true);
+
+    assert_eq!(buffer.find(&[1, 2]), Some(3));
+
+    buffer.cursor = 6;
+    assert_eq!(buffer.find(&[1, 2]), Some(0));
+    assert_eq!(buffer.find(&[5]), None);
+    assert_eq!(buffer.find(&[0; 8]), None);
+}
@@ -3,21 +3,42 @@ extern crate gl;
 extern crate glfw;
 
 mod app;
+mod buffer;
+mod caret;
 mod check_error;
+mod config;
 mod cursor;
+mod diff;
+mod display_map;
 mod editor_action;
+mod encoding;
+mod file_format;
+mod file_io;
+mod folding;
 mod font;
+mod grapheme;
+mod hex;
 mod matrix;
+mod minimap;
+mod occurrences;
 mod offset_of;
 mod process_keyboard;
 mod program;
+mod prompt;
+mod recovery;
 mod rect;
 mod render;
 mod scroll;
+mod scrollbar;
 mod shaders;
+mod status_bar;
+mod tab_bar;
 mod task_executor;
 mod text;
+mod theme;
 mod timer;
+mod watcher;
+mod whitespace;
 
 fn main() {
     let program = program::Program::new();
@@ -27,4 +48,40 @@ fn main() {
 #[cfg(test)]
 mod app_test;
 #[cfg(test)]
+mod caret_test;
+#[cfg(test)]
+mod config_test;
+#[cfg(test)]
+mod diff_test;
+#[cfg(test)]
+mod display_map_test;
+#[cfg(test)]
+mod encoding_test;
+#[cfg(test)]
+mod file_format_test;
+#[cfg(test)]
+mod file_io_test;
+#[cfg(test)]
+mod folding_test;
+#[cfg(test)]
+mod hex_test;
+#[cfg(test)]
+mod minimap_test;
+#[cfg(test)]
+mod occurrences_test;
+#[cfg(test)]
+mod prompt_test;
+#[cfg(test)]
+mod recovery_test;
+#[cfg(test)]
+mod scrollbar_test;
+#[cfg(test)]
+mod status_bar_test;
+#[cfg(test)]
+mod tab_bar_test;
+#[cfg(test)]
 mod text_test;
+#[cfg(test)]
+mod watcher_test;
+#[cfg(test)]
+mod whitespace_test;
@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+
+use ropey::{Rope, RopeSlice};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::grapheme::grapheme_width;
+use crate::text::LineEdit;
+
+// Columns past this are cut off
+pub const MINIMAP_COLUMNS: usize = 120;
+// Size of one character cell in logical pixels, blocks leave a gap below them
+// so lines don't merge
+pub const MINIMAP_CHAR_WIDTH: f32 = 1.0;
+pub const MINIMAP_LINE_HEIGHT: f32 = 2.0;
+pub const MINIMAP_BLOCK_HEIGHT: f32 = 1.0;
+
+// Columns `start..start + len` of a line are covered by non whitespace
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Run {
+    pub start: usize,
+    pub len: usize,
+}
+
+pub fn line_runs(line: RopeSlice) -> Vec<Run> {
+    let line: Cow<str> = line.into();
+    let mut runs: Vec<Run> = Vec::new();
+    let mut column = 0;
+
+    for grapheme in line.graphemes(true) {
+        if column >= MINIMAP_COLUMNS {
+            break;
+        }
+
+        let width = grapheme_width(grapheme).min(MINIMAP_COLUMNS - column);
+        if width > 0 && !grapheme.chars().all(char::is_whitespace) {
+            match runs.last_mut() {
+                Some(run) if run.start + run.len == column => run.len += width,
+                _ => runs.push(Run {
+                    start: column,
+                    len: width,
+                }),
+            }
+        }
+        column += width;
+    }
+
+    runs
+}
+
+// Runs of every text line. Follows the text through its line edits, so typing
+// only reads the lines that changed instead of the whole file.
+pub struct Minimap {
+    lines: Vec<Vec<Run>>,
+    revision: Option<u64>,
+}
+
+impl Minimap {
+    pub fn new() -> Minimap {
+        Minimap {
+            lines: Vec::new(),
+            revision: None,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn runs(&self, line: usize) -> &[Run] {
+        match self.lines.get(line) {
+            Some(runs) => runs,
+            None => &[],
+        }
+    }
+
+    // `edits` are all the changes that brought the text to `revision`, every
+    // edit is one revision. Anything missed means reading the whole text again.
+    pub fn update(&mut self, text: &Rope, revision: u64, edits: &[LineEdit]) {
+        if self.revision == Some(revision) {
+            return;
+        }
+
+        let follows = self.revision.map(|r| r + edits.len() as u64) == Some(revision);
+        let edit = match edits.split_first() {
+            Some((first, rest)) => Some(rest.iter().fold(*first, |edit, &next| edit.then(next))),
+            None => None,
+        };
+
+        match edit {
+            Some(edit) if follows && edit.old_end <= self.lines.len() => {
+                let changed = (edit.start..edit.new_end).map(|line| line_runs(text.line(line)));
+                self.lines.splice(edit.start..edit.old_end, changed);
+            }
+            _ => self.lines.clear(),
+        }
+
+        if self.lines.len() != text.len_lines() {
+            self.lines = text.lines().map(line_runs).collect();
+        }
+        self.revision = Some(revision);
+    }
+}
+
+// Placement of the minimap on screen in framebuffer pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimapLayout {
+    pub x: f32,
+    pub char_width: f32,
+    pub line_height: f32,
+    pub block_height: f32,
+    // minimap content scrolled out above the window
+    pub offset: f32,
+}
+
+impl MinimapLayout {
+    // When all lines don't fit in `height` the minimap scrolls along with the
+    // text, reaching its end when the text does
+    pub fn new(
+        x: f32,
+        scale: f32,
+        height: f32,
+        line_count: usize,
+        scroll: f32,
+        max_scroll: f32,
+    ) -> MinimapLayout {
+        let line_height = MINIMAP_LINE_HEIGHT * scale;
+        let overflow = line_count as f32 * line_height - height;
+        let offset = if overflow > 0.0 && max_scroll > 0.0 {
+            (scroll / max_scroll).max(0.0).min(1.0) * overflow
+        } else {
+            0.0
+        };
+
+        MinimapLayout {
+            x,
+            char_width: MINIMAP_CHAR_WIDTH * scale,
+            line_height,
+            block_height: MINIMAP_BLOCK_HEIGHT * scale,
+            offset: offset.round(),
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        MINIMAP_COLUMNS as f32 * self.char_width
+    }
+
+    pub fn line_to_y(&self, line: usize) -> f32 {
+        line as f32 * self.line_height - self.offset
+    }
+
+    pub fn y_to_line(&self, y: f32, line_count: usize) -> usize {
+        let line = ((y + self.offset) / self.line_height).max(0.0) as usize;
+        line.min(line_count.max(1) - 1)
+    }
+
+    pub fn visible_lines(&self, height: f32, line_count: usize) -> std::ops::Range<usize> {
+        let start = (self.offset / self.line_height) as usize;
+        let end = ((self.offset + height) / self.line_height).ceil() as usize;
+        start.min(line_count)..end.min(line_count)
+    }
+}
@@ -0,0 +1,124 @@
+use ropey::Rope;
+
+use crate::minimap::{line_runs, Minimap, MinimapLayout, Run, MINIMAP_COLUMNS};
+use crate::text::{LineEdit, Text};
+
+fn run(start: usize, len: usize) -> Run {
+    Run { start, len }
+}
+
+fn sync(minimap: &mut Minimap, text: &mut Text) {
+    let edits = text.take_edits();
+    minimap.update(text.get_text(), text.revision(), &edits);
+}
+
+#[test]
+fn runs_skip_whitespace() {
+    let text = Rope::from_str("  let x\t= 1;\n");
+    assert_eq!(
+        line_runs(text.line(0)),
+        vec![run(2, 3), run(6, 1), run(11, 1), run(13, 2)]
+    );
+}
+
+#[test]
+fn runs_use_visual_columns() {
+    let text = Rope::from_str("日本 ab");
+    assert_eq!(line_runs(text.line(0)), vec![run(0, 4), run(5, 2)]);
+}
+
+#[test]
+fn runs_are_cut_at_last_column() {
+    let line = "x".repeat(MINIMAP_COLUMNS + 10);
+    let text = Rope::from_str(&line);
+    assert_eq!(line_runs(text.line(0)), vec![run(0, MINIMAP_COLUMNS)]);
+}
+
+#[test]
+fn update_follows_edits() {
+    let mut text = Text::new("a\nb\nc".as_bytes());
+    let mut minimap = Minimap::new();
+    sync(&mut minimap, &mut text);
+    assert_eq!(minimap.line_count(), 3);
+
+    text.get_cursor().position.x = 1;
+    text.insert_text("\n  xy");
+    text.insert_text("z");
+    sync(&mut minimap, &mut text);
+
+    assert_eq!(minimap.line_count(), 4);
+    assert_eq!(minimap.runs(0), &[run(0, 1)]);
+    assert_eq!(minimap.runs(1), &[run(2, 3)]);
+    assert_eq!(minimap.runs(2), &[run(0, 1)]);
+}
+
+#[test]
+fn update_rereads_after_missed_edits() {
+    let mut text = Text::new("a\nb".as_bytes());
+    let mut minimap = Minimap::new();
+    sync(&mut minimap, &mut text);
+
+    text.insert_text("x");
+    text.take_edits();
+    text.get_cursor().position.y = 1;
+    text.insert_text("yy");
+    sync(&mut minimap, &mut text);
+
+    assert_eq!(minimap.runs(0), &[run(0, 2)]);
+    assert_eq!(minimap.runs(1), &[run(0, 3)]);
+}
+
+#[test]
+fn edits_merge() {
+    let insert = LineEdit {
+        start: 0,
+        old_end: 1,
+        new_end: 2,
+    };
+    let below = LineEdit {
+        start: 5,
+        old_end: 6,
+        new_end: 6,
+    };
+    assert_eq!(
+        insert.then(below),
+        LineEdit {
+            start: 0,
+            old_end: 5,
+            new_end: 6
+        }
+    );
+
+    let remove = LineEdit {
+        start: 2,
+        old_end: 4,
+        new_end: 3,
+    };
+    assert_eq!(
+        remove.then(insert),
+        LineEdit {
+            start: 0,
+            old_end: 4,
+            new_end: 4
+        }
+    );
+}
+
+#[test]
+fn layout_scrolls_with_text() {
+    // 100 lines of 2px in a 100px window
+    let layout = MinimapLayout::new(0.0, 1.0, 100.0, 100, 0.0, 50.0);
+    assert_eq!(layout.offset, 0.0);
+    assert_eq!(layout.visible_lines(100.0, 100), 0..50);
+
+    let layout = MinimapLayout::new(0.0, 1.0, 100.0, 100, 50.0, 50.0);
+    assert_eq!(layout.offset, 100.0);
+    assert_eq!(layout.visible_lines(100.0, 100), 50..100);
+    assert_eq!(layout.y_to_line(11.0, 100), 55);
+    assert_eq!(layout.y_to_line(500.0, 100), 99);
+
+    // everything fits, nothing moves
+    let layout = MinimapLayout::new(0.0, 2.0, 100.0, 10, 5.0, 5.0);
+    assert_eq!(layout.offset, 0.0);
+    assert_eq!(layout.line_to_y(3), 12.0);
+}
@@ -0,0 +1,59 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use ropey::RopeSlice;
+
+fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+// Chars of the word touching `x`, a caret right after a word is still on it
+pub fn word_at(line: RopeSlice, x: usize) -> Option<Range<usize>> {
+    let len = line.len_chars();
+    let x = x.min(len);
+
+    let mut start = x;
+    while start > 0 && is_word_char(line.char(start - 1)) {
+        start -= 1;
+    }
+    let mut end = x;
+    while end < len && is_word_char(line.char(end)) {
+        end += 1;
+    }
+
+    if start < end {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+// Char ranges of `needle` in `line`. With `whole_word` matches that are part
+// of a longer word are skipped.
+pub fn find_in_line(line: RopeSlice, needle: &str, whole_word: bool) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let haystack: Cow<str> = line.into();
+    let needle_chars = needle.chars().count();
+    let mut found = Vec::new();
+    let mut chars = 0;
+    let mut counted = 0;
+
+    for (byte, _) in haystack.match_indices(needle) {
+        chars += haystack[counted..byte].chars().count();
+        counted = byte;
+
+        if whole_word {
+            let before = haystack[..byte].chars().next_back();
+            let after = haystack[byte + needle.len()..].chars().next();
+            if before.map_or(false, is_word_char) || after.map_or(false, is_word_char) {
+                continue;
+            }
+        }
+        found.push(chars..chars + needle_chars);
+    }
+
+    found
+}
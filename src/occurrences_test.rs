@@ -0,0 +1,32 @@
+use ropey::Rope;
+
+use crate::occurrences::{find_in_line, word_at};
+
+#[test]
+fn finds_word_at_caret() {
+    let text = Rope::from_str("let foo_bar = 1;\n");
+    let line = text.line(0);
+
+    assert_eq!(word_at(line, 4), Some(4..11));
+    assert_eq!(word_at(line, 6), Some(4..11));
+    assert_eq!(word_at(line, 11), Some(4..11));
+    assert_eq!(word_at(line, 12), None);
+    assert_eq!(word_at(line, 3), Some(0..3));
+}
+
+#[test]
+fn finds_whole_words_only() {
+    let text = Rope::from_str("a ab a_b (a) ä a");
+    let line = text.line(0);
+
+    assert_eq!(find_in_line(line, "a", true), vec![0..1, 10..11, 15..16]);
+    assert_eq!(find_in_line(line, "a", false).len(), 5);
+}
+
+#[test]
+fn match_ranges_are_chars() {
+    let text = Rope::from_str("日本 x 日本");
+
+    assert_eq!(find_in_line(text.line(0), "日本", false), vec![0..2, 5..7]);
+    assert_eq!(find_in_line(text.line(0), "", false), vec![]);
+}
@@ -1,5 +1,8 @@
 use crate::app;
 use crate::editor_action::{dispatch_action, EditorAction};
+use crate::grapheme::visual_column;
+use crate::status_bar;
+use crate::text::Selection;
 
 use app::App;
 
@@ -7,11 +10,40 @@ use glfw::{Key, Modifiers};
 use std::collections::HashMap;
 
 pub fn process_char(app: &mut App, char: &char) {
+    if app.prompt.is_some() {
+        app::prompt_char(app, *char);
+        return;
+    }
+    if app.picker.is_some() {
+        return;
+    }
+    if let Some(hex) = &mut app.buffer.hex {
+        if let Some(digit) = char.to_digit(16) {
+            hex.set_nibble(digit as u8);
+            app.should_rerender = true;
+        }
+        return;
+    }
+    if app.buffer.overwrite {
+        select_next_char(app);
+    }
     let mut tmp = [0; 4];
-    app.text.insert_text(char.encode_utf8(&mut tmp));
+    app.buffer.text.insert_text(char.encode_utf8(&mut tmp));
     app.should_rerender = true;
 }
 
+// In overwrite mode the typed character replaces the one after the cursor,
+// line breaks stay where they are
+fn select_next_char(app: &mut App) {
+    let cursor = *app.buffer.text.get_cursor();
+    let text = app.buffer.text.get_text();
+    let idx = cursor.position.to_char(text);
+    if cursor.selection.is_some() || idx >= text.len_chars() || text.char(idx) == '\n' {
+        return;
+    }
+    app.buffer.text.move_cursor(1, Selection::Select);
+}
+
 #[derive(PartialEq, Eq, Hash)]
 pub struct KeyAction {
     pub key: Key,
@@ -41,6 +73,62 @@ fn default_shortcuts() -> Vec<(KeyAction, EditorAction)> {
             },
             EditorAction::Save,
         ),
+        (
+            KeyAction {
+                key: Key::S,
+                modifiers: Modifiers::Control | Modifiers::Shift,
+            },
+            EditorAction::SaveAs,
+        ),
+        (
+            KeyAction {
+                key: Key::N,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::NewFile,
+        ),
+        (
+            KeyAction {
+                key: Key::Tab,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::NextTab,
+        ),
+        (
+            KeyAction {
+                key: Key::PageDown,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::NextTab,
+        ),
+        (
+            KeyAction {
+                key: Key::Tab,
+                modifiers: Modifiers::Control | Modifiers::Shift,
+            },
+            EditorAction::PrevTab,
+        ),
+        (
+            KeyAction {
+                key: Key::PageUp,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::PrevTab,
+        ),
+        (
+            KeyAction {
+                key: Key::W,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::CloseTab,
+        ),
+        (
+            KeyAction {
+                key: Key::T,
+                modifiers: Modifiers::Control | Modifiers::Shift,
+            },
+            EditorAction::ReopenClosedTab,
+        ),
         (
             KeyAction {
                 key: Key::Backspace,
@@ -258,11 +346,102 @@ fn default_shortcuts() -> Vec<(KeyAction, EditorAction)> {
             },
             EditorAction::Cut,
         ),
+        (
+            KeyAction {
+                key: Key::Equal,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::ZoomIn,
+        ),
+        (
+            KeyAction {
+                key: Key::KpAdd,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::ZoomIn,
+        ),
+        (
+            KeyAction {
+                key: Key::Minus,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::ZoomOut,
+        ),
+        (
+            KeyAction {
+                key: Key::KpSubtract,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::ZoomOut,
+        ),
+        (
+            KeyAction {
+                key: Key::Num0,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::ZoomReset,
+        ),
+        (
+            KeyAction {
+                key: Key::Z,
+                modifiers: Modifiers::Alt,
+            },
+            EditorAction::ToggleSoftWrap,
+        ),
+        (
+            KeyAction {
+                key: Key::L,
+                modifiers: Modifiers::Alt,
+            },
+            EditorAction::ToggleLineEnding,
+        ),
+        (
+            KeyAction {
+                key: Key::LeftBracket,
+                modifiers: Modifiers::Control | Modifiers::Shift,
+            },
+            EditorAction::Fold,
+        ),
+        (
+            KeyAction {
+                key: Key::RightBracket,
+                modifiers: Modifiers::Control | Modifiers::Shift,
+            },
+            EditorAction::Unfold,
+        ),
+        (
+            KeyAction {
+                key: Key::H,
+                modifiers: Modifiers::Alt,
+            },
+            EditorAction::ToggleHexView,
+        ),
+    ];
+}
+
+// Only bound while the hex view is shown, the text view keeps these keys
+fn hex_shortcuts() -> Vec<(KeyAction, EditorAction)> {
+    return vec![
+        (
+            KeyAction {
+                key: Key::F,
+                modifiers: Modifiers::Control,
+            },
+            EditorAction::FindBytes,
+        ),
+        (
+            KeyAction {
+                key: Key::F3,
+                modifiers: Modifiers::empty(),
+            },
+            EditorAction::FindNext,
+        ),
     ];
 }
 
 pub struct KeyBoardShortcuts {
     shortcuts: HashMap<KeyAction, EditorAction>,
+    hex_shortcuts: HashMap<KeyAction, EditorAction>,
 }
 
 impl KeyBoardShortcuts {
@@ -270,27 +449,51 @@ impl KeyBoardShortcuts {
         let mut e = HashMap::new();
         e.extend(default_shortcuts().into_iter());
 
-        return KeyBoardShortcuts { shortcuts: e };
+        return KeyBoardShortcuts {
+            shortcuts: e,
+            hex_shortcuts: hex_shortcuts().into_iter().collect(),
+        };
     }
 
-    pub fn get_action(&self, key_action: &KeyAction) -> Option<EditorAction> {
-        let e = self.shortcuts.get(key_action);
+    pub fn get_action(&self, key_action: &KeyAction, hex: bool) -> Option<EditorAction> {
+        let e = match self.hex_shortcuts.get(key_action) {
+            Some(action) if hex => Some(action),
+            _ => self.shortcuts.get(key_action),
+        };
         return e.map(|x| *x);
     }
 }
 
 pub fn process_keyboard(app: &mut App, key: KeyAction) {
-    if let Some(action) = app.shortcuts.get_action(&key) {
+    if app.prompt.is_some() {
+        app::prompt_key(app, key.key);
+        return;
+    }
+    if app.picker.is_some() {
+        status_bar::picker_key(app, key.key);
+        return;
+    }
+
+    if let Some(action) = app.shortcuts.get_action(&key, app.buffer.hex.is_some()) {
         dispatch_action(app, action);
         return;
     }
+    if app.buffer.hex.is_some() {
+        return;
+    }
 
     match key.key {
         Key::Enter => {
-            app.text.insert_text("\n");
+            app.buffer.text.insert_text("\n");
         }
         Key::Tab => {
-            app.text.insert_text("\t");
+            let position = app.buffer.text.get_cursor().position;
+            let line = app.buffer.text.get_text().line(position.y as usize);
+            let indent = app.buffer.indent.text_at(visual_column(line, position.x as usize));
+            app.buffer.text.insert_text(&indent);
+        }
+        Key::Insert => {
+            app.buffer.overwrite = !app.buffer.overwrite;
         }
         _ => {}
     }
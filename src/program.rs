@@ -3,8 +3,12 @@ extern crate gl;
 extern crate glfw;
 use glfw::{Action, Context};
 
+use crate::caret;
+use crate::config::Config;
 use crate::render;
 use crate::scroll;
+use crate::status_bar;
+use crate::tab_bar;
 use crate::{app, process_keyboard::KeyAction};
 use crate::{app::visible_range_x, process_keyboard};
 use app::{projection_from_size, App};
@@ -12,30 +16,51 @@ use app::{projection_from_size, App};
 use std::{env, time::Instant};
 
 fn clamp_scroll(app: &mut App) {
-    let visible_range = app::visible_range(app, app.scroll.target_scroll.y);
-    let cursor_position = app.text.get_cursor().position;
-    if cursor_position.y > visible_range.end as i64 - 3 {
-        let target_scroll =
-            cursor_position.y + 3 - ((visible_range.end - visible_range.start) as i64);
-        scroll::scroll_to(&mut app.scroll, target_scroll as f32)
+    app::sync_display_map(app);
+    let visible_range = app::visible_range(app, app.buffer.scroll.target_scroll.y);
+    let cursor_position = app.buffer.text.get_cursor().position;
+    let cursor_row = app::cursor_row(app) as i64;
+    if cursor_row > visible_range.end as i64 - 3 {
+        let target_scroll = cursor_row + 3 - ((visible_range.end - visible_range.start) as i64);
+        scroll::scroll_to(&mut app.buffer.scroll, target_scroll as f32)
     }
 
-    if cursor_position.y < (visible_range.start + 1) as i64 {
-        let target_scroll = (cursor_position.y - 1).max(0);
-        scroll::scroll_to(&mut app.scroll, target_scroll as f32);
+    if cursor_row < (visible_range.start + 1) as i64 {
+        let target_scroll = (cursor_row - 1).max(0);
+        scroll::scroll_to(&mut app.buffer.scroll, target_scroll as f32);
     }
 
-    let range_x = visible_range_x(app, app.scroll.current_scroll.x);
+    // wrapped text and the hex view never need horizontal scrolling
+    if app.buffer.display_map.is_wrapping() || app.buffer.hex.is_some() {
+        return;
+    }
+
+    let range_x = visible_range_x(app, app.buffer.scroll.current_scroll.x);
 
     if cursor_position.x > range_x.end as i64 - 1 {
         let target_scroll = cursor_position.x + 1 - ((range_x.end - range_x.start) as i64);
-        scroll::scroll_to_x(&mut app.scroll, target_scroll as f32);
+        scroll::scroll_to_x(&mut app.buffer.scroll, target_scroll as f32);
     }
 
     if cursor_position.x < (range_x.start + 1) as i64 {
         let target_scroll = (cursor_position.x - 1).max(0);
-        scroll::scroll_to_x(&mut app.scroll, target_scroll as f32);
+        scroll::scroll_to_x(&mut app.buffer.scroll, target_scroll as f32);
+    }
+}
+
+// Cursor positions come in screen coordinates, which differ from framebuffer
+// pixels on HiDPI displays
+fn cursor_to_framebuffer(app: &App, x: f64, y: f64) -> (f32, f32) {
+    let (width, height) = app.window.get_size();
+    let (fb_width, fb_height) = app.window.get_framebuffer_size();
+    if width <= 0 || height <= 0 {
+        return (x as f32, y as f32);
     }
+
+    (
+        (x * fb_width as f64 / width as f64) as f32,
+        (y * fb_height as f64 / height as f64) as f32,
+    )
 }
 
 pub struct Program {
@@ -54,14 +79,20 @@ impl<'t> Program {
         ));
 
         glfw.window_hint(glfw::WindowHint::Samples(Some(4)));
+        glfw.window_hint(glfw::WindowHint::ScaleToMonitor(true));
 
         let (mut window, events) = glfw
-            .create_window(800, 600, "Hello this is window", glfw::WindowMode::Windowed)
+            .create_window(800, 600, "editor", glfw::WindowMode::Windowed)
             .expect("Failed to create GLFW window.");
 
         window.set_framebuffer_size_polling(true);
+        window.set_content_scale_polling(true);
         window.set_refresh_polling(true);
         window.set_char_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_focus_polling(true);
+        window.set_close_polling(true);
 
         window.make_current();
         window.set_key_polling(true);
@@ -72,18 +103,22 @@ impl<'t> Program {
 
 
         unsafe {
-            gl::ClearColor(30.0 / 255.0, 30.0 / 255.0, 30.0 / 255.0, 1.0);
             gl::Enable(gl::BLEND);
             gl::Enable(gl::MULTISAMPLE);
         }
 
-        let file_path = if env::args().count() > 1 {
-            env::args().last().unwrap()
-        } else {
-            "./text.txt".into()
-        };
+        let mut file_paths: Vec<String> = env::args().skip(1).collect();
+        if file_paths.is_empty() {
+            file_paths.push("./text.txt".into());
+        }
 
-        let app = App::new(window, glfw, 800, 600, file_path);
+        let (width, height) = window.get_framebuffer_size();
+        let app = App::new(window, glfw, width, height, file_paths, Config::load());
+
+        let [r, g, b] = app.config.theme.background;
+        unsafe {
+            gl::ClearColor(r, g, b, 1.0);
+        }
 
         Program { events, app }
     }
@@ -94,7 +129,15 @@ impl<'t> Program {
         let mut now = Instant::now();
 
         while !app.window.should_close() {
-            app.glfw.wait_events();
+            // blinking, fading and caret movement need frames without any
+            // events coming in
+            match app::next_frame_in(&app) {
+                Some(timeout) => {
+                    app.glfw.wait_events_timeout(timeout as f64);
+                    app.should_rerender = true;
+                }
+                None => app.glfw.wait_events(),
+            }
 
             let dt = now.elapsed().as_secs_f32();
             now = Instant::now();
@@ -103,13 +146,25 @@ impl<'t> Program {
                 Program::process_event(&mut app, &event);
             }
 
-            if scroll::advance_scroll(&mut app.scroll, dt) {
+            if scroll::advance_scroll(&mut app.buffer.scroll, dt) {
+                app.scrollbar.wake();
                 app.glfw.post_empty_event();
                 app.should_rerender = true
             }
 
+            if caret::advance_caret(&mut app.caret, dt) {
+                app.should_rerender = true;
+            }
+
+            if app.buffer.watcher.has_changes() {
+                app::check_disk(&mut app);
+            }
+
+            app::autosave(&mut app);
+            app::update_title(&mut app);
             render::render_app(&mut app);
         }
+        app::close(&mut app);
     }
 
     pub fn process_event(app: &mut App, event: &glfw::WindowEvent) {
@@ -121,24 +176,73 @@ impl<'t> Program {
                 }
                 app.should_rerender = true;
             }
+            glfw::WindowEvent::ContentScale(x_scale, _) => {
+                app::set_content_scale(app, *x_scale);
+                clamp_scroll(app);
+            }
             glfw::WindowEvent::Refresh => {
                 app.should_rerender = true;
             }
+            glfw::WindowEvent::Close => {
+                app::request_close(app);
+            }
+            glfw::WindowEvent::Focus(focused) => {
+                if !*focused && app.config.autosave_on_focus_loss {
+                    app::save_on_focus_loss(app);
+                }
+                caret::reset_blink(&mut app.caret);
+                app.should_rerender = true;
+            }
             glfw::WindowEvent::Key(key, _scancode, action, modifiers) => {
                 if *action == Action::Press || *action == Action::Repeat {
                     let key = KeyAction {
                         key: *key,
                         modifiers: *modifiers,
                     };
+                    caret::reset_blink(&mut app.caret);
                     process_keyboard::process_keyboard(app, key);
                     clamp_scroll(app);
                     app.should_rerender = true;
                 }
             }
             glfw::WindowEvent::Char(char) => {
+                caret::reset_blink(&mut app.caret);
                 process_keyboard::process_char(app, char);
                 clamp_scroll(app);
             }
+            glfw::WindowEvent::MouseButton(glfw::MouseButtonLeft, action, _) => {
+                app.minimap_drag = false;
+                app.scrollbar.drag = None;
+                if *action == Action::Press {
+                    let (x, y) = app.window.get_cursor_pos();
+                    let (x, y) = cursor_to_framebuffer(app, x, y);
+                    if tab_bar::click(app, x, y) {
+                        return;
+                    }
+                    let y = y - app::tab_bar_height(app);
+                    if app::click_prompt(app, x, y) || status_bar::click(app, x, y) {
+                        return;
+                    }
+                    if app::is_on_scrollbar(app, x) {
+                        app::press_scrollbar(app, y);
+                    } else if app::is_on_minimap(app, x) {
+                        app.minimap_drag = true;
+                        app::scroll_to_minimap(app, y);
+                    }
+                }
+            }
+            glfw::WindowEvent::CursorPos(x, y) => {
+                let (x, y) = cursor_to_framebuffer(app, *x, *y);
+                let y = y - app::tab_bar_height(app);
+                if app.scrollbar.drag.is_some() {
+                    app::drag_scrollbar(app, y);
+                } else if app.minimap_drag {
+                    app::scroll_to_minimap(app, y);
+                } else if app.config.scrollbar_fade && app::is_on_scrollbar(app, x) {
+                    app.scrollbar.wake();
+                    app.should_rerender = true;
+                }
+            }
             _ => {}
         }
     }
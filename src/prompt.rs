@@ -0,0 +1,146 @@
+use std::ops::Range;
+
+use glfw::Key;
+
+use crate::diff::DiffLine;
+use crate::encoding::FileEncoding;
+use crate::status_bar::text_width;
+
+// What a prompt asks, decides what its answer does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Question {
+    // only tells something, answered by dismissing it
+    Message,
+    CloseUnsaved,
+    ReopenWithEncoding(FileEncoding),
+    FindBytes,
+    CloseTabUnsaved,
+    SaveAs,
+    ReplaceFile,
+    // the file goes in a directory that doesn't exist yet
+    CreateDirectory,
+    // the file changed on disk while the buffer has changes of its own
+    ChangedOnDisk,
+    DiskDiff,
+    OverwriteChanged,
+    // a recovery file was found on start
+    Recover,
+    RecoveryDiff,
+}
+
+// Lines Page Up and Page Down scroll by
+const PAGE_LINES: usize = 20;
+
+// Done once a save that had to ask something first is written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AfterSave {
+    CloseWindow,
+    CloseTab,
+}
+
+// A message with a row of choices, the last one backs out
+pub struct Prompt {
+    pub question: Question,
+    pub message: String,
+    pub choices: Vec<&'static str>,
+    pub selected: usize,
+    // text typed after the message, None when the prompt takes no text
+    pub input: Option<String>,
+    // file a save waits on the answer to write
+    pub path: Option<String>,
+    pub after_save: Option<AfterSave>,
+    // shown above the prompt, scrolled by the up and down arrows
+    pub lines: Vec<DiffLine>,
+    // first of `lines` shown
+    pub scroll: usize,
+}
+
+impl Prompt {
+    pub fn new(question: Question, message: String, choices: Vec<&'static str>) -> Prompt {
+        Prompt {
+            question,
+            message,
+            choices,
+            selected: 0,
+            input: None,
+            path: None,
+            after_save: None,
+            lines: Vec::new(),
+            scroll: 0,
+        }
+    }
+
+    // Typed characters go into the input instead of picking choices
+    pub fn with_input(question: Question, message: String, choices: Vec<&'static str>) -> Prompt {
+        Prompt {
+            input: Some(String::new()),
+            ..Prompt::new(question, message, choices)
+        }
+    }
+
+    // Message followed by the input
+    pub fn text(&self) -> String {
+        match &self.input {
+            Some(input) => format!("{} {}", self.message, input),
+            None => self.message.clone(),
+        }
+    }
+
+    pub fn cancel(&self) -> usize {
+        self.choices.len() - 1
+    }
+
+    // The choice `key` answers with, arrows only move the selection or scroll
+    // the lines
+    pub fn key(&mut self, key: Key) -> Option<usize> {
+        let count = self.choices.len();
+        let last_line = self.lines.len().saturating_sub(1);
+        match key {
+            Key::Up if !self.lines.is_empty() => self.scroll = self.scroll.saturating_sub(1),
+            Key::Down if !self.lines.is_empty() => self.scroll = (self.scroll + 1).min(last_line),
+            Key::PageUp if !self.lines.is_empty() => {
+                self.scroll = self.scroll.saturating_sub(PAGE_LINES)
+            }
+            Key::PageDown if !self.lines.is_empty() => {
+                self.scroll = (self.scroll + PAGE_LINES).min(last_line)
+            }
+            Key::Left | Key::Up => self.selected = (self.selected + count - 1) % count,
+            Key::Right | Key::Down | Key::Tab => self.selected = (self.selected + 1) % count,
+            Key::Enter | Key::KpEnter => return Some(self.selected),
+            Key::Escape => return Some(self.cancel()),
+            Key::Backspace => {
+                if let Some(input) = &mut self.input {
+                    input.pop();
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // Choices are also picked by typing their first letter
+    pub fn char(&mut self, c: char) -> Option<usize> {
+        if let Some(input) = &mut self.input {
+            input.push(c);
+            return None;
+        }
+        let c = c.to_lowercase().next()?;
+        self.choices
+            .iter()
+            .position(|choice| choice.to_lowercase().starts_with(c))
+    }
+
+    // Screen x ranges of the choices, they follow the text on one row
+    pub fn choice_ranges(&self, char_width: f32) -> Vec<Range<f32>> {
+        let mut x = (text_width(&self.text()) + 3) as f32 * char_width;
+        self.choices
+            .iter()
+            .map(|choice| {
+                let width = (text_width(choice) + 2) as f32 * char_width;
+                let range = x..x + width;
+                x += width + char_width;
+                range
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,83 @@
+use glfw::Key;
+
+use crate::diff::diff_lines;
+use crate::prompt::{Prompt, Question};
+
+fn close_prompt() -> Prompt {
+    Prompt::new(
+        Question::CloseUnsaved,
+        "Save changes?".to_owned(),
+        vec!["Save", "Discard", "Cancel"],
+    )
+}
+
+#[test]
+fn arrows_move_the_selection() {
+    let mut prompt = close_prompt();
+
+    assert_eq!(prompt.key(Key::Right), None);
+    assert_eq!(prompt.selected, 1);
+    assert_eq!(prompt.key(Key::Left), None);
+    assert_eq!(prompt.key(Key::Left), None);
+    assert_eq!(prompt.selected, 2);
+    assert_eq!(prompt.key(Key::Enter), Some(2));
+}
+
+#[test]
+fn escape_cancels() {
+    let mut prompt = close_prompt();
+
+    assert_eq!(prompt.key(Key::Escape), Some(2));
+    assert_eq!(prompt.key(Key::A), None);
+}
+
+#[test]
+fn first_letter_picks_a_choice() {
+    let mut prompt = close_prompt();
+
+    assert_eq!(prompt.char('d'), Some(1));
+    assert_eq!(prompt.char('S'), Some(0));
+    assert_eq!(prompt.char('x'), None);
+}
+
+#[test]
+fn choices_follow_the_message() {
+    let prompt = Prompt::new(
+        Question::CloseUnsaved,
+        "Save?".to_owned(),
+        vec!["Yes", "No"],
+    );
+
+    assert_eq!(prompt.choice_ranges(10.0), vec![80.0..130.0, 140.0..180.0]);
+}
+
+#[test]
+fn typing_goes_into_the_input() {
+    let mut prompt = Prompt::with_input(
+        Question::FindBytes,
+        "Find:".to_owned(),
+        vec!["Find", "Cancel"],
+    );
+
+    assert_eq!(prompt.char('f'), None);
+    assert_eq!(prompt.char('0'), None);
+    assert_eq!(prompt.char('1'), None);
+    assert_eq!(prompt.key(Key::Backspace), None);
+    assert_eq!(prompt.input, Some("f0".to_owned()));
+    assert_eq!(prompt.text(), "Find: f0");
+    assert_eq!(prompt.choice_ranges(10.0)[0], 110.0..170.0);
+}
+
+#[test]
+fn arrows_scroll_the_lines() {
+    let mut prompt = Prompt::new(Question::DiskDiff, "Diff".to_owned(), vec!["Back"]);
+    prompt.lines = diff_lines("a\n", "b\n");
+
+    assert_eq!(prompt.key(Key::Down), None);
+    assert_eq!(prompt.key(Key::Down), None);
+    assert_eq!(prompt.key(Key::Down), None);
+    assert_eq!(prompt.scroll, 2);
+    assert_eq!(prompt.key(Key::PageUp), None);
+    assert_eq!(prompt.scroll, 0);
+    assert_eq!(prompt.key(Key::Enter), Some(0));
+}
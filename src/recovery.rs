@@ -0,0 +1,156 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+// Changes are written once typing stops for this long
+const IDLE: Duration = Duration::from_secs(2);
+
+pub fn recovery_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+
+    Some(base.join("editor").join("recovery"))
+}
+
+// The whole absolute path goes into the name so files with the same name in
+// different directories don't share one
+pub fn recovery_file_name(path: &Path) -> String {
+    let absolute = match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+    absolute
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('/', "%2F")
+        .replace('\\', "%5C")
+}
+
+// Untitled buffers go by an id instead, the process id keeps editors running
+// side by side apart
+pub fn untitled_recovery_file_name(id: u64) -> String {
+    format!("untitled-{}-{}", process::id(), id)
+}
+
+// Process id in the name of an untitled buffer's recovery file
+pub fn untitled_pid(name: &str) -> Option<u32> {
+    let mut parts = name.strip_prefix("untitled-")?.split('-');
+    let pid = parts.next()?.parse().ok()?;
+    parts.next()?.parse::<u64>().ok()?;
+    match parts.next() {
+        Some(_) => None,
+        None => Some(pid),
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // signal 0 only looks for the process, which may belong to someone else
+    let found = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    found || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// Without a way to tell, the files are left to the editor that wrote them
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+// Recovery files of untitled buffers from editors that are gone. Called before
+// this one writes any, so its own process id is from an earlier editor too.
+pub fn leftover_untitled_files() -> Vec<PathBuf> {
+    let entries = match recovery_dir().map(fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return Vec::new(),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let pid = path
+                .file_name()
+                .and_then(|name| untitled_pid(&name.to_string_lossy()));
+            match pid {
+                Some(pid) => pid == process::id() || !is_running(pid),
+                None => false,
+            }
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+// Decides when unsaved changes go to the recovery file, after a short idle
+// time or every `interval` while they keep coming
+pub struct Autosave {
+    interval: Duration,
+    // last revision seen and when it was first seen
+    seen: Option<(u64, Instant)>,
+    // first change the recovery file doesn't have yet
+    pending_since: Option<Instant>,
+    // revision in the recovery file
+    written: Option<u64>,
+    // there is a recovery file for this buffer that can be removed on exit
+    pub has_file: bool,
+}
+
+impl Autosave {
+    pub fn new(interval: Duration) -> Autosave {
+        Autosave {
+            interval,
+            seen: None,
+            pending_since: None,
+            written: None,
+            has_file: false,
+        }
+    }
+
+    // Revisions start over, whatever is in the recovery file is out of date
+    pub fn reset(&mut self) {
+        self.seen = None;
+        self.pending_since = None;
+        self.written = None;
+    }
+
+    fn due_at(&self) -> Option<Instant> {
+        let (_, changed_at) = self.seen?;
+        let pending_since = self.pending_since?;
+        Some((changed_at + IDLE).min(pending_since + self.interval))
+    }
+
+    // Notes the text at `revision`, true when it should be written now
+    pub fn is_due(&mut self, revision: u64, now: Instant) -> bool {
+        if self.written == Some(revision) {
+            return false;
+        }
+        if self.seen.map(|(seen, _)| seen) != Some(revision) {
+            self.seen = Some((revision, now));
+            self.pending_since.get_or_insert(now);
+        }
+        self.due_at().map_or(false, |due_at| now >= due_at)
+    }
+
+    // Seconds until the next write, None when nothing is waiting
+    pub fn next_in(&self, now: Instant) -> Option<f32> {
+        let due_at = self.due_at()?;
+        Some(due_at.saturating_duration_since(now).as_secs_f32())
+    }
+
+    pub fn mark_written(&mut self, revision: u64) {
+        self.written = Some(revision);
+        self.pending_since = None;
+        self.has_file = true;
+    }
+
+    pub fn mark_removed(&mut self) {
+        self.reset();
+        self.has_file = false;
+    }
+}
@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::recovery::{recovery_file_name, untitled_pid, untitled_recovery_file_name, Autosave};
+
+fn seconds(seconds: u64) -> Duration {
+    Duration::from_secs(seconds)
+}
+
+#[test]
+fn writes_after_idle_time() {
+    let start = Instant::now();
+    let mut autosave = Autosave::new(seconds(30));
+
+    assert!(!autosave.is_due(1, start));
+    assert!(!autosave.is_due(1, start + seconds(1)));
+    assert_eq!(autosave.next_in(start + seconds(1)), Some(1.0));
+    assert!(autosave.is_due(1, start + seconds(2)));
+
+    autosave.mark_written(1);
+    assert!(!autosave.is_due(1, start + seconds(10)));
+    assert_eq!(autosave.next_in(start + seconds(10)), None);
+    assert!(autosave.has_file);
+}
+
+#[test]
+fn writes_periodically_while_typing() {
+    let start = Instant::now();
+    let mut autosave = Autosave::new(seconds(5));
+
+    for second in 0..5 {
+        assert!(!autosave.is_due(second, start + seconds(second)));
+    }
+    assert!(autosave.is_due(5, start + seconds(5)));
+
+    autosave.mark_written(5);
+    assert!(!autosave.is_due(6, start + seconds(6)));
+    assert!(autosave.is_due(6, start + seconds(8)));
+}
+
+#[test]
+fn reset_forgets_the_written_revision() {
+    let start = Instant::now();
+    let mut autosave = Autosave::new(seconds(30));
+    autosave.mark_written(0);
+
+    autosave.reset();
+
+    assert!(!autosave.is_due(0, start));
+    assert!(autosave.is_due(0, start + seconds(2)));
+
+    autosave.mark_removed();
+    assert!(!autosave.has_file);
+}
+
+#[test]
+fn names_keep_the_whole_path() {
+    assert_eq!(
+        recovery_file_name(Path::new("/home/me/100%/a.rs")),
+        "%2Fhome%2Fme%2F100%25%2Fa.rs"
+    );
+    assert_ne!(
+        recovery_file_name(Path::new("/a/b.rs")),
+        recovery_file_name(Path::new("/c/b.rs"))
+    );
+}
+
+#[test]
+fn untitled_names_differ_by_id() {
+    assert_ne!(
+        untitled_recovery_file_name(0),
+        untitled_recovery_file_name(1)
+    );
+    assert_ne!(
+        untitled_recovery_file_name(0),
+        recovery_file_name(Path::new("untitled"))
+    );
+}
+
+#[test]
+fn untitled_names_tell_the_process() {
+    let name = untitled_recovery_file_name(3);
+
+    assert_eq!(untitled_pid(&name), Some(std::process::id()));
+    assert_eq!(untitled_pid("untitled-12-0"), Some(12));
+    assert_eq!(untitled_pid(".untitled-12-0.1.0.tmp"), None);
+    assert_eq!(untitled_pid("untitled-12"), None);
+    assert_eq!(
+        untitled_pid(&recovery_file_name(Path::new("/untitled-1-2"))),
+        None
+    );
+}
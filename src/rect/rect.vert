@@ -1,7 +1,7 @@
 #version 330 core
 layout (location = 0) in vec2 pos;
 layout (location = 1) in vec2 dim;
-layout (location = 2) in vec3 color;
+layout (location = 2) in vec4 color;
 
 out vec4 vertexColor;
 
@@ -18,5 +18,5 @@ void main()
     vec2 position = pos + dim * v;
 
     gl_Position = projection * vec4(position.xy, 0.0, 1.0);
-    vertexColor = vec4(color.xyz, 1.0);
+    vertexColor = color;
 }
\ No newline at end of file
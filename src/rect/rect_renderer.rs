@@ -9,14 +9,25 @@ use crate::offset_of;
 pub struct RectInstance {
     pos: [f32; 2],
     dimensions: [f32; 2],
-    color: [f32; 3],
+    color: [f32; 4],
 }
 
 pub fn create_rect(x: f32, y: f32, width: f32, height: f32, color: [f32; 3]) -> RectInstance {
+    create_rect_alpha(x, y, width, height, color, 1.0)
+}
+
+pub fn create_rect_alpha(
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    color: [f32; 3],
+    alpha: f32,
+) -> RectInstance {
     RectInstance {
         pos: [x, y],
         dimensions: [width, height],
-        color: color,
+        color: [color[0], color[1], color[2], alpha],
     }
 }
 
@@ -98,7 +109,7 @@ impl RectRenderer {
             gl::EnableVertexAttribArray(2);
             gl::VertexAttribPointer(
                 2,
-                3,
+                4,
                 gl::FLOAT,
                 gl::FALSE,
                 std::mem::size_of::<RectInstance>() as gl::types::GLint,
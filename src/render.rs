@@ -1,38 +1,91 @@
 use crate::app;
+use crate::caret::{blink_on, jump_caret, move_caret};
+use crate::config::CursorStyle;
 use crate::cursor::Point;
+use crate::diff::Change;
+use crate::display_map::DisplayLine;
+use crate::folding::{is_foldable, FoldMarker};
+use crate::font::font_renderer::Label;
+use crate::grapheme::next_grapheme_boundary;
+use crate::hex::{self, BYTES_PER_ROW};
 use crate::matrix;
-use crate::rect::rect_renderer::create_rect;
+use crate::occurrences::{find_in_line, word_at};
+use crate::rect::rect_renderer::{create_rect, create_rect_alpha};
+use crate::scroll::PointF;
+use crate::scrollbar::{marker_y, opacity, MARKER_HEIGHT};
+use crate::status_bar;
+use crate::tab_bar;
 use crate::timer;
+use crate::whitespace::{indent_guides, trailing_whitespace};
 
 use crate::glfw::Context;
 
 use app::App;
 use matrix::Matrix;
 
-fn x_to_screen(app: &App, x: i64) -> f32 {
-    let char_width = app.font_renderer.char_width;
-    x as f32 * char_width
+const CURSOR_WIDTH: f32 = 2.0;
+const UNDERLINE_HEIGHT: f32 = 2.0;
+// width of indent guides and rulers
+const GUIDE_WIDTH: f32 = 1.0;
+
+// Screen x of the caret at `pos`, measured from the start of its row
+fn x_to_screen(app: &mut App, pos: Point) -> f32 {
+    let left = app::text_left(app);
+    let text = app.buffer.text.get_text();
+    if pos.y as usize >= text.len_lines() {
+        return left;
+    }
+    let row = app
+        .buffer
+        .display_map
+        .display_line(text, app.buffer.display_map.point_to_row(pos));
+    x_in_row(app, row, pos.x as usize)
 }
 
-fn y_to_screen(app: &App, y: i64) -> f32 {
-    let height = app.font_renderer.advance_height;
-    ((y as f32 - app.scroll.current_scroll.y + 2.0) as f32 * height) - app.font_renderer.ascender
+// Screen x of char `x` of the line, clamped to `row`
+fn x_in_row(app: &mut App, row: DisplayLine, x: usize) -> f32 {
+    let left = app::text_left(app);
+    let line = app
+        .buffer
+        .text
+        .get_text()
+        .line(row.line)
+        .slice(row.start..row.end);
+    let x = x.max(row.start).min(row.end) - row.start;
+    left + app.font_renderer.caret_x(line, x)
+}
+
+// Selected rows that continue on the next one also cover their line break
+fn row_end_to_screen(app: &mut App, row: DisplayLine) -> f32 {
+    let left = app::text_left(app);
+    let text = app.buffer.text.get_text();
+    let ends_line = row.end == text.line(row.line).len_chars();
+    let line = text.line(row.line).slice(row.start..row.end);
+    let x = left + app.font_renderer.caret_x(line, row.end - row.start);
+
+    if ends_line {
+        x + app.font_renderer.char_width
+    } else {
+        x
+    }
 }
 
-fn grid_to_screen(app: &mut App, pos: Point) -> (f32, f32) {
-    (x_to_screen(app, pos.x), y_to_screen(app, pos.y))
+fn y_to_screen(app: &App, row: usize) -> f32 {
+    let height = app.font_renderer.advance_height;
+    ((row as f32 - app.buffer.scroll.current_scroll.y + 2.0) as f32 * height)
+        - app.font_renderer.ascender
 }
 
 fn render_selection(app: &mut App, projection: &Matrix, range: std::ops::Range<usize>) {
     let height = app.font_renderer.advance_height;
 
-    if app.text.get_cursor().selection.is_none() {
+    if app.buffer.text.get_cursor().selection.is_none() {
         return;
     }
 
-    let selection = app.text.get_cursor().selection.unwrap();
+    let selection = app.buffer.text.get_cursor().selection.unwrap();
 
-    let mut pos = [app.text.get_cursor().position, selection];
+    let mut pos = [app.buffer.text.get_cursor().position, selection];
 
     pos.sort_by(|a, b| {
         if a.y == b.y {
@@ -42,86 +95,705 @@ fn render_selection(app: &mut App, projection: &Matrix, range: std::ops::Range<u
         }
     });
 
-    let mut start = pos[0];
-    let mut end = pos[1];
-
-    start.y = start.y.max(range.start as i64);
-    end.y = end.y.max(start.y).min(range.end as i64);
+    let start = pos[0];
+    let end = pos[1];
+    let start_row = app.buffer.display_map.point_to_row(start);
+    let end_row = app.buffer.display_map.point_to_row(end);
 
     let mut v = Vec::new();
 
-    let start_screen = grid_to_screen(app, start);
-    let screen_end = grid_to_screen(app, end);
-    if start.y == end.y {
+    for row in start_row.max(range.start)..=end_row.min(range.end) {
+        let display_line = app
+            .buffer
+            .display_map
+            .display_line(app.buffer.text.get_text(), row);
+
+        let x = if row == start_row {
+            x_to_screen(app, start)
+        } else {
+            app::text_left(app)
+        };
+        let x_end = if row == end_row {
+            x_to_screen(app, end)
+        } else {
+            row_end_to_screen(app, display_line)
+        };
+
         v.push(create_rect(
-            start_screen.0,
-            start_screen.1,
-            screen_end.0 - start_screen.0,
+            x,
+            y_to_screen(app, row),
+            x_end - x,
             height,
-            [0.5, 0.5, 0.5],
+            app.config.theme.selection,
         ));
+    }
+
+    app.rect_renderer.render(&v, &projection);
+}
+
+fn fold_markers(app: &App, rows: &[DisplayLine]) -> Vec<FoldMarker> {
+    let text = app.buffer.text.get_text();
+    rows.iter()
+        .map(|row| {
+            if row.is_continuation() {
+                FoldMarker::None
+            } else if app.buffer.folds.is_folded(row.line) {
+                FoldMarker::Folded
+            } else if is_foldable(text, row.line) {
+                FoldMarker::Open
+            } else {
+                FoldMarker::None
+            }
+        })
+        .collect()
+}
+
+// Drawn last so it covers long lines running under it
+fn render_minimap(app: &mut App, mvp: &Matrix, range: std::ops::Range<usize>) {
+    if !app.config.minimap {
+        return;
+    }
+
+    let height = app::text_height(app);
+    let layout = app::minimap_layout(app, app.buffer.scroll.current_scroll.y);
+    let text = app.buffer.text.get_text();
+
+    let mut v = vec![create_rect(
+        layout.x,
+        0.0,
+        layout.width(),
+        height,
+        app.config.theme.minimap_background,
+    )];
+
+    let first = app.buffer.display_map.display_line(text, range.start).line;
+    let last = app
+        .buffer
+        .display_map
+        .display_line(text, range.end.max(range.start + 1) - 1)
+        .line;
+    let y = layout.line_to_y(first);
+    v.push(create_rect(
+        layout.x,
+        y,
+        layout.width(),
+        layout.line_to_y(last + 1) - y,
+        app.config.theme.minimap_viewport,
+    ));
+
+    for line in layout.visible_lines(height, app.buffer.minimap.line_count()) {
+        let y = layout.line_to_y(line);
+        for run in app.buffer.minimap.runs(line) {
+            v.push(create_rect(
+                layout.x + run.start as f32 * layout.char_width,
+                y,
+                run.len as f32 * layout.char_width,
+                layout.block_height,
+                app.config.theme.minimap_text,
+            ));
+        }
+    }
+
+    app.rect_renderer.render(&v, &mvp);
+}
+
+// Lines the selection covers, they get a tick on the scrollbar
+fn selected_lines(app: &mut App) -> std::ops::Range<usize> {
+    let cursor = app.buffer.text.get_cursor();
+    let selection = match cursor.selection {
+        Some(selection) => selection,
+        None => return 0..0,
+    };
+    let (start, end) = if selection.y < cursor.position.y {
+        (selection.y, cursor.position.y)
     } else {
-        v.push(create_rect(
-            start_screen.0,
-            start_screen.1,
-            x_to_screen(
-                app,
-                app.text.get_text().line(start.y as usize).len_chars() as i64 - start.x,
-            ),
-            height,
-            [0.5, 0.5, 0.5],
+        (cursor.position.y, selection.y)
+    };
+
+    start as usize..end as usize + 1
+}
+
+fn render_scrollbar(app: &mut App, mvp: &Matrix) {
+    if !app.config.scrollbar {
+        return;
+    }
+    let alpha = if app.config.scrollbar_fade {
+        opacity(app.scrollbar.idle())
+    } else {
+        1.0
+    };
+    if alpha <= 0.0 {
+        return;
+    }
+
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let height = app::text_height(app);
+    let width = app::scrollbar_width(app);
+    let x = x_size as f32 - width;
+    let (thumb_y, thumb_height) = app::scrollbar_thumb(app);
+    let theme = app.config.theme;
+
+    let mut v = vec![
+        create_rect_alpha(x, 0.0, width, height, theme.scrollbar_track, alpha),
+        create_rect_alpha(
+            x,
+            thumb_y,
+            width,
+            thumb_height,
+            theme.scrollbar_thumb,
+            alpha,
+        ),
+    ];
+
+    let marker_height = app::scaled(app, MARKER_HEIGHT);
+    let row_count = app.buffer.display_map.row_count(app.buffer.text.get_text());
+    // a big selection would otherwise stack thousands of ticks on each other
+    let mut last = None;
+    for line in selected_lines(app) {
+        let row = app.buffer.display_map.line_to_row(line);
+        let y = marker_y(height - marker_height, row, row_count);
+        if last == Some(y as i32) {
+            continue;
+        }
+        last = Some(y as i32);
+
+        v.push(create_rect_alpha(
+            x,
+            y,
+            width,
+            marker_height,
+            theme.marker_selection,
+            alpha,
         ));
+    }
+
+    app.rect_renderer.render(&v, &mvp);
+}
+
+// Rulers, indent guides and trailing whitespace, all under the text
+fn render_decorations(app: &mut App, mvp: &Matrix, rows: &[DisplayLine]) {
+    let text_height = app::text_height(app);
+    let left = app::text_left(app);
+    let char_width = app.font_renderer.char_width;
+    let height = app.font_renderer.advance_height;
+    let line_width = app::scaled(app, GUIDE_WIDTH);
+    let theme = app.config.theme;
+
+    let mut v = Vec::new();
+
+    for &column in app.config.rulers.iter() {
+        let x = left + column as f32 * char_width;
+        v.push(create_rect(x, 0.0, line_width, text_height, theme.ruler));
+    }
+
+    let first_row = app.buffer.scroll.current_scroll.y as usize;
+    for (index, &row) in rows.iter().enumerate() {
+        let y = y_to_screen(app, first_row + index);
+
+        if app.config.indent_guides {
+            for column in indent_guides(app.buffer.text.get_text(), row.line) {
+                let x = left + column as f32 * char_width;
+                v.push(create_rect(x, y, line_width, height, theme.indent_guide));
+            }
+        }
+
+        if app.config.trailing_whitespace {
+            let trailing = trailing_whitespace(app.buffer.text.get_text().line(row.line));
+            if let Some(trailing) = trailing {
+                let start = trailing.start.max(row.start);
+                let end = trailing.end.min(row.end);
+                if start < end {
+                    let x = x_in_row(app, row, start);
+                    let x_end = x_in_row(app, row, end);
+                    v.push(create_rect(
+                        x,
+                        y,
+                        x_end - x,
+                        height,
+                        theme.trailing_whitespace,
+                    ));
+                }
+            }
+        }
+    }
+
+    if !v.is_empty() {
+        app.rect_renderer.render(&v, &mvp);
+    }
+}
+
+// What to look for: the selection if it is on one line, otherwise the word
+// under the cursor. The cursor's own match is left out.
+fn occurrence_needle(app: &mut App) -> Option<(String, bool, usize, std::ops::Range<usize>)> {
+    let cursor = app.buffer.text.get_cursor();
+    let position = cursor.position;
+    let line = position.y as usize;
 
-        for (i, l) in app
+    if let Some(selection) = cursor.selection {
+        if selection.y != position.y || selection.x == position.x {
+            return None;
+        }
+        let start = selection.x.min(position.x) as usize;
+        let end = selection.x.max(position.x) as usize;
+        let needle = app
+            .buffer
             .text
             .get_text()
-            .lines_at(start.y as usize + 1)
-            .take((end.y - start.y) as usize - 1)
-            .enumerate()
-        {
-            let line = i as i64 + start.y + 1;
+            .line(line)
+            .slice(start..end)
+            .to_string();
+        if needle.trim().is_empty() {
+            return None;
+        }
+        return Some((needle, false, line, start..end));
+    }
 
+    let text_line = app.buffer.text.get_text().line(line);
+    let word = word_at(text_line, position.x as usize)?;
+    let needle = text_line.slice(word.clone()).to_string();
+    Some((needle, true, line, word))
+}
+
+// Current line and the occurrences, only visible lines are searched
+fn render_line_highlights(app: &mut App, mvp: &Matrix, rows: &[DisplayLine]) {
+    let height = app.font_renderer.advance_height;
+    let theme = app.config.theme;
+    let first_row = app.buffer.scroll.current_scroll.y as usize;
+    let cursor_line = app.buffer.text.get_cursor().position.y as usize;
+    let needle = if app.config.highlight_occurrences {
+        occurrence_needle(app)
+    } else {
+        None
+    };
+
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let left = app::text_left(app);
+    let right = x_size as f32 - app::minimap_width(app) - app::scrollbar_width(app);
+
+    let mut v = Vec::new();
+    let mut searched_line = None;
+    let mut found = Vec::new();
+
+    for (index, &row) in rows.iter().enumerate() {
+        let y = y_to_screen(app, first_row + index);
+
+        if app.config.highlight_current_line && row.line == cursor_line {
             v.push(create_rect(
-                0.0,
-                y_to_screen(app, line),
-                x_to_screen(app, l.len_chars() as i64),
+                left,
+                y,
+                right - left,
                 height,
-                [0.5, 0.5, 0.5],
+                theme.current_line,
             ));
         }
 
-        let end_screen = grid_to_screen(app, end);
+        let (needle, whole_word, own_line, own) = match &needle {
+            Some(needle) => needle,
+            None => continue,
+        };
+        // wrapped lines have several rows, search them once
+        if searched_line != Some(row.line) {
+            let line = app.buffer.text.get_text().line(row.line);
+            found = find_in_line(line, needle, *whole_word);
+            searched_line = Some(row.line);
+        }
 
+        for range in found.iter() {
+            if row.line == *own_line && range == own {
+                continue;
+            }
+            let start = range.start.max(row.start);
+            let end = range.end.min(row.end);
+            if start >= end {
+                continue;
+            }
+            let x = x_in_row(app, row, start);
+            let x_end = x_in_row(app, row, end);
+            v.push(create_rect(x, y, x_end - x, height, theme.occurrence));
+        }
+    }
+
+    if !v.is_empty() {
+        app.rect_renderer.render(&v, &mvp);
+    }
+}
+
+fn render_cursor(app: &mut App, mvp: &Matrix, range: std::ops::Range<usize>) {
+    let focused = app.window.is_focused();
+    if focused && app.config.cursor_blink && !blink_on(app.caret.idle()) {
+        return;
+    }
+
+    let height = app.font_renderer.advance_height;
+    let char_width = app.font_renderer.char_width;
+    let theme = app.config.theme;
+    let left = app::text_left(app);
+
+    let position = app.buffer.text.get_cursor().position;
+    let cursor_row = app::cursor_row(app);
+    let row = app
+        .buffer
+        .display_map
+        .display_line(app.buffer.text.get_text(), cursor_row);
+    let x = x_to_screen(app, position);
+
+    // the cluster under the cursor decides how wide block and underline are
+    let text = app.buffer.text.get_text();
+    let line_start = text.line_to_char(position.y as usize);
+    let char_idx = (line_start + position.x as usize).min(text.len_chars());
+    let next = next_grapheme_boundary(text, char_idx);
+    let cluster = text.slice(char_idx..next).to_string();
+    let width = if cluster.is_empty() || cluster.ends_with('\n') {
+        char_width
+    } else {
+        let next_x = position.x as usize + next - char_idx;
+        (x_in_row(app, row, next_x) - x).max(1.0)
+    };
+
+    let target = PointF {
+        x: x - left,
+        y: cursor_row as f32,
+    };
+    if app.config.smooth_caret {
+        move_caret(&mut app.caret, target);
+    } else {
+        jump_caret(&mut app.caret, target);
+    }
+    let x = left + app.caret.current.x;
+    let y = y_to_screen(app, 0) + app.caret.current.y * height;
+
+    let mut v = Vec::new();
+    if !focused {
+        let border = app::scaled(app, 1.0);
+        v.push(create_rect(x, y, width, border, theme.cursor));
         v.push(create_rect(
-            0.0,
-            end_screen.1,
-            end_screen.0,
+            x,
+            y + height - border,
+            width,
+            border,
+            theme.cursor,
+        ));
+        v.push(create_rect(x, y, border, height, theme.cursor));
+        v.push(create_rect(
+            x + width - border,
+            y,
+            border,
             height,
-            [0.5, 0.5, 0.5],
+            theme.cursor,
+        ));
+    } else {
+        match app.config.cursor_style {
+            CursorStyle::Bar => {
+                let bar = app::scaled(app, CURSOR_WIDTH);
+                v.push(create_rect(x, y, bar, height, theme.cursor));
+            }
+            CursorStyle::Underline => {
+                let underline = app::scaled(app, UNDERLINE_HEIGHT);
+                v.push(create_rect(
+                    x,
+                    y + height - underline,
+                    width,
+                    underline,
+                    theme.cursor,
+                ));
+            }
+            CursorStyle::Block => v.push(create_rect(x, y, width, height, theme.cursor)),
+        }
+    }
+    app.rect_renderer.render(&v, &mvp);
+
+    let on_glyph = !cluster.chars().all(char::is_whitespace);
+    if focused
+        && app.config.cursor_style == CursorStyle::Block
+        && on_glyph
+        && !app.caret.is_moving()
+        && cursor_row >= range.start
+    {
+        app.font_renderer.render_cluster(
+            &cluster,
+            x,
+            cursor_row - range.start,
+            theme.cursor_text,
+            &mvp,
+        );
+    }
+}
+
+// Drawn last so it covers the text rows running under it
+fn render_status_bar(app: &mut App, mvp: &Matrix) {
+    if !app.config.status_bar {
+        return;
+    }
+
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let width = x_size as f32;
+    let top = app::text_height(app);
+    let baseline = top + app::scaled(app, app::STATUS_BAR_PADDING) + app.font_renderer.ascender;
+    let char_width = app.font_renderer.char_width;
+    let theme = app.config.theme;
+
+    let items = status_bar::items(app);
+    let ranges = status_bar::layout(&items, char_width, width);
+    let mut v = vec![create_rect(
+        0.0,
+        top,
+        width,
+        app::status_bar_height(app),
+        theme.status_bar,
+    )];
+    let mut labels: Vec<Label> = items
+        .iter()
+        .zip(ranges.iter())
+        .map(|(item, range)| Label {
+            text: &item.text,
+            x: status_bar::text_x(range, char_width),
+            baseline,
+            color: theme.status_text,
+        })
+        .collect();
+
+    if let Some(picker) = &app.picker {
+        let (x, y, picker_width, row_height) = status_bar::picker_rect(app, picker);
+        v.push(create_rect(
+            x,
+            y,
+            picker_width,
+            top - y,
+            theme.picker_background,
+        ));
+        v.push(create_rect(
+            x,
+            y + picker.selected as f32 * row_height,
+            picker_width,
+            row_height,
+            theme.picker_selected,
         ));
+        if let Some(group_start) = picker.group_start {
+            v.push(create_rect_alpha(
+                x,
+                y + group_start as f32 * row_height,
+                picker_width,
+                1.0,
+                theme.status_text,
+                0.5,
+            ));
+        }
+        for (index, choice) in picker.choices.iter().enumerate() {
+            labels.push(Label {
+                text: choice,
+                x: x + char_width,
+                baseline: y + index as f32 * row_height + app.font_renderer.ascender,
+                color: theme.status_text,
+            });
+        }
+    }
+
+    app.rect_renderer.render(&v, &mvp);
+    app.font_renderer.render_labels(&labels, &mvp);
+}
+
+fn render_prompt(app: &mut App, mvp: &Matrix) {
+    let prompt = match &app.prompt {
+        Some(prompt) => prompt,
+        None => return,
     };
 
-    app.rect_renderer.render(&v, &projection);
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let top = app::prompt_top(app);
+    let bottom = app::text_height(app);
+    let char_width = app.font_renderer.char_width;
+    let baseline = top + app::scaled(app, app::STATUS_BAR_PADDING) + app.font_renderer.ascender;
+    let theme = app.config.theme;
+
+    let mut v = vec![create_rect(
+        0.0,
+        top,
+        x_size as f32,
+        bottom - top,
+        theme.picker_background,
+    )];
+    let text = prompt.text();
+    let mut labels = vec![Label {
+        text: &text,
+        x: char_width,
+        baseline,
+        color: theme.status_text,
+    }];
+
+    let ranges = prompt.choice_ranges(char_width);
+    for (index, (choice, range)) in prompt.choices.iter().zip(ranges.iter()).enumerate() {
+        if index == prompt.selected {
+            v.push(create_rect(
+                range.start,
+                top,
+                range.end - range.start,
+                bottom - top,
+                theme.picker_selected,
+            ));
+        }
+        labels.push(Label {
+            text: choice,
+            x: range.start + char_width,
+            baseline,
+            color: theme.status_text,
+        });
+    }
+
+    let line_height = app.font_renderer.advance_height;
+    if !prompt.lines.is_empty() {
+        v.push(create_rect(0.0, 0.0, x_size as f32, top, theme.background));
+    }
+    let rows = (top / line_height) as usize;
+    let shown = prompt.lines.iter().skip(prompt.scroll).take(rows);
+    for (row, line) in shown.enumerate() {
+        let color = match line.change {
+            Change::Same => theme.text,
+            Change::Removed => theme.diff_removed,
+            Change::Added => theme.diff_added,
+            Change::Hunk => theme.line_number,
+        };
+        labels.push(Label {
+            text: &line.text,
+            x: char_width,
+            baseline: row as f32 * line_height + app.font_renderer.ascender,
+            color,
+        });
+    }
+
+    app.rect_renderer.render(&v, &mvp);
+    app.font_renderer.render_labels(&labels, &mvp);
 }
 
-fn render_cursor(app: &mut App, mvp: &matrix::Matrix) {
-    let width = 2.0;
+// Rows of `offset | bytes | ASCII` in place of the text, the byte under the
+// cursor is marked in both columns
+fn render_hex(app: &mut App, mvp: &Matrix) {
+    let hex = match &app.buffer.hex {
+        Some(hex) => hex,
+        None => return,
+    };
+
     let height = app.font_renderer.advance_height;
+    let char_width = app.font_renderer.char_width;
+    let theme = app.config.theme;
+    let left = char_width;
+    let first_row = app.buffer.scroll.current_scroll.y as usize;
+    let visible = app::visible_range(app, app.buffer.scroll.current_scroll.y);
+    let rows = first_row..visible.end.min(hex::row_count(hex.bytes.len()));
+    let column_x = |column: usize| left + column as f32 * char_width;
+
+    let mut v = Vec::new();
+    let match_len = hex.pattern.len();
+    let on_match = |offset: usize| {
+        let start = hex.cursor_byte();
+        match_len > 0
+            && offset >= start
+            && offset < start + match_len
+            && hex.bytes[start..].starts_with(&hex.pattern)
+    };
+    for row in rows.clone() {
+        let y = y_to_screen(app, row);
+        if row == hex.cursor_row() && app.config.highlight_current_line {
+            v.push(create_rect(
+                0.0,
+                y,
+                column_x(hex::ascii_column(BYTES_PER_ROW) + 1),
+                height,
+                theme.current_line,
+            ));
+        }
+        for index in 0..BYTES_PER_ROW {
+            let offset = row * BYTES_PER_ROW + index;
+            if offset >= hex.bytes.len() {
+                break;
+            }
+            if offset == hex.cursor_byte() || on_match(offset) {
+                let color = if offset == hex.cursor_byte() {
+                    theme.selection
+                } else {
+                    theme.occurrence
+                };
+                v.push(create_rect(
+                    column_x(hex::hex_column(index)),
+                    y,
+                    2.0 * char_width,
+                    height,
+                    color,
+                ));
+                v.push(create_rect(
+                    column_x(hex::ascii_column(index)),
+                    y,
+                    char_width,
+                    height,
+                    color,
+                ));
+            }
+        }
+    }
 
-    let cursor_position = app.text.get_cursor().position;
+    let cursor_index = hex.cursor_byte() % BYTES_PER_ROW;
+    let cursor_x = column_x(hex::hex_column(cursor_index) + hex.cursor % 2);
+    let bar = app::scaled(app, CURSOR_WIDTH);
+    v.push(create_rect(
+        cursor_x,
+        y_to_screen(app, hex.cursor_row()),
+        bar,
+        height,
+        theme.cursor,
+    ));
+    app.rect_renderer.render(&v, &mvp);
 
-    let screen_pos = grid_to_screen(app, cursor_position);
+    let texts: Vec<String> = rows.map(|row| hex::row_text(&hex.bytes, row)).collect();
+    let labels: Vec<Label> = texts
+        .iter()
+        .enumerate()
+        .map(|(index, text)| Label {
+            text,
+            x: left,
+            baseline: (index + 1) as f32 * height,
+            color: theme.text,
+        })
+        .collect();
+    app.font_renderer.render_labels(&labels, &mvp);
+}
 
-    app.rect_renderer.render(
-        &vec![create_rect(
-            screen_pos.0,
-            screen_pos.1,
-            width,
-            height,
-            [1.0, 1.0, 1.0],
-        )],
-        &mvp,
-    );
+fn render_tab_bar(app: &mut App, mvp: &Matrix) {
+    if !app.config.tab_bar {
+        return;
+    }
+
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let height = app::tab_bar_height(app);
+    let baseline = app::scaled(app, app::STATUS_BAR_PADDING) + app.font_renderer.ascender;
+    let char_width = app.font_renderer.char_width;
+    let theme = app.config.theme;
+
+    let titles = tab_bar::titles(app);
+    let ranges = tab_bar::layout(&titles, char_width, x_size as f32, app.active);
+    let mut v = vec![create_rect(0.0, 0.0, x_size as f32, height, theme.tab_bar)];
+    let mut labels = Vec::new();
+    for (index, (title, range)) in titles.iter().zip(ranges.iter()).enumerate() {
+        let color = if index == app.active {
+            v.push(create_rect(
+                range.start,
+                0.0,
+                range.end - range.start,
+                height,
+                theme.tab_active,
+            ));
+            theme.tab_active_text
+        } else {
+            theme.tab_text
+        };
+        labels.push(Label {
+            text: title,
+            x: tab_bar::text_x(range, char_width),
+            baseline,
+            color,
+        });
+    }
+
+    app.rect_renderer.render(&v, &mvp);
+    app.font_renderer.render_labels(&labels, &mvp);
 }
 
 pub fn render_app(app: &mut App) {
@@ -131,21 +803,57 @@ pub fn render_app(app: &mut App) {
         unsafe {
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
+        app.font_renderer.begin_frame();
+
+        // everything but the tab bar is drawn from the bottom of the tab bar
+        let projection = app.projection.clone();
+        let tab_bar_offset = matrix::translate(0.0, -app::tab_bar_height(app), 0.0);
+        let mvp = matrix::mul(&projection, &tab_bar_offset);
+        if app.buffer.hex.is_some() {
+            render_hex(app, &mvp);
+            render_status_bar(app, &mvp);
+            render_tab_bar(app, &projection);
+            render_prompt(app, &mvp);
 
-        let visible_range = app::visible_range(app, app.scroll.current_scroll.y);
+            app.window.swap_buffers();
+            app.should_rerender = false;
+            return;
+        }
 
-        let mvp = app.projection.clone();
+        app::sync_display_map(app);
+        let visible_range = app::visible_range(app, app.buffer.scroll.current_scroll.y);
+        let rows = app
+            .buffer
+            .display_map
+            .display_lines(app.buffer.text.get_text(), visible_range.clone());
+
+        render_line_highlights(app, &mvp, &rows);
+        render_decorations(app, &mvp, &rows);
         {
             timer!("render_selection");
             render_selection(app, &mvp, visible_range.clone());
         }
-        render_cursor(app, &mvp);
 
+        let markers = fold_markers(app, &rows);
+        let current_line = if app.config.highlight_current_line {
+            Some(app.buffer.text.get_cursor().position.y as usize)
+        } else {
+            None
+        };
         app.font_renderer.render_text_with_line_numbers(
-            &app.text.get_text(),
-            visible_range.clone(),
+            &app.buffer.text.get_text(),
+            &rows,
+            &markers,
+            current_line,
+            &app.config.theme,
             &mvp,
         );
+        render_cursor(app, &mvp, visible_range.clone());
+        render_minimap(app, &mvp, visible_range);
+        render_scrollbar(app, &mvp);
+        render_status_bar(app, &mvp);
+        render_tab_bar(app, &projection);
+        render_prompt(app, &mvp);
 
         app.window.swap_buffers();
         app.should_rerender = false;
@@ -1,4 +1,4 @@
-fn lerp(lower: f32, upper: f32, by: f32) -> f32 {
+pub fn lerp(lower: f32, upper: f32, by: f32) -> f32 {
     lower * (1.0 - by) + upper * by
 }
 
@@ -41,6 +41,14 @@ pub fn scroll_to(scroll: &mut Scroll, y: f32) {
     scroll.animation_time = 1.0 / 60.0;
 }
 
+pub fn jump_to(scroll: &mut Scroll, y: f32) {
+    scroll.current_scroll.y = y;
+    scroll.target_scroll.y = y;
+    scroll.base_scroll = scroll.current_scroll;
+    scroll.animation_time = 0.0;
+    scroll.started_animating = false;
+}
+
 pub fn scroll_to_x(scroll: &mut Scroll, x: f32) {
     scroll.current_scroll.x = x
 }
@@ -0,0 +1,101 @@
+use std::time::Instant;
+
+// Sizes in logical pixels
+pub const SCROLLBAR_WIDTH: f32 = 14.0;
+pub const MIN_THUMB_HEIGHT: f32 = 20.0;
+pub const MARKER_HEIGHT: f32 = 2.0;
+
+// Seconds without scrolling before the scrollbar starts fading, and how long
+// fading takes
+pub const FADE_DELAY: f32 = 1.5;
+pub const FADE_TIME: f32 = 0.3;
+
+// Thumb top and height on a track of `track_height` pixels. Scrolling past
+// the last row makes the content taller, so the thumb never runs off the end.
+pub fn thumb(
+    track_height: f32,
+    min_height: f32,
+    row_count: usize,
+    visible_rows: usize,
+    scroll: f32,
+) -> (f32, f32) {
+    let visible = visible_rows.max(1) as f32;
+    let content = (row_count as f32).max(scroll + visible);
+    let height = (track_height * visible / content)
+        .max(min_height)
+        .min(track_height);
+
+    if content <= visible {
+        return (0.0, height);
+    }
+    let y = (track_height - height) * scroll.max(0.0) / (content - visible);
+    (y, height)
+}
+
+// Inverse of `thumb`, scroll that puts the thumb top at `y`
+pub fn scroll_at(
+    track_height: f32,
+    thumb_height: f32,
+    row_count: usize,
+    visible_rows: usize,
+    y: f32,
+) -> f32 {
+    let range = track_height - thumb_height;
+    let max_scroll = row_count.saturating_sub(visible_rows) as f32;
+    if range <= 0.0 {
+        return 0.0;
+    }
+    (y / range).max(0.0).min(1.0) * max_scroll
+}
+
+pub fn marker_y(track_height: f32, row: usize, row_count: usize) -> f32 {
+    track_height * row as f32 / row_count.max(1) as f32
+}
+
+// 1.0 while active, then fades linearly to 0.0
+pub fn opacity(idle: f32) -> f32 {
+    if idle <= FADE_DELAY {
+        return 1.0;
+    }
+    (1.0 - (idle - FADE_DELAY) / FADE_TIME).max(0.0)
+}
+
+pub struct Scrollbar {
+    // distance from the thumb top to where it was grabbed, while dragging
+    pub drag: Option<f32>,
+    last_active: Instant,
+}
+
+impl Scrollbar {
+    pub fn new() -> Scrollbar {
+        Scrollbar {
+            drag: None,
+            last_active: Instant::now(),
+        }
+    }
+
+    // Shows a faded out scrollbar again
+    pub fn wake(&mut self) {
+        self.last_active = Instant::now();
+    }
+
+    pub fn idle(&self) -> f32 {
+        if self.drag.is_some() {
+            return 0.0;
+        }
+        self.last_active.elapsed().as_secs_f32()
+    }
+
+    // Seconds until the scrollbar has to be drawn again to fade it, None once
+    // it is gone
+    pub fn next_frame_in(&self) -> Option<f32> {
+        let idle = self.idle();
+        if idle < FADE_DELAY {
+            Some(FADE_DELAY - idle)
+        } else if idle < FADE_DELAY + FADE_TIME {
+            Some(1.0 / 60.0)
+        } else {
+            None
+        }
+    }
+}
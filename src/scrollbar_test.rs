@@ -0,0 +1,49 @@
+use crate::scrollbar::{marker_y, opacity, scroll_at, thumb, FADE_DELAY, FADE_TIME};
+
+#[test]
+fn thumb_follows_scroll() {
+    // 1000 rows, 100 visible on a 500px track
+    assert_eq!(thumb(500.0, 20.0, 1000, 100, 0.0), (0.0, 50.0));
+    assert_eq!(thumb(500.0, 20.0, 1000, 100, 450.0), (225.0, 50.0));
+    assert_eq!(thumb(500.0, 20.0, 1000, 100, 900.0), (450.0, 50.0));
+}
+
+#[test]
+fn thumb_keeps_min_height() {
+    let (y, height) = thumb(500.0, 20.0, 100_000, 100, 99_900.0);
+    assert_eq!(height, 20.0);
+    assert_eq!(y, 480.0);
+}
+
+#[test]
+fn thumb_fills_track_when_everything_fits() {
+    assert_eq!(thumb(500.0, 20.0, 10, 100, 0.0), (0.0, 500.0));
+}
+
+#[test]
+fn thumb_stays_on_track_past_last_row() {
+    let (y, height) = thumb(500.0, 20.0, 1000, 100, 1000.0);
+    assert_eq!(y + height, 500.0);
+}
+
+#[test]
+fn scroll_at_inverts_thumb() {
+    let (y, height) = thumb(500.0, 20.0, 1000, 100, 300.0);
+    assert_eq!(scroll_at(500.0, height, 1000, 100, y), 300.0);
+    assert_eq!(scroll_at(500.0, height, 1000, 100, -40.0), 0.0);
+    assert_eq!(scroll_at(500.0, height, 1000, 100, 900.0), 900.0);
+}
+
+#[test]
+fn markers_spread_over_track() {
+    assert_eq!(marker_y(500.0, 0, 1000), 0.0);
+    assert_eq!(marker_y(500.0, 500, 1000), 250.0);
+}
+
+#[test]
+fn fades_after_delay() {
+    assert_eq!(opacity(0.0), 1.0);
+    assert_eq!(opacity(FADE_DELAY), 1.0);
+    assert!((opacity(FADE_DELAY + FADE_TIME / 2.0) - 0.5).abs() < 0.001);
+    assert_eq!(opacity(FADE_DELAY + FADE_TIME * 2.0), 0.0);
+}
@@ -0,0 +1,374 @@
+use std::ops::Range;
+use std::path::Path;
+
+use glfw::Key;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::app::{self, App};
+use crate::config::Indent;
+use crate::encoding::FileEncoding;
+use crate::file_format::LineEnding;
+use crate::grapheme::{grapheme_width, visual_column};
+
+// Space around the text of every item, in characters
+const ITEM_PADDING: f32 = 1.0;
+
+pub const LANGUAGES: [(&str, &[&str]); 16] = [
+    ("Plain Text", &["txt"]),
+    ("Rust", &["rs"]),
+    ("C", &["c", "h"]),
+    ("C++", &["cpp", "cc", "cxx", "hpp", "hh"]),
+    ("Go", &["go"]),
+    ("Python", &["py"]),
+    ("JavaScript", &["js", "mjs"]),
+    ("TypeScript", &["ts", "tsx"]),
+    ("JSON", &["json"]),
+    ("TOML", &["toml"]),
+    ("YAML", &["yaml", "yml"]),
+    ("Markdown", &["md", "markdown"]),
+    ("HTML", &["html", "htm"]),
+    ("CSS", &["css"]),
+    ("Shell", &["sh", "bash", "zsh"]),
+    ("GLSL", &["glsl", "vert", "frag"]),
+];
+
+pub fn language_for(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    let extension = match extension {
+        Some(extension) => extension,
+        None => return LANGUAGES[0].0,
+    };
+    LANGUAGES
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map_or(LANGUAGES[0].0, |(name, _)| name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    File,
+    Position,
+    Selection,
+    Language,
+    Indentation,
+    Encoding,
+    LineEnding,
+    Mode,
+}
+
+pub struct Item {
+    pub segment: Segment,
+    pub text: String,
+    // right aligned items are laid out from the right edge
+    pub right: bool,
+}
+
+// Name shown for a buffer that has no path yet
+const UNTITLED: &str = "Untitled";
+
+pub fn file_text(path: &str, dirty: bool) -> String {
+    let path = if path.is_empty() { UNTITLED } else { path };
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.into(), |name| name.to_string_lossy());
+    if dirty {
+        format!("{} \u{25CF}", name)
+    } else {
+        name.into_owned()
+    }
+}
+
+// `name • dir — editor`, with a dot in front while there are unsaved changes
+pub fn window_title(path: &str, modified: bool) -> String {
+    let path = Path::new(if path.is_empty() { UNTITLED } else { path });
+    let name = path
+        .file_name()
+        .map_or("".into(), |name| name.to_string_lossy());
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_string_lossy(),
+        _ => ".".into(),
+    };
+    let marker = if modified { "\u{25CF} " } else { "" };
+    format!("{}{} \u{2022} {} \u{2014} editor", marker, name, dir)
+}
+
+pub fn position_text(line: usize, column: usize) -> String {
+    format!("Ln {}, Col {}", line + 1, column + 1)
+}
+
+pub fn offset_text(offset: usize, len: usize) -> String {
+    format!("Offset {:#X} of {:#X}", offset, len)
+}
+
+pub fn selection_text(chars: usize, lines: usize) -> String {
+    if lines > 1 {
+        format!("{} selected ({} lines)", chars, lines)
+    } else {
+        format!("{} selected", chars)
+    }
+}
+
+pub fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+// Screen x ranges of the items on a bar `width` pixels wide
+pub fn layout(items: &[Item], char_width: f32, width: f32) -> Vec<Range<f32>> {
+    let item_width =
+        |item: &Item| (text_width(&item.text) as f32 + 2.0 * ITEM_PADDING) * char_width;
+
+    let mut left = 0.0;
+    let mut right = width;
+    let mut ranges = vec![0.0..0.0; items.len()];
+
+    for (index, item) in items.iter().enumerate() {
+        if !item.right {
+            ranges[index] = left..left + item_width(item);
+            left += item_width(item);
+        }
+    }
+    for (index, item) in items.iter().enumerate().rev() {
+        if item.right {
+            ranges[index] = right - item_width(item)..right;
+            right -= item_width(item);
+        }
+    }
+    ranges
+}
+
+pub fn text_x(range: &Range<f32>, char_width: f32) -> f32 {
+    range.start + ITEM_PADDING * char_width
+}
+
+// A list of choices for one segment, drawn above the status bar
+pub struct Picker {
+    pub segment: Segment,
+    pub choices: Vec<String>,
+    pub selected: usize,
+    pub x: f32,
+    // first choice of a second group, a line sets it apart from the ones above
+    pub group_start: Option<usize>,
+}
+
+fn line_ending_text(app: &App) -> String {
+    let name = app.buffer.text.line_ending.name();
+    if app.buffer.text.mixed_line_endings {
+        format!("{} (mixed)", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+pub fn items(app: &mut App) -> Vec<Item> {
+    let dirty = app.buffer.is_modified();
+    if let Some(hex) = &app.buffer.hex {
+        let settings = vec![
+            (Segment::File, file_text(&app.buffer.file_path, dirty)),
+            (
+                Segment::Position,
+                offset_text(hex.cursor_byte(), hex.bytes.len()),
+            ),
+            (Segment::Mode, "HEX".to_owned()),
+        ];
+        return settings
+            .into_iter()
+            .map(|(segment, text)| Item {
+                segment,
+                right: segment != Segment::File,
+                text,
+            })
+            .collect();
+    }
+
+    let cursor = *app.buffer.text.get_cursor();
+    let text = app.buffer.text.get_text();
+    let line = cursor.position.y as usize;
+    let column = visual_column(text.line(line), cursor.position.x as usize);
+
+    let mut items = vec![
+        Item {
+            segment: Segment::File,
+            text: file_text(&app.buffer.file_path, dirty),
+            right: false,
+        },
+        Item {
+            segment: Segment::Position,
+            text: position_text(line, column),
+            right: true,
+        },
+    ];
+
+    if let Some(selection) = cursor.selection {
+        let start = selection.to_char(text);
+        let end = cursor.position.to_char(text);
+        let lines = (selection.y - cursor.position.y).abs() as usize + 1;
+        items.push(Item {
+            segment: Segment::Selection,
+            text: selection_text(start.max(end) - start.min(end), lines),
+            right: true,
+        });
+    }
+
+    let mode = if app.buffer.overwrite { "OVR" } else { "INS" };
+    let settings = vec![
+        (Segment::Indentation, app.buffer.indent.name()),
+        (Segment::Encoding, app.buffer.text.encoding.name()),
+        (Segment::LineEnding, line_ending_text(app)),
+        (Segment::Language, app.buffer.language.to_owned()),
+        (Segment::Mode, mode.to_owned()),
+    ];
+    items.extend(settings.into_iter().map(|(segment, text)| Item {
+        segment,
+        text,
+        right: true,
+    }));
+    items
+}
+
+// Reopening comes first and is preselected, Enter shouldn't rewrite the file.
+// Saving follows in a group of its own.
+pub fn encoding_choices(current: FileEncoding) -> (Vec<String>, usize) {
+    let encodings = FileEncoding::CHOICES.iter();
+    let reopen = encodings
+        .clone()
+        .map(|encoding| format!("Reopen with {}", encoding.name()));
+    let save = encodings.map(|encoding| format!("Save with {}", encoding.name()));
+    let selected = FileEncoding::CHOICES
+        .iter()
+        .position(|&encoding| encoding == current)
+        .unwrap_or(0);
+    (reopen.chain(save).collect(), selected)
+}
+
+// Choices for the segment and the one in use, None for segments that can't
+// be changed
+fn choices(app: &App, segment: Segment) -> Option<(Vec<String>, usize)> {
+    fn pick<T: PartialEq>(
+        all: &[T],
+        current: T,
+        name: impl Fn(&T) -> String,
+    ) -> (Vec<String>, usize) {
+        let selected = all
+            .iter()
+            .position(|choice| *choice == current)
+            .unwrap_or(0);
+        (all.iter().map(name).collect(), selected)
+    }
+
+    match segment {
+        Segment::Indentation => Some(pick(&Indent::CHOICES, app.buffer.indent, |indent| indent.name())),
+        Segment::Encoding => Some(encoding_choices(app.buffer.text.encoding)),
+        Segment::LineEnding => Some(pick(&LineEnding::ALL, app.buffer.text.line_ending, |ending| {
+            ending.name().to_owned()
+        })),
+        Segment::Language => {
+            let names: Vec<&str> = LANGUAGES.iter().map(|(name, _)| *name).collect();
+            Some(pick(&names, app.buffer.language, |name| (*name).to_owned()))
+        }
+        Segment::Mode if app.buffer.hex.is_some() => None,
+        Segment::Mode => Some(pick(&[false, true], app.buffer.overwrite, |&overwrite| {
+            let mode = if overwrite { "Overwrite" } else { "Insert" };
+            mode.to_owned()
+        })),
+        Segment::File | Segment::Position | Segment::Selection => None,
+    }
+}
+
+fn choose(app: &mut App, segment: Segment, index: usize) {
+    match segment {
+        Segment::Indentation => app.buffer.indent = Indent::CHOICES[index],
+        Segment::LineEnding => app.buffer.text.set_line_ending(LineEnding::ALL[index]),
+        Segment::Language => app.buffer.language = LANGUAGES[index].0,
+        Segment::Encoding => {
+            let count = FileEncoding::CHOICES.len();
+            if index < count {
+                app::reopen_with_encoding(app, FileEncoding::CHOICES[index]);
+            } else {
+                app::save_with_encoding(app, FileEncoding::CHOICES[index - count]);
+            }
+        }
+        Segment::Mode => app.buffer.overwrite = index == 1,
+        _ => {}
+    }
+}
+
+fn open_picker(app: &mut App, segment: Segment, x: f32) {
+    if let Some((choices, selected)) = choices(app, segment) {
+        app.picker = Some(Picker {
+            segment,
+            choices,
+            selected,
+            x,
+            group_start: match segment {
+                Segment::Encoding => Some(FileEncoding::CHOICES.len()),
+                _ => None,
+            },
+        });
+    }
+}
+
+// Top left corner, width and row height of the open picker
+pub fn picker_rect(app: &App, picker: &Picker) -> (f32, f32, f32, f32) {
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let char_width = app.font_renderer.char_width;
+    let row_height = app.font_renderer.advance_height;
+
+    let widest = picker.choices.iter().map(|choice| text_width(choice)).max();
+    let width = (widest.unwrap_or(0) as f32 + 2.0 * ITEM_PADDING) * char_width;
+    let x = picker.x.min(x_size as f32 - width).max(0.0);
+    let y = app::text_height(app) - picker.choices.len() as f32 * row_height;
+    (x, y, width, row_height)
+}
+
+// Handles a click on the status bar or the open picker, false when the click
+// was somewhere else
+pub fn click(app: &mut App, x: f32, y: f32) -> bool {
+    if let Some(picker) = app.picker.take() {
+        let (left, top, width, row_height) = picker_rect(app, &picker);
+        if x >= left && x < left + width && y >= top && y < app::text_height(app) {
+            let index = ((y - top) / row_height) as usize;
+            choose(app, picker.segment, index.min(picker.choices.len() - 1));
+        }
+        app.should_rerender = true;
+        return true;
+    }
+
+    if !app.config.status_bar || y < app::text_height(app) {
+        return false;
+    }
+
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let items = items(app);
+    let ranges = layout(&items, app.font_renderer.char_width, x_size as f32);
+    for (item, range) in items.iter().zip(ranges.iter()) {
+        if range.contains(&x) {
+            open_picker(app, item.segment, range.start);
+        }
+    }
+    app.should_rerender = true;
+    true
+}
+
+// Keys go to the picker while it is open
+pub fn picker_key(app: &mut App, key: Key) {
+    let mut picker = match app.picker.take() {
+        Some(picker) => picker,
+        None => return,
+    };
+
+    match key {
+        Key::Up => picker.selected = picker.selected.saturating_sub(1),
+        Key::Down => picker.selected = (picker.selected + 1).min(picker.choices.len() - 1),
+        Key::Enter => {
+            choose(app, picker.segment, picker.selected);
+            return;
+        }
+        Key::Escape => return,
+        _ => {}
+    }
+    app.picker = Some(picker);
+}
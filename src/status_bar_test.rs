@@ -0,0 +1,83 @@
+use crate::encoding::FileEncoding;
+use crate::status_bar::{
+    encoding_choices, file_text, language_for, layout, offset_text, position_text, selection_text,
+    window_title, Item, Segment,
+};
+
+fn item(text: &str, right: bool) -> Item {
+    Item {
+        segment: Segment::File,
+        text: text.to_owned(),
+        right,
+    }
+}
+
+#[test]
+fn language_from_extension() {
+    assert_eq!(language_for("src/main.rs"), "Rust");
+    assert_eq!(language_for("README.MD"), "Markdown");
+    assert_eq!(language_for("shaders/text.frag"), "GLSL");
+    assert_eq!(language_for("Makefile"), "Plain Text");
+    assert_eq!(language_for("notes.unknown"), "Plain Text");
+}
+
+#[test]
+fn item_texts() {
+    assert_eq!(file_text("./src/app.rs", false), "app.rs");
+    assert_eq!(file_text("./src/app.rs", true), "app.rs \u{25CF}");
+    assert_eq!(file_text("", false), "Untitled");
+    assert_eq!(position_text(0, 0), "Ln 1, Col 1");
+    assert_eq!(position_text(9, 4), "Ln 10, Col 5");
+    assert_eq!(offset_text(0x1F, 0x200), "Offset 0x1F of 0x200");
+    assert_eq!(selection_text(5, 1), "5 selected");
+    assert_eq!(selection_text(12, 3), "12 selected (3 lines)");
+}
+
+#[test]
+fn layout_from_both_edges() {
+    let items = vec![item("a.rs", false), item("Ln 1", true), item("LF", true)];
+    let ranges = layout(&items, 10.0, 200.0);
+
+    assert_eq!(ranges[0], 0.0..60.0);
+    assert_eq!(ranges[1], 100.0..160.0);
+    assert_eq!(ranges[2], 160.0..200.0);
+}
+
+#[test]
+fn layout_measures_wide_characters() {
+    let items = vec![item("日本", false)];
+
+    assert_eq!(layout(&items, 10.0, 200.0)[0], 0.0..60.0);
+}
+
+#[test]
+fn title_with_directory() {
+    assert_eq!(
+        window_title("src/app.rs", false),
+        "app.rs \u{2022} src \u{2014} editor"
+    );
+    assert_eq!(
+        window_title("notes.txt", true),
+        "\u{25CF} notes.txt \u{2022} . \u{2014} editor"
+    );
+    assert_eq!(
+        window_title("", false),
+        "Untitled \u{2022} . \u{2014} editor"
+    );
+}
+
+#[test]
+fn encoding_picker_preselects_reopening() {
+    let current = FileEncoding::CHOICES[1];
+    let (choices, selected) = encoding_choices(current);
+    let count = FileEncoding::CHOICES.len();
+
+    assert_eq!(choices.len(), 2 * count);
+    assert_eq!(choices[selected], format!("Reopen with {}", current.name()));
+    assert!(choices[..count]
+        .iter()
+        .all(|choice| choice.starts_with("Reopen with ")));
+    assert!(choices[count..]
+        .iter()
+        .all(|choice| choice.starts_with("Save with ")));
+}
@@ -0,0 +1,64 @@
+use std::ops::Range;
+
+use crate::app::{self, App};
+use crate::status_bar::{file_text, text_width};
+
+// Space around the title of every tab, in characters
+const TAB_PADDING: f32 = 1.0;
+
+// Screen x ranges of tabs with `titles` on a bar `width` pixels wide. When
+// they don't all fit they move left as far as it takes to show `active`.
+pub fn layout(titles: &[String], char_width: f32, width: f32, active: usize) -> Vec<Range<f32>> {
+    let mut left = 0.0;
+    let mut ranges: Vec<Range<f32>> = titles
+        .iter()
+        .map(|title| {
+            let tab_width = (text_width(title) as f32 + 2.0 * TAB_PADDING) * char_width;
+            left += tab_width;
+            left - tab_width..left
+        })
+        .collect();
+
+    let shift = ranges
+        .get(active)
+        .map_or(0.0, |range| (range.end - width).max(0.0).min(range.start));
+    for range in ranges.iter_mut() {
+        *range = range.start - shift..range.end - shift;
+    }
+    ranges
+}
+
+pub fn text_x(range: &Range<f32>, char_width: f32) -> f32 {
+    range.start + TAB_PADDING * char_width
+}
+
+// File name of every tab, with a dot on the ones with unsaved changes
+pub fn titles(app: &mut App) -> Vec<String> {
+    (0..app::tab_count(app))
+        .map(|index| {
+            let buffer = app::tab(app, index);
+            let modified = buffer.is_modified();
+            file_text(&buffer.file_path, modified)
+        })
+        .collect()
+}
+
+// Switches to the tab under `x`, false when the click was below the tab bar
+pub fn click(app: &mut App, x: f32, y: f32) -> bool {
+    if y >= app::tab_bar_height(app) {
+        return false;
+    }
+    // an open question is about the active tab and has to be answered first
+    if app.prompt.is_some() {
+        return true;
+    }
+
+    let (x_size, _) = app.window.get_framebuffer_size();
+    let titles = titles(app);
+    let char_width = app.font_renderer.char_width;
+    let ranges = layout(&titles, char_width, x_size as f32, app.active);
+    if let Some(index) = ranges.iter().position(|range| range.contains(&x)) {
+        app::switch_tab(app, index);
+    }
+    true
+}
@@ -0,0 +1,29 @@
+use crate::tab_bar::layout;
+
+fn titles(titles: &[&str]) -> Vec<String> {
+    titles.iter().map(|title| title.to_string()).collect()
+}
+
+#[test]
+fn layout_from_the_left() {
+    let ranges = layout(&titles(&["a.rs", "main.rs"]), 10.0, 200.0, 1);
+
+    assert_eq!(ranges, vec![0.0..60.0, 60.0..150.0]);
+}
+
+#[test]
+fn layout_moves_to_show_the_active_tab() {
+    let tabs = titles(&["a.rs", "b.rs", "c.rs", "d.rs"]);
+
+    assert_eq!(layout(&tabs, 10.0, 100.0, 0)[0], 0.0..60.0);
+    let ranges = layout(&tabs, 10.0, 100.0, 2);
+    assert_eq!(ranges[2], 40.0..100.0);
+    assert_eq!(ranges[0], -80.0..-20.0);
+}
+
+#[test]
+fn layout_keeps_a_wide_active_tab_at_the_left() {
+    let ranges = layout(&titles(&["a.rs", "very_long_name.rs"]), 10.0, 100.0, 1);
+
+    assert_eq!(ranges[1], 0.0..190.0);
+}
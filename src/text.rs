@@ -1,6 +1,11 @@
-use std::{io::Read, ptr};
-
 use crate::cursor;
+use crate::display_map::DisplayMap;
+use crate::encoding::FileEncoding;
+use crate::file_format::{split_line_endings, with_line_ending, LineEnding, LONE_CR};
+use crate::grapheme::{
+    char_x_at_column, is_grapheme_boundary, next_grapheme_boundary, prev_grapheme_boundary,
+    visual_column,
+};
 
 use cursor::{Cursor, Point};
 use memchr::memchr_iter;
@@ -17,10 +22,45 @@ pub struct UndoState {
     pub index: usize,
 }
 
+// Lines `start..old_end` of the text before an edit became `start..new_end`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineEdit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize,
+}
+
+impl LineEdit {
+    // One edit covering `self` followed by `next`, lines between the two are
+    // treated as changed
+    pub fn then(self, next: LineEdit) -> LineEdit {
+        // end of both edits in the lines between them
+        let end = self.new_end.max(next.old_end);
+        LineEdit {
+            start: self.start.min(next.start),
+            old_end: end - self.new_end + self.old_end,
+            new_end: end - next.old_end + next.new_end,
+        }
+    }
+}
+
 pub struct Text {
     pub history: Vec<UndoPoint>,
     pub index: usize,
     pub last_added: bool,
+    // what line breaks turn into on save
+    pub line_ending: LineEnding,
+    // the file had more than one kind of line ending
+    pub mixed_line_endings: bool,
+    pub encoding: FileEncoding,
+    // bumped on every change to the text, lets views cache what they derive from it
+    revision: u64,
+    // changes not yet seen by take_edits
+    edits: Vec<LineEdit>,
+    // history point holding the text as it was last loaded or saved, None
+    // once that point was edited or dropped or when it isn't known
+    saved_index: Option<usize>,
+    saved_line_ending: LineEnding,
 }
 
 fn clamp(x: i64, min: i64, max: i64) -> i64 {
@@ -81,66 +121,67 @@ pub enum DeleteDirection {
     Back,
 }
 
-struct RemoveCrlf<T: std::io::Read> {
-    reader: T,
-    matches: Vec<usize>,
+#[derive(Clone)]
+pub enum Selection {
+    Select,
+    NotSelect,
 }
 
-impl<T: std::io::Read> RemoveCrlf<T> {
-    pub fn new(reader: T) -> RemoveCrlf<T> {
-        return RemoveCrlf {
-            reader,
-            matches: vec![],
-        };
-    }
-}
+fn diff_lines(old: &ropey::Rope, new: &ropey::Rope) -> LineEdit {
+    let (old_len, new_len) = (old.len_lines(), new.len_lines());
 
-pub fn remove_crlf_from_buff(buf: &mut [u8], matches: &[usize]) {
-    let b = buf.as_mut_ptr();
-    for i in 0..matches.len() - 1 {
-        let current = matches[i] as isize;
-        let next = matches[i + 1] as isize;
-        unsafe {
-            ptr::copy(
-                b.offset(current + 1),
-                b.offset(current - i as isize),
-                (next - current) as usize,
-            )
-        }
+    let mut start = 0;
+    while start < old_len.min(new_len) && old.line(start) == new.line(start) {
+        start += 1;
     }
-}
-
-impl<T: std::io::Read> Read for RemoveCrlf<T> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let res = self.reader.read(buf)?;
-        let s = &mut buf[..res];
 
-        self.matches.clear();
-        self.matches.extend(memchr_iter('\r' as u8, s));
-        self.matches.push(res);
-        remove_crlf_from_buff(s, &self.matches);
+    let mut suffix = 0;
+    while suffix < old_len.min(new_len) - start
+        && old.line(old_len - 1 - suffix) == new.line(new_len - 1 - suffix)
+    {
+        suffix += 1;
+    }
 
-        return Ok(res - (self.matches.len() - 1));
+    LineEdit {
+        start,
+        old_end: old_len - suffix,
+        new_end: new_len - suffix,
     }
 }
 
-#[derive(Clone)]
-pub enum Selection {
-    Select,
-    NotSelect,
+// remembered_x is kept as a visual column so vertical movement stays aligned
+// across tabs and wide characters
+fn column_of(position: &Point, text: &ropey::Rope) -> i64 {
+    visual_column(text.line(position.y as usize), position.x as usize) as i64
 }
 
 impl Text {
-    pub fn new<T: std::io::Read>(reader: T) -> Text {
-        let initial_text = ropey::Rope::from_reader(RemoveCrlf::new(reader)).unwrap();
+    #[cfg(test)]
+    pub fn new<T: std::io::Read>(mut reader: T) -> Text {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        Text::decode(&bytes, crate::encoding::detect(&bytes))
+    }
+
+    pub fn decode(bytes: &[u8], encoding: FileEncoding) -> Text {
+        let content = encoding.decode(bytes);
+        let (content, line_ending, mixed_line_endings) = split_line_endings(&content);
+        let initial_text = ropey::Rope::from_str(&content);
         let p = UndoPoint {
-            text: initial_text,
+            text: initial_text.clone(),
             cursor: Cursor::new(),
         };
         return Text {
             history: vec![p],
             index: 0,
             last_added: false,
+            line_ending,
+            mixed_line_endings,
+            encoding,
+            revision: 0,
+            edits: Vec::new(),
+            saved_index: Some(0),
+            saved_line_ending: line_ending,
         };
     }
 
@@ -161,6 +202,107 @@ impl Text {
         &self.history[self.index].text
     }
 
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Differs from what is on disk, undoing back to the saved text counts as
+    // unmodified
+    pub fn is_modified(&self) -> bool {
+        self.saved_index != Some(self.index) || self.line_ending != self.saved_line_ending
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_index = Some(self.index);
+        self.saved_line_ending = self.line_ending;
+        // the next edit starts a new history point so undo can get back here
+        self.last_added = false;
+    }
+
+    // Counts as modified until the next save, for text that didn't come from
+    // the file as it is on disk
+    pub fn forget_saved(&mut self) {
+        self.saved_index = None;
+    }
+
+    // Contents of the file, fails with the first character the encoding
+    // can't store
+    pub fn to_bytes(&self) -> Result<Vec<u8>, char> {
+        self.encoding.encode(&self.to_file_string())
+    }
+
+    // The text with the line breaks it is saved with, not encoded yet
+    pub fn to_file_string(&self) -> String {
+        with_line_ending(&self.get_text().to_string(), self.line_ending)
+    }
+
+    // Line breaks turn into `line_ending` on the next save, lines of a mixed
+    // file that kept their own CRLF are converted too
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+        self.mixed_line_endings = false;
+        self.remove_kept_crs();
+    }
+
+    // Drops the `LONE_CR` in front of line breaks as one undo step
+    fn remove_kept_crs(&mut self) {
+        let text = self.get_text();
+        let crs: Vec<usize> = text
+            .chars()
+            .zip(text.chars().skip(1))
+            .enumerate()
+            .filter(|&(_, pair)| pair == (LONE_CR, '\n'))
+            .map(|(at, _)| at)
+            .collect();
+        if crs.is_empty() {
+            return;
+        }
+
+        self.add_undo_point();
+        let UndoPoint { text, cursor } = self.current_point();
+        let cursor = cursor.position.to_char(text);
+        for &at in crs.iter().rev() {
+            self.record_edit(at..at + 1, "");
+            self.current_point().text.remove(at..at + 1);
+        }
+
+        let before_cursor = crs.iter().filter(|&&at| at < cursor).count();
+        let UndoPoint { text, cursor: c } = self.current_point();
+        c.position = Point::from_char(cursor - before_cursor, text);
+        c.remembered_x = column_of(&c.position, text);
+        c.selection = None;
+    }
+
+    pub fn take_edits(&mut self) -> Vec<LineEdit> {
+        std::mem::replace(&mut self.edits, Vec::new())
+    }
+
+    // Has to be called before `removed` is replaced with `inserted`
+    fn record_edit(&mut self, removed: std::ops::Range<usize>, inserted: &str) {
+        let text = self.get_text();
+        let start = text.char_to_line(removed.start);
+        let edit = LineEdit {
+            start,
+            old_end: text.char_to_line(removed.end) + 1,
+            new_end: start + memchr_iter(b'\n', inserted.as_bytes()).count() + 1,
+        };
+
+        // the saved text is being edited in place
+        if self.saved_index == Some(self.index) {
+            self.saved_index = None;
+        }
+        self.revision += 1;
+        self.edits.push(edit);
+    }
+
+    // Undo and redo swap the whole rope, find which lines differ
+    fn record_history_change(&mut self, previous: usize) {
+        let edit = diff_lines(&self.history[previous].text, self.get_text());
+
+        self.revision += 1;
+        self.edits.push(edit);
+    }
+
     fn soft_undo_point(&mut self) {
         if !self.last_added {
             self.add_undo_point();
@@ -177,6 +319,7 @@ impl Text {
         }
 
         if let Some(range) = self.selection_range() {
+            self.record_edit(range.clone(), str);
             let UndoPoint { text, cursor } = self.current_point();
 
             text.remove(range.clone());
@@ -186,62 +329,73 @@ impl Text {
         } else {
             let UndoPoint { text, cursor } = self.current_point();
             let start_idx = cursor.position.to_char(text);
+            self.record_edit(start_idx..start_idx, str);
 
+            let UndoPoint { text, cursor } = self.current_point();
             text.insert(start_idx, str);
 
             let end_idx = start_idx + str.chars().count();
             cursor.position = Point::from_char(end_idx, text);
-            cursor.remembered_x = cursor.position.x;
+            cursor.remembered_x = column_of(&cursor.position, text);
         }
     }
 
     pub fn delete_text(&mut self, key: DeleteDirection) {
-        fn char_to_delete_next(text: &mut ropey::Rope, cursor: &mut Cursor) -> Option<usize> {
+        fn grapheme_to_delete_next(
+            text: &mut ropey::Rope,
+            cursor: &mut Cursor,
+        ) -> Option<std::ops::Range<usize>> {
             let start_idx = cursor.position.to_char(text);
 
             if start_idx < text.len_chars() {
-                return Some(start_idx);
+                return Some(start_idx..next_grapheme_boundary(text, start_idx));
             }
             None
         }
 
-        fn char_to_delete_previous(text: &mut ropey::Rope, cursor: &mut Cursor) -> Option<usize> {
+        fn grapheme_to_delete_previous(
+            text: &mut ropey::Rope,
+            cursor: &mut Cursor,
+        ) -> Option<std::ops::Range<usize>> {
             let start_idx = cursor.position.to_char(text);
 
             if start_idx > 0 {
-                return Some(start_idx - 1);
+                return Some(prev_grapheme_boundary(text, start_idx)..start_idx);
             }
             return None;
         }
 
         if let Some(range) = self.selection_range() {
             self.add_undo_point();
+            self.record_edit(range.clone(), "");
 
             let UndoPoint { text, cursor } = self.current_point();
             text.remove(range.clone());
             cursor.position = Point::from_char(range.start, text);
             cursor.selection = None;
         } else {
-            let char_to_delete = match key {
+            let grapheme_to_delete = match key {
                 DeleteDirection::Forward => {
                     let UndoPoint { text, cursor } = self.current_point();
-                    char_to_delete_next(text, cursor)
+                    grapheme_to_delete_next(text, cursor)
                 }
                 DeleteDirection::Back => {
                     let UndoPoint { text, cursor } = self.current_point();
-                    char_to_delete_previous(text, cursor)
+                    grapheme_to_delete_previous(text, cursor)
                 }
             };
 
-            if let Some(idx) = char_to_delete {
-                if self.current_point().text.char(idx) == '\n' {
+            if let Some(range) = grapheme_to_delete {
+                if self.current_point().text.char(range.start) == '\n' {
                     self.add_undo_point();
                 } else {
                     self.soft_undo_point();
                 }
+                self.record_edit(range.clone(), "");
                 let point = self.current_point();
-                point.cursor.position = Point::from_char(idx, &point.text);
-                point.text.remove(idx..(idx + 1));
+                point.cursor.position = Point::from_char(range.start, &point.text);
+                point.cursor.remembered_x = column_of(&point.cursor.position, &point.text);
+                point.text.remove(range);
             }
         }
     }
@@ -250,6 +404,9 @@ impl Text {
         self.last_added = true;
         let undo_point = self.current_point().clone();
         self.history.truncate(self.index + 1);
+        if self.saved_index.map_or(false, |saved| saved > self.index) {
+            self.saved_index = None;
+        }
         self.history.push(undo_point);
         self.index = self.history.len() - 1;
     }
@@ -258,11 +415,16 @@ impl Text {
         self.last_added = false;
         if self.index > 0 {
             self.index -= 1;
+            self.record_history_change(self.index + 1);
         }
     }
 
     pub fn redo(&mut self) {
+        let previous = self.index;
         self.index = (self.index + 1).min(self.history.len() - 1);
+        if self.index != previous {
+            self.record_history_change(previous);
+        }
     }
 
     fn process_selection(&mut self, selection: Selection) {
@@ -275,16 +437,39 @@ impl Text {
         }
     }
 
+    // Moves by `by` grapheme clusters
     pub fn move_cursor(&mut self, by: i64, selection: Selection) {
         self.process_selection(selection);
         let UndoPoint { cursor, text } = self.current_point();
-        let idx = clamp(
-            cursor.position.to_char(text) as i64 + by,
-            0,
-            text.len_chars() as i64,
-        );
-        cursor.position = Point::from_char(idx as usize, text);
-        cursor.remembered_x = cursor.position.x
+        let mut idx = cursor.position.to_char(text);
+
+        for _ in 0..by.abs() {
+            idx = if by > 0 {
+                next_grapheme_boundary(text, idx)
+            } else {
+                prev_grapheme_boundary(text, idx)
+            };
+        }
+
+        cursor.position = Point::from_char(idx, text);
+        cursor.remembered_x = column_of(&cursor.position, text);
+    }
+
+    fn move_cursor_to_char(&mut self, idx: usize, forward: bool, selection: Selection) {
+        self.process_selection(selection);
+        let UndoPoint { cursor, text } = self.current_point();
+        let mut idx = idx.min(text.len_chars());
+
+        if !is_grapheme_boundary(text, idx) {
+            idx = if forward {
+                next_grapheme_boundary(text, idx)
+            } else {
+                prev_grapheme_boundary(text, idx)
+            };
+        }
+
+        cursor.position = Point::from_char(idx, text);
+        cursor.remembered_x = column_of(&cursor.position, text);
     }
 
     pub fn move_cursor_y(&mut self, by: i64, selection: Selection) {
@@ -300,13 +485,46 @@ impl Text {
         let is_last_line = line_idx + 1 == text.len_lines();
 
         let max = text.line(line_idx).len_chars() - if is_last_line { 0 } else { 1 };
+        let x = char_x_at_column(text.line(line_idx), cursor.remembered_x.max(0) as usize);
 
-        let new_idx =
-            text.line_to_char(line_idx) + clamp(cursor.remembered_x, 0, max as i64) as usize;
+        let new_idx = text.line_to_char(line_idx) + x.min(max);
 
         cursor.position = Point::from_char(new_idx, text);
     }
 
+    // Moves by screen rows, skipping folded lines. The column is kept relative
+    // to the start of the row so it survives moving through wrapped lines
+    pub fn move_cursor_rows(&mut self, by: i64, selection: Selection, map: &DisplayMap) {
+        if map.is_identity() {
+            return self.move_cursor_y(by, selection);
+        }
+
+        self.process_selection(selection);
+        let UndoPoint { cursor, text } = self.current_point();
+
+        let row = map.point_to_row(cursor.position);
+        let current = map.display_line(text, row);
+        let row_column = visual_column(text.line(current.line), current.start) as i64;
+        let goal = (cursor.remembered_x - row_column).max(0) as usize;
+
+        let target_row = clamp(row as i64 + by, 0, map.row_count(text) as i64 - 1) as usize;
+        let target = map.display_line(text, target_row);
+        let line = text.line(target.line);
+
+        let mut x = target.start + char_x_at_column(line.slice(target.start..target.end), goal);
+        // a caret on the break would show up on the next row
+        if x >= target.end && target.end < line.len_chars() {
+            let line_start = text.line_to_char(target.line);
+            x = prev_grapheme_boundary(text, line_start + target.end) - line_start;
+        }
+
+        cursor.position = Point {
+            x: x as i64,
+            y: target.line as i64,
+        };
+        cursor.remembered_x = visual_column(line, target.start) as i64 + goal as i64;
+    }
+
     pub fn move_to_next_word(&mut self, selection: Selection) {
         self.process_selection(selection.clone());
 
@@ -314,7 +532,7 @@ impl Text {
 
         let idx = cursor.position.to_char(text);
         let move_by = next_word(&mut text.chars_at(idx).into_iter());
-        self.move_cursor(move_by, selection);
+        self.move_cursor_to_char(idx + move_by as usize, true, selection);
     }
 
     pub fn move_to_prev_word(&mut self, selection: Selection) {
@@ -329,7 +547,8 @@ impl Text {
         };
 
         let move_by = next_word(&mut iter);
-        self.move_cursor(-move_by, selection);
+        let target = (idx as i64 - move_by).max(0) as usize;
+        self.move_cursor_to_char(target, false, selection);
     }
 
     pub fn move_to_end_of_line(&mut self, selection: Selection) {
@@ -344,7 +563,7 @@ impl Text {
         if !is_last_line {
             cursor.position.x -= 1;
         }
-        cursor.remembered_x = cursor.position.x;
+        cursor.remembered_x = column_of(&cursor.position, text);
     }
 
     pub fn move_to_beginning_of_line(&mut self, selection: Selection) {
@@ -390,6 +609,7 @@ impl Text {
         let str = self.get_selection_str();
         let range = self.selection_range()?;
         self.add_undo_point();
+        self.record_edit(range.clone(), "");
 
         let UndoPoint { text, .. } = self.current_point();
         text.remove(range);
@@ -408,7 +628,9 @@ impl Text {
         cursor.remembered_x = 0;
         let start_idx = Point::to_char(&cursor.position, text);
         let end_idx = start_idx + text.line(cursor.position.y as usize).len_chars();
+        self.record_edit(start_idx..end_idx, "");
 
+        let UndoPoint { text, .. } = self.current_point();
         text.remove(start_idx..end_idx);
         return line_to_remove;
     }
@@ -1,5 +1,9 @@
 use crate::cursor;
-use crate::text::{DeleteDirection, Selection, Text};
+use crate::display_map::DisplayMap;
+use crate::file_format::LineEnding;
+use crate::folding::{Fold, Folds};
+use crate::grapheme::visual_column;
+use crate::text::{DeleteDirection, LineEdit, Selection, Text};
 use cursor::Point;
 
 fn create_text(initial_text: &str) -> Text {
@@ -731,3 +735,291 @@ fn remove_current_line_empty() {
         "should not add undo point if file is empty"
     );
 }
+
+#[test]
+fn move_cursor_over_combining_character() {
+    let mut text = create_text("e\u{301}a");
+    text.move_cursor(1, Selection::NotSelect);
+    assert_eq!(text.get_cursor().position.x, 2);
+
+    text.move_cursor(-1, Selection::NotSelect);
+    assert_eq!(text.get_cursor().position.x, 0);
+}
+
+#[test]
+fn move_cursor_over_emoji_sequence() {
+    let mut text = create_text("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1F1F5}\u{1F1F1}x");
+    text.move_cursor(1, Selection::NotSelect);
+    assert_eq!(text.get_cursor().position.x, 5);
+
+    text.move_cursor(1, Selection::NotSelect);
+    assert_eq!(text.get_cursor().position.x, 7);
+}
+
+#[test]
+fn delete_whole_grapheme() {
+    let mut text = create_text("ae\u{301}b");
+    text.get_cursor().position.x = 3;
+    text.delete_text(DeleteDirection::Back);
+    assert_eq!(text.get_string(), "ab");
+    assert_eq!(text.get_cursor().position.x, 1);
+
+    text.delete_text(DeleteDirection::Forward);
+    assert_eq!(text.get_string(), "a");
+}
+
+#[test]
+fn select_whole_grapheme() {
+    let mut text = create_text("a\u{1F1F5}\u{1F1F1}b");
+    text.move_cursor(1, Selection::NotSelect);
+    text.move_cursor(1, Selection::Select);
+    assert_eq!(text.get_selection_str(), Some("\u{1F1F5}\u{1F1F1}".to_owned()));
+}
+
+#[test]
+fn move_down_keeps_visual_column_with_wide_chars() {
+    let mut text = create_text("\u{4F60}\u{597D}x\nabcdef");
+    text.move_cursor(2, Selection::NotSelect);
+    text.move_cursor_y(1, Selection::NotSelect);
+    assert_eq!(text.get_cursor().position.x, 4);
+
+    text.move_cursor(-1, Selection::NotSelect);
+    text.move_cursor_y(-1, Selection::NotSelect);
+    assert_eq!(text.get_cursor().position.x, 1);
+}
+
+#[test]
+fn move_cursor_by_wrapped_rows() {
+    let mut text = create_text("hello world foo\nend");
+    let mut map = DisplayMap::new();
+    map.update(text.get_text(), text.revision(), &[], Some(8), &Folds::new());
+
+    text.move_cursor(2, Selection::NotSelect);
+    text.move_cursor_rows(1, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.y, 0);
+    assert_eq!(text.get_cursor().position.x, 8);
+
+    text.move_cursor_rows(1, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.x, 14);
+
+    text.move_cursor_rows(1, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.y, 1);
+    assert_eq!(text.get_cursor().position.x, 2);
+
+    text.move_cursor_rows(-2, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.y, 0);
+    assert_eq!(text.get_cursor().position.x, 8);
+}
+
+#[test]
+fn move_cursor_rows_stays_before_break() {
+    let mut text = create_text("abcdefghij\nwxyz");
+    let mut map = DisplayMap::new();
+    map.update(text.get_text(), text.revision(), &[], Some(4), &Folds::new());
+
+    text.move_cursor_y(1, Selection::NotSelect);
+    text.move_to_end_of_line(Selection::NotSelect);
+    text.move_cursor_rows(-2, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.y, 0);
+    assert_eq!(text.get_cursor().position.x, 7);
+}
+
+#[test]
+fn move_cursor_rows_skips_folded_lines() {
+    let mut text = create_text("a:\n  b\n  c\nd");
+    let mut folds = Folds::new();
+    folds.add(Fold { start: 0, end: 2 });
+    let mut map = DisplayMap::new();
+    map.update(text.get_text(), text.revision(), &[], None, &folds);
+
+    text.move_cursor_rows(1, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.y, 3);
+
+    text.move_cursor_rows(-1, Selection::NotSelect, &map);
+    assert_eq!(text.get_cursor().position.y, 0);
+}
+
+#[test]
+fn record_line_edits() {
+    let mut text = create_text("a\nb\nc");
+    text.get_cursor().position = Point { x: 1, y: 0 };
+    text.insert_text("\nx");
+    assert_eq!(
+        text.take_edits(),
+        vec![LineEdit {
+            start: 0,
+            old_end: 1,
+            new_end: 2
+        }]
+    );
+
+    text.undo();
+    assert_eq!(
+        text.take_edits(),
+        vec![LineEdit {
+            start: 1,
+            old_end: 2,
+            new_end: 1
+        }]
+    );
+}
+
+#[test]
+fn tracks_modified_state() {
+    let mut text = create_text("text");
+    assert!(!text.is_modified());
+
+    text.insert_text("a");
+    assert!(text.is_modified());
+
+    text.undo();
+    assert!(!text.is_modified());
+
+    text.redo();
+    assert!(text.is_modified());
+
+    text.mark_saved();
+    assert!(!text.is_modified());
+
+    text.undo();
+    assert!(text.is_modified());
+}
+
+#[test]
+fn undo_back_to_the_saved_text_is_unmodified() {
+    let mut text = create_text("ab");
+    text.move_to_end(Selection::NotSelect);
+    text.insert_text("c");
+    text.mark_saved();
+
+    text.insert_text("d");
+    assert!(text.is_modified());
+
+    text.undo();
+    assert!(!text.is_modified());
+    assert_eq!(text.get_string(), "abc");
+}
+
+#[test]
+fn dropping_the_saved_history_point_stays_modified() {
+    let mut text = create_text("ab");
+    text.move_to_end(Selection::NotSelect);
+    text.insert_text("c");
+    text.mark_saved();
+
+    text.undo();
+    text.insert_text("d");
+    text.undo();
+    assert!(text.is_modified());
+    text.redo();
+    assert!(text.is_modified());
+}
+
+#[test]
+fn forgotten_saved_text_stays_modified() {
+    let mut text = create_text("ab");
+    text.forget_saved();
+    assert!(text.is_modified());
+
+    text.insert_text("c");
+    text.undo();
+    assert!(text.is_modified());
+
+    text.mark_saved();
+    assert!(!text.is_modified());
+}
+
+#[test]
+fn remembers_line_ending() {
+    let mut text = create_text("line 1\r\nline 2\r\n");
+    assert_eq!(text.line_ending, LineEnding::Crlf);
+    assert!(!text.mixed_line_endings);
+
+    text.set_line_ending(LineEnding::Lf);
+    assert!(text.is_modified());
+
+    text.set_line_ending(LineEnding::Crlf);
+    assert!(!text.is_modified());
+}
+
+#[test]
+fn converting_a_mixed_file_drops_kept_crs() {
+    let mut text = create_text("a\r\nb\nc\r\n");
+    assert!(text.mixed_line_endings);
+    assert_eq!(text.to_bytes(), Ok(b"a\r\nb\nc\r\n".to_vec()));
+
+    text.get_cursor().position = Point { x: 1, y: 2 };
+    text.set_line_ending(LineEnding::Crlf);
+    assert_eq!(text.get_text(), "a\nb\nc\n");
+    assert_eq!(text.get_cursor().position.x, 1);
+    assert_eq!(text.get_cursor().position.y, 2);
+    assert_eq!(text.to_bytes(), Ok(b"a\r\nb\r\nc\r\n".to_vec()));
+}
+
+#[test]
+fn lone_cr_stays_in_line() {
+    let text = create_text("a\rb\nc\n");
+
+    assert_eq!(text.get_text().len_lines(), 3);
+    assert_eq!(text.line_ending, LineEnding::Lf);
+    assert!(text.mixed_line_endings);
+}
+
+#[test]
+fn decodes_and_saves_in_file_encoding() {
+    let text = create_text("\u{FEFF}line\r\n");
+    assert_eq!(text.get_text(), "line\n");
+    assert_eq!(text.to_bytes(), Ok("\u{FEFF}line\r\n".as_bytes().to_vec()));
+
+    let bytes = b"\xFF\xFEa\x00\r\x00\n\x00";
+    let text = Text::new(&bytes[..]);
+
+    assert_eq!(text.get_text(), "a\n");
+    assert_eq!(text.line_ending, LineEnding::Crlf);
+    assert_eq!(text.to_bytes(), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn invalid_bytes_survive_a_round_trip() {
+    let bytes = b"ok \xC3\xA9\r\nbad \xFF\xFE\r\n\xC0";
+    let text = Text::new(&bytes[..]);
+
+    assert_eq!(text.get_text().len_lines(), 3);
+    assert_eq!(text.to_bytes(), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn utf16_unpaired_surrogates_survive_a_round_trip() {
+    // the low byte of the surrogate is 0x0D, which isn't a '\r'
+    let bytes = b"\xFF\xFEa\x00\x0D\xD8b\x00";
+    let text = Text::new(&bytes[..]);
+
+    assert_eq!(text.get_text().len_lines(), 1);
+    assert_eq!(text.to_bytes(), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn utf16_odd_last_byte_survives_a_round_trip() {
+    let bytes = b"\xFF\xFEa\x00\x0D";
+    let text = Text::new(&bytes[..]);
+
+    assert_eq!(text.to_bytes(), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn lone_cr_in_utf16_saves_as_a_whole_unit() {
+    let bytes = b"\xFF\xFEa\x00\r\x00b\x00\n\x00";
+    let text = Text::new(&bytes[..]);
+
+    assert_eq!(text.get_text().len_lines(), 2);
+    assert_eq!(text.to_bytes(), Ok(bytes.to_vec()));
+}
+
+#[test]
+fn raw_bytes_move_as_one_unit() {
+    let mut text = Text::new(&b"\xC3\xA9\xFFb"[..]);
+    text.move_cursor(2, Selection::NotSelect);
+
+    assert_eq!(text.get_cursor().position.x, 2);
+    assert_eq!(visual_column(text.get_text().line(0), 2), 5);
+}
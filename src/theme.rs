@@ -0,0 +1,127 @@
+pub type Color = [f32; 3];
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0]
+}
+
+// `rrggbb`, a leading `#` is allowed but starts a comment in the config file
+pub fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Some(rgb(channel(0), channel(2), channel(4)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub text: Color,
+    pub line_number: Color,
+    pub current_line_number: Color,
+    pub current_line: Color,
+    pub occurrence: Color,
+    pub selection: Color,
+    pub cursor: Color,
+    // glyph under a block cursor
+    pub cursor_text: Color,
+    pub whitespace: Color,
+    pub raw_byte: Color,
+    pub trailing_whitespace: Color,
+    pub indent_guide: Color,
+    pub ruler: Color,
+    pub minimap_background: Color,
+    pub minimap_viewport: Color,
+    pub minimap_text: Color,
+    pub scrollbar_track: Color,
+    pub scrollbar_thumb: Color,
+    pub marker_selection: Color,
+    pub status_bar: Color,
+    pub status_text: Color,
+    pub picker_background: Color,
+    pub picker_selected: Color,
+    pub diff_added: Color,
+    pub diff_removed: Color,
+    pub tab_bar: Color,
+    pub tab_active: Color,
+    pub tab_text: Color,
+    pub tab_active_text: Color,
+}
+
+impl Theme {
+    pub fn new() -> Theme {
+        Theme {
+            background: rgb(30, 30, 30),
+            text: rgb(213, 213, 213),
+            line_number: rgb(110, 110, 110),
+            current_line_number: rgb(213, 213, 213),
+            current_line: rgb(40, 40, 40),
+            occurrence: rgb(58, 62, 72),
+            selection: rgb(128, 128, 128),
+            cursor: rgb(255, 255, 255),
+            cursor_text: rgb(30, 30, 30),
+            whitespace: rgb(75, 75, 75),
+            raw_byte: rgb(230, 110, 90),
+            trailing_whitespace: rgb(90, 40, 40),
+            indent_guide: rgb(55, 55, 55),
+            ruler: rgb(50, 50, 50),
+            minimap_background: rgb(37, 37, 38),
+            minimap_viewport: rgb(60, 60, 62),
+            minimap_text: rgb(140, 140, 140),
+            scrollbar_track: rgb(40, 40, 40),
+            scrollbar_thumb: rgb(90, 90, 90),
+            marker_selection: rgb(153, 153, 153),
+            status_bar: rgb(0, 122, 204),
+            status_text: rgb(255, 255, 255),
+            picker_background: rgb(37, 37, 38),
+            picker_selected: rgb(4, 57, 94),
+            diff_added: rgb(120, 190, 120),
+            diff_removed: rgb(230, 110, 90),
+            tab_bar: rgb(37, 37, 38),
+            tab_active: rgb(30, 30, 30),
+            tab_text: rgb(140, 140, 140),
+            tab_active_text: rgb(255, 255, 255),
+        }
+    }
+
+    // Sets the color called `name`, false if there is no such color
+    pub fn set(&mut self, name: &str, color: Color) -> bool {
+        let field = match name {
+            "background" => &mut self.background,
+            "text" => &mut self.text,
+            "line_number" => &mut self.line_number,
+            "current_line_number" => &mut self.current_line_number,
+            "current_line" => &mut self.current_line,
+            "occurrence" => &mut self.occurrence,
+            "selection" => &mut self.selection,
+            "cursor" => &mut self.cursor,
+            "cursor_text" => &mut self.cursor_text,
+            "whitespace" => &mut self.whitespace,
+            "raw_byte" => &mut self.raw_byte,
+            "trailing_whitespace" => &mut self.trailing_whitespace,
+            "indent_guide" => &mut self.indent_guide,
+            "ruler" => &mut self.ruler,
+            "minimap_background" => &mut self.minimap_background,
+            "minimap_viewport" => &mut self.minimap_viewport,
+            "minimap_text" => &mut self.minimap_text,
+            "scrollbar_track" => &mut self.scrollbar_track,
+            "scrollbar_thumb" => &mut self.scrollbar_thumb,
+            "marker_selection" => &mut self.marker_selection,
+            "status_bar" => &mut self.status_bar,
+            "status_text" => &mut self.status_text,
+            "picker_background" => &mut self.picker_background,
+            "picker_selected" => &mut self.picker_selected,
+            "diff_added" => &mut self.diff_added,
+            "diff_removed" => &mut self.diff_removed,
+            "tab_bar" => &mut self.tab_bar,
+            "tab_active" => &mut self.tab_active,
+            "tab_text" => &mut self.tab_text,
+            "tab_active_text" => &mut self.tab_active_text,
+            _ => return false,
+        };
+        *field = color;
+        true
+    }
+}
@@ -0,0 +1,241 @@
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::file_io::{resolve_symlinks, FileStamp};
+
+// How often the file is looked at where it can't be watched
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Tells when the file at a path may have changed, the change still has to be
+// checked. `wake` is called from the watching thread to get the event loop
+// to look.
+pub struct Watcher {
+    changes: Receiver<()>,
+    // closing these wakes the thread and makes it stop, the pipe is what the
+    // inotify thread waits on
+    stop: Option<Sender<()>>,
+    #[cfg(target_os = "linux")]
+    stop_pipe: Option<std::fs::File>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    pub fn new(path: &Path, wake: fn()) -> Watcher {
+        let (sender, changes) = channel();
+        let mut watcher = Watcher {
+            changes,
+            stop: None,
+            #[cfg(target_os = "linux")]
+            stop_pipe: None,
+            thread: None,
+        };
+        // a buffer without a path has nothing to watch
+        if path.as_os_str().is_empty() {
+            return watcher;
+        }
+        let path = resolve_symlinks(path).unwrap_or_else(|_| path.to_path_buf());
+        let (stop, stopped) = channel();
+        watcher.stop = Some(stop);
+        #[cfg(target_os = "linux")]
+        let pipe = inotify::pipe();
+        #[cfg(target_os = "linux")]
+        let stopped_pipe = match pipe {
+            Some((read, write)) => {
+                watcher.stop_pipe = Some(write);
+                Some(read)
+            }
+            None => None,
+        };
+
+        watcher.thread = Some(thread::spawn(move || {
+            #[cfg(target_os = "linux")]
+            {
+                if let Some(stopped_pipe) = stopped_pipe {
+                    if inotify::watch(&path, &sender, wake, &stopped_pipe) {
+                        return;
+                    }
+                }
+            }
+            poll(&path, &sender, wake, &stopped);
+        }));
+        watcher
+    }
+
+    pub fn has_changes(&self) -> bool {
+        let mut changed = false;
+        while self.changes.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+}
+
+impl Drop for Watcher {
+    // The thread would otherwise only find out at the next change to the file
+    fn drop(&mut self) {
+        self.stop.take();
+        #[cfg(target_os = "linux")]
+        self.stop_pipe.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Stops once `stopped` is closed or nobody listens
+fn poll(path: &Path, sender: &Sender<()>, wake: fn(), stopped: &Receiver<()>) {
+    let mut stamp = FileStamp::of(path);
+    loop {
+        match stopped.recv_timeout(POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return,
+        }
+        let new_stamp = FileStamp::of(path);
+        if new_stamp != stamp {
+            stamp = new_stamp;
+            if sender.send(()).is_err() {
+                return;
+            }
+            wake();
+        }
+    }
+}
+
+// Names in a buffer of `inotify_event`s, which are a fixed header followed by
+// a NUL padded name `len` bytes long
+pub fn event_names(buffer: &[u8]) -> Vec<OsString> {
+    const HEADER: usize = 16;
+    let mut names = Vec::new();
+    let mut offset = 0;
+    while offset + HEADER <= buffer.len() {
+        let len_bytes = [
+            buffer[offset + 12],
+            buffer[offset + 13],
+            buffer[offset + 14],
+            buffer[offset + 15],
+        ];
+        let len = u32::from_ne_bytes(len_bytes) as usize;
+        let end = (offset + HEADER + len).min(buffer.len());
+        let name = &buffer[offset + HEADER..end];
+        let name = match name.iter().position(|&byte| byte == 0) {
+            Some(nul) => &name[..nul],
+            None => name,
+        };
+        names.push(os_string(name));
+        offset = end;
+    }
+    names
+}
+
+#[cfg(unix)]
+fn os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::path::Path;
+    use std::sync::mpsc::Sender;
+
+    use super::event_names;
+
+    // Read and write end, the read end sees the end of the pipe once the
+    // write end is closed
+    pub fn pipe() -> Option<(File, File)> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+            return None;
+        }
+        Some(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+    }
+
+    // Waits until `events` can be read, false when `stopped` woke it instead
+    fn wait(events: &File, stopped: &File) -> bool {
+        let mut fds = [
+            libc::pollfd {
+                fd: events.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: stopped.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        loop {
+            if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } >= 0 {
+                return fds[1].revents == 0;
+            }
+            if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return false;
+            }
+        }
+    }
+
+    // Watches the directory, as tools that replace the file by renaming a new
+    // one over it would end a watch on the file itself. False when the
+    // directory can't be watched, true once stopped or nobody listens
+    // anymore.
+    pub fn watch(path: &Path, sender: &Sender<()>, wake: fn(), stopped: &File) -> bool {
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let name = match path.file_name() {
+            Some(name) => name.to_owned(),
+            None => return false,
+        };
+        let dir = match CString::new(dir.as_os_str().as_bytes()) {
+            Ok(dir) => dir,
+            Err(_) => return false,
+        };
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return false;
+        }
+        // closes the descriptor when dropped
+        let mut events = unsafe { File::from_raw_fd(fd) };
+        // writes in place are seen once the file is closed, not halfway
+        let mask = libc::IN_CLOSE_WRITE
+            | libc::IN_MOVED_FROM
+            | libc::IN_MOVED_TO
+            | libc::IN_CREATE
+            | libc::IN_DELETE;
+        if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+            return false;
+        }
+
+        let mut buffer = [0; 4096];
+        loop {
+            if !wait(&events, stopped) {
+                return true;
+            }
+            let len = match events.read(&mut buffer) {
+                Ok(len) if len > 0 => len,
+                _ => return false,
+            };
+            if event_names(&buffer[..len]).contains(&name) {
+                if sender.send(()).is_err() {
+                    return true;
+                }
+                wake();
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::file_io::write_atomically;
+use crate::watcher::{event_names, Watcher};
+
+fn event(name: &str, padded_len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; 12];
+    bytes.extend_from_slice(&(padded_len as u32).to_ne_bytes());
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.resize(16 + padded_len, 0);
+    bytes
+}
+
+#[test]
+fn reads_event_names() {
+    let mut buffer = event("a.rs", 16);
+    buffer.extend(event("", 0));
+    buffer.extend(event("longer_name.txt", 16));
+
+    assert_eq!(event_names(&buffer), vec!["a.rs", "", "longer_name.txt"]);
+}
+
+fn wait_for_changes(watcher: &Watcher) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if watcher.has_changes() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[test]
+fn notices_files_replaced_by_rename() {
+    let dir = std::env::temp_dir().join(format!("editor-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.txt");
+    fs::write(&path, "one").unwrap();
+
+    let watcher = Watcher::new(&path, || {});
+    // the watch starts on its own thread
+    thread::sleep(Duration::from_millis(100));
+    assert!(!watcher.has_changes());
+
+    write_atomically(&path, b"two").unwrap();
+    assert!(wait_for_changes(&watcher));
+
+    fs::write(&path, "three").unwrap();
+    assert!(wait_for_changes(&watcher));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dropping_stops_the_thread() {
+    let dir = std::env::temp_dir().join(format!("editor-unwatch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.txt");
+    fs::write(&path, "one").unwrap();
+
+    let watcher = Watcher::new(&path, || {});
+    thread::sleep(Duration::from_millis(100));
+    // joins the thread, which would wait for the next change without a stop
+    let start = Instant::now();
+    drop(watcher);
+    assert!(start.elapsed() < Duration::from_millis(500));
+    fs::remove_dir_all(&dir).unwrap();
+}
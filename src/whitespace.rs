@@ -0,0 +1,45 @@
+use ropey::{Rope, RopeSlice};
+
+use crate::folding::indent_of;
+use crate::grapheme::TAB_WIDTH;
+
+// Blank lines look this far for the block they are in
+const GUIDE_SCAN_LINES: usize = 100;
+
+// Chars of the whitespace at the end of a line, before its line break. Lines
+// with nothing but whitespace count as trailing whitespace too.
+pub fn trailing_whitespace(line: RopeSlice) -> Option<std::ops::Range<usize>> {
+    let mut end = line.len_chars();
+    while end > 0 && (line.char(end - 1) == '\n' || line.char(end - 1) == '\r') {
+        end -= 1;
+    }
+
+    let mut start = end;
+    while start > 0 && line.char(start - 1).is_whitespace() {
+        start -= 1;
+    }
+
+    if start < end {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+// Columns to draw indent guides at, one per indentation level. Blank lines
+// get the guides of the lines around them so guides don't break at them.
+pub fn indent_guides(text: &Rope, line: usize) -> Vec<usize> {
+    let indent = match indent_of(text.line(line)) {
+        Some(indent) => indent,
+        None => {
+            let above = (line.saturating_sub(GUIDE_SCAN_LINES)..line)
+                .rev()
+                .find_map(|l| indent_of(text.line(l)));
+            let below = (line + 1..text.len_lines().min(line + GUIDE_SCAN_LINES))
+                .find_map(|l| indent_of(text.line(l)));
+            above.unwrap_or(0).min(below.unwrap_or(0))
+        }
+    };
+
+    (0..indent).step_by(TAB_WIDTH).collect()
+}
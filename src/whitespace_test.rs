@@ -0,0 +1,32 @@
+use ropey::Rope;
+
+use crate::whitespace::{indent_guides, trailing_whitespace};
+
+#[test]
+fn finds_trailing_whitespace() {
+    let text = Rope::from_str("a  \nb\n \t\r\n  c \t");
+
+    assert_eq!(trailing_whitespace(text.line(0)), Some(1..3));
+    assert_eq!(trailing_whitespace(text.line(1)), None);
+    assert_eq!(trailing_whitespace(text.line(2)), Some(0..2));
+    assert_eq!(trailing_whitespace(text.line(3)), Some(3..5));
+}
+
+#[test]
+fn guides_per_indent_level() {
+    let text = Rope::from_str("a\n    b\n        c\n\t\td\n      e");
+
+    assert_eq!(indent_guides(&text, 0), vec![]);
+    assert_eq!(indent_guides(&text, 1), vec![0]);
+    assert_eq!(indent_guides(&text, 2), vec![0, 4]);
+    assert_eq!(indent_guides(&text, 3), vec![0, 4]);
+    assert_eq!(indent_guides(&text, 4), vec![0, 4]);
+}
+
+#[test]
+fn blank_lines_continue_guides() {
+    let text = Rope::from_str("a\n        b\n\n    c\n\nd");
+
+    assert_eq!(indent_guides(&text, 2), vec![0]);
+    assert_eq!(indent_guides(&text, 4), vec![]);
+}